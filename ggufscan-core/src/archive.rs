@@ -0,0 +1,111 @@
+//! Compressing cold models with zstd into an archive directory, below the
+//! path they had like the quarantine, and restoring them. The `zstd` tool
+//! does the work, it has to be installed.
+
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    format::is_model_extension,
+    quarantine::{data_dir, original_path, quarantine_path, remove_empty_parents},
+};
+
+/// What archives are named, the model's own name with this added.
+pub const ARCHIVE_EXTENSION: &str = "zst";
+
+/// `$XDG_DATA_HOME/ggufscan/archive`, `%LOCALAPPDATA%` on Windows and
+/// `~/.local/share` otherwise.
+pub fn default_archive_dir() -> Option<PathBuf> {
+    Some(data_dir()?.join("archive"))
+}
+
+/// Whether the file is named like an archived model, `model.gguf.zst`.
+pub fn is_archived_model(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(ARCHIVE_EXTENSION))
+        && path
+            .file_stem()
+            .and_then(|stem| Path::new(stem).extension())
+            .is_some_and(is_model_extension)
+}
+
+/// Compresses the file into `archive` below its absolute path and removes
+/// it, `/models/a.gguf` ends up as `<archive>/models/a.gguf.zst`.
+pub fn archive_file(path: &Path, archive: &Path) -> io::Result<PathBuf> {
+    let dest = with_archive_extension(&quarantine_path(path, archive)?);
+    if dest.symlink_metadata().is_ok() {
+        return Err(already_exists(&dest));
+    }
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    zstd(&["-q", "-T0"], path, &dest)?;
+    fs::remove_file(path)?;
+    Ok(dest)
+}
+
+/// Decompresses an archive back to where the model was and removes it.
+/// Archives found outside the archive directory are restored next to
+/// themselves.
+pub fn restore_archive(path: &Path, archive: &Path) -> io::Result<PathBuf> {
+    let stem = path.with_extension("");
+    let dest = original_path(&stem, archive).unwrap_or(stem);
+    if dest.symlink_metadata().is_ok() {
+        return Err(already_exists(&dest));
+    }
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    zstd(&["-d", "-q"], path, &dest)?;
+    fs::remove_file(path)?;
+    remove_empty_parents(path, archive);
+    Ok(dest)
+}
+
+// Writes next to the destination first, an interrupted run leaves no
+// half-written file under the final name
+fn zstd(args: &[&str], from: &Path, to: &Path) -> io::Result<()> {
+    let mut partial = to.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let status = Command::new("zstd")
+        .args(args)
+        .arg("-f")
+        .arg("-o")
+        .arg(&partial)
+        .arg("--")
+        .arg(from)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                io::Error::new(io::ErrorKind::NotFound, "zstd not found, install it first")
+            }
+            _ => e,
+        })?;
+    if !status.success() {
+        fs::remove_file(&partial).ok();
+        return Err(io::Error::other(format!("zstd failed ({})", status)));
+    }
+    fs::rename(&partial, to)
+}
+
+fn with_archive_extension(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(ARCHIVE_EXTENSION);
+    PathBuf::from(name)
+}
+
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} already exists", path.display()),
+    )
+}
@@ -0,0 +1,146 @@
+//! Models kept as a directory of files rather than a single one, Core ML
+//! bundles and quantized weights like MLX, GPTQ, AWQ and exl2, reported as
+//! one entry sized by their contents.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::ModelFormat;
+
+// Compiled models and the packages Xcode compiles them from
+const CORE_ML_EXTENSIONS: &[&str] = &["mlmodelc", "mlpackage"];
+// A config bigger than this isn't a model config
+const MAX_CONFIG_LEN: u64 = 1024 * 1024;
+
+// The files below a bundle directory, symlinks are left out like they are
+// from the totals of single files
+#[derive(Debug, Default)]
+pub(crate) struct BundleContents {
+    pub(crate) size: u64,
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) accessed: Option<SystemTime>,
+}
+
+// Whether the directory holds a model as a whole. Core ML bundles go by
+// their name, the others by the files next to the weights of a model in
+// the Hugging Face layout. A snapshot of the Hugging Face cache links to its
+// blobs and isn't one, the blobs are reported on their own.
+pub(crate) fn detect_bundle(dir: &Path) -> Option<ModelFormat> {
+    let core_ml = dir.extension().is_some_and(|extension| {
+        CORE_ML_EXTENSIONS
+            .iter()
+            .any(|known| extension.eq_ignore_ascii_case(known))
+    });
+    if core_ml {
+        return Some(ModelFormat::CoreMl);
+    }
+    detect_weights_dir(dir)
+}
+
+// Quantized weights with `config.json`:
+// - MLX, by the `quantization` section `mlx_lm` writes into it, or by the
+//   `weights.npz` of older conversions
+// - GPTQ and AWQ, by the `quantize_config.json` of AutoGPTQ, the
+//   `quant_config.json` of AutoAWQ or the `quant_method` both write into
+//   its `quantization_config`
+// - exl2, by the `quant_method` exllamav2 writes or the `measurement.json`
+//   it leaves next to the weights
+fn detect_weights_dir(dir: &Path) -> Option<ModelFormat> {
+    let config = dir.join("config.json");
+    let metadata = config.symlink_metadata().ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_CONFIG_LEN {
+        return None;
+    }
+    let (mut safetensors, mut npz) = (false, false);
+    let mut quantize_config = None;
+    let mut measurement = false;
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        if !entry.file_type().is_ok_and(|kind| kind.is_file()) {
+            continue;
+        }
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        safetensors |= name.ends_with(".safetensors");
+        npz |= name.starts_with("weights") && name.ends_with(".npz");
+        measurement |= name == "measurement.json";
+        match name.as_ref() {
+            "quantize_config.json" => quantize_config = Some(ModelFormat::Gptq),
+            "quant_config.json" => quantize_config = Some(ModelFormat::Awq),
+            _ => {}
+        }
+    }
+    if npz {
+        return Some(ModelFormat::Mlx);
+    }
+    if !safetensors {
+        return None;
+    }
+
+    let config = read_json(&config)?;
+    if let Some(quantization) = config.get("quantization") {
+        if quantization.get("group_size").is_some() && quantization.get("bits").is_some() {
+            return Some(ModelFormat::Mlx);
+        }
+    }
+    let method = config
+        .get("quantization_config")
+        .and_then(|quantization| quantization.get("quant_method"))
+        .and_then(serde_json::Value::as_str)
+        .map(str::to_ascii_lowercase);
+    match method.as_deref() {
+        Some("gptq") => return Some(ModelFormat::Gptq),
+        Some("awq") => return Some(ModelFormat::Awq),
+        Some("exl2") => return Some(ModelFormat::Exl2),
+        _ => {}
+    }
+    // AutoGPTQ writes the same file for AWQ models it quantized
+    if quantize_config == Some(ModelFormat::Gptq) {
+        let method = read_json(&dir.join("quantize_config.json"))
+            .and_then(|config| Some(config.get("quant_method")?.as_str()?.to_ascii_lowercase()));
+        if method.as_deref() == Some("awq") {
+            return Some(ModelFormat::Awq);
+        }
+    }
+    quantize_config.or_else(|| measurement.then_some(ModelFormat::Exl2))
+}
+
+fn read_json(path: &Path) -> Option<serde_json::Value> {
+    let metadata = path.metadata().ok()?;
+    if metadata.len() > MAX_CONFIG_LEN {
+        return None;
+    }
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+// The bundle the path is in or is, looking no further up than the root
+pub(crate) fn bundle_containing(path: &Path, root: &Path) -> Option<(PathBuf, ModelFormat)> {
+    path.ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .find_map(|dir| {
+            let format = detect_bundle(dir)?;
+            Some((dir.to_owned(), format))
+        })
+}
+
+// Sizes add up and the times are those of the latest file
+pub(crate) fn bundle_contents(dir: &Path) -> io::Result<BundleContents> {
+    let mut contents = BundleContents::default();
+    let mut pending = vec![dir.to_owned()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if metadata.is_file() {
+                contents.size += metadata.len();
+                contents.modified = contents.modified.max(metadata.modified().ok());
+                contents.accessed = contents.accessed.max(metadata.accessed().ok());
+            }
+        }
+    }
+    Ok(contents)
+}
@@ -0,0 +1,149 @@
+//! Results of earlier scans, so unchanged files are shown right away and
+//! unchanged directories don't have to be inspected again.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{GgufHeader, ModelFormat, ScanOptions};
+
+// Bumped whenever the records change shape, older caches are dropped
+const CACHE_VERSION: u32 = 4;
+
+/// Where the scan cache is kept unless another path is given.
+pub fn default_cache_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(dir.join("ggufscan").join("scan-cache.jsonl"))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct CachedFile {
+    pub(crate) path: PathBuf,
+    pub(crate) size: u64,
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) format: ModelFormat,
+    pub(crate) header: Option<GgufHeader>,
+    pub(crate) corrupt: Option<String>,
+    #[serde(default)]
+    pub(crate) packed: Vec<String>,
+}
+
+// One line of the cache file
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Record {
+    Version { version: u32 },
+    File(CachedFile),
+    // Directories walked completely, a changed mtime means entries were
+    // added, removed or renamed since
+    Dir { path: PathBuf, modified: SystemTime },
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ScanCache {
+    files: HashMap<PathBuf, CachedFile>,
+    dirs: HashMap<PathBuf, SystemTime>,
+}
+
+impl ScanCache {
+    // A missing or outdated cache is the same as an empty one, lines that
+    // fail to parse are skipped
+    pub(crate) fn load(path: &Path) -> Self {
+        let mut cache = Self::default();
+        let Ok(file) = fs::File::open(path) else {
+            return cache;
+        };
+        let mut lines = io::BufReader::new(file).lines().map_while(Result::ok);
+
+        let version = lines
+            .next()
+            .and_then(|line| serde_json::from_str::<Record>(&line).ok());
+        if !matches!(version, Some(Record::Version { version }) if version == CACHE_VERSION) {
+            return cache;
+        }
+
+        for line in lines {
+            match serde_json::from_str(&line) {
+                Ok(Record::File(file)) => {
+                    cache.files.insert(file.path.clone(), file);
+                }
+                Ok(Record::Dir { path, modified }) => {
+                    cache.dirs.insert(path, modified);
+                }
+                Ok(Record::Version { .. }) | Err(_) => {}
+            }
+        }
+        cache
+    }
+
+    pub(crate) fn files(&self) -> impl Iterator<Item = &CachedFile> {
+        self.files.values()
+    }
+
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    pub(crate) fn is_unchanged_dir(&self, path: &Path, modified: Option<SystemTime>) -> bool {
+        modified.is_some() && self.dirs.get(path) == modified.as_ref()
+    }
+
+    // Number of directories the earlier scans walked below the root
+    pub(crate) fn dirs_below(&self, root: &Path, options: &ScanOptions) -> usize {
+        self.dirs
+            .keys()
+            .filter(|path| path.starts_with(root) && options.within_depth(path))
+            .count()
+    }
+
+    // Replaces everything the scan covered with its results, entries under
+    // other roots or deeper than the scan went are kept for later scans
+    pub(crate) fn save(
+        mut self,
+        path: &Path,
+        options: &ScanOptions,
+        files: Vec<CachedFile>,
+        dirs: Vec<(PathBuf, SystemTime)>,
+    ) -> io::Result<()> {
+        let scanned = |path: &Path| {
+            options.roots.iter().any(|root| path.starts_with(root)) && options.within_depth(path)
+        };
+        self.files.retain(|path, _| !scanned(path));
+        self.dirs.retain(|path, _| !scanned(path));
+        self.files
+            .extend(files.into_iter().map(|file| (file.path.clone(), file)));
+        self.dirs.extend(dirs);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Written next to the cache and moved over it, so an interrupted
+        // write never leaves a truncated cache behind
+        let temp = path.with_extension("jsonl.tmp");
+        let mut writer = io::BufWriter::new(fs::File::create(&temp)?);
+        let mut write = |record: &Record| -> io::Result<()> {
+            serde_json::to_writer(&mut writer, record)?;
+            writeln!(writer)
+        };
+        write(&Record::Version {
+            version: CACHE_VERSION,
+        })?;
+        for file in self.files.into_values() {
+            write(&Record::File(file))?;
+        }
+        for (path, modified) in self.dirs {
+            write(&Record::Dir { path, modified })?;
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(temp, path)
+    }
+}
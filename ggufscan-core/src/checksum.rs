@@ -0,0 +1,130 @@
+//! BLAKE3 and SHA256 checksums of model files, for comparing against the
+//! ones published with a download, kept between runs for unchanged files.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io::{self, BufRead, Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{cache::default_cache_path, scan::open_untouched, FileInfo};
+
+/// Hex digests of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksums {
+    pub blake3: String,
+    pub sha256: String,
+}
+
+/// Reads the file once for both hashes, `progress` gets the bytes hashed so
+/// far.
+pub fn checksum_file(path: &Path, mut progress: impl FnMut(u64)) -> io::Result<Checksums> {
+    let mut file = open_untouched(path)?;
+    let mut blake3 = blake3::Hasher::new();
+    let mut sha256 = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    let mut hashed = 0;
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        blake3.update(&buffer[..read]);
+        sha256.update(&buffer[..read]);
+        hashed += read as u64;
+        progress(hashed);
+    }
+    let mut hex = String::with_capacity(64);
+    for byte in sha256.finalize() {
+        write!(hex, "{:02x}", byte).expect("writing to a string");
+    }
+    Ok(Checksums {
+        blake3: blake3.finalize().to_hex().to_string(),
+        sha256: hex,
+    })
+}
+
+/// Next to the scan cache, `checksums.jsonl`.
+pub fn default_checksum_cache_path() -> Option<PathBuf> {
+    Some(default_cache_path()?.with_file_name("checksums.jsonl"))
+}
+
+// One line of the cache file, later lines win
+#[derive(Serialize, Deserialize)]
+struct Record {
+    path: PathBuf,
+    size: u64,
+    modified: Option<SystemTime>,
+    #[serde(flatten)]
+    checksums: Checksums,
+}
+
+/// Checksums computed earlier, only handed out while the file has the size
+/// and modification time it had then.
+#[derive(Debug, Default)]
+pub struct ChecksumCache {
+    path: Option<PathBuf>,
+    files: HashMap<PathBuf, (u64, Option<SystemTime>, Checksums)>,
+}
+
+impl ChecksumCache {
+    /// A missing cache is the same as an empty one, lines that fail to parse
+    /// are skipped.
+    pub fn load(path: &Path) -> Self {
+        let mut cache = Self {
+            path: Some(path.to_owned()),
+            files: HashMap::new(),
+        };
+        let Ok(file) = fs::File::open(path) else {
+            return cache;
+        };
+        for line in io::BufReader::new(file).lines().map_while(Result::ok) {
+            if let Ok(record) = serde_json::from_str::<Record>(&line) {
+                cache.files.insert(
+                    record.path,
+                    (record.size, record.modified, record.checksums),
+                );
+            }
+        }
+        cache
+    }
+
+    pub fn get(&self, file: &FileInfo) -> Option<&Checksums> {
+        let (size, modified, checksums) = self.files.get(&file.path)?;
+        (*size == file.size && *modified == file.modified).then_some(checksums)
+    }
+
+    /// Keeps the checksums, appending them to the cache file when there is
+    /// one.
+    pub fn insert(&mut self, file: &FileInfo, checksums: Checksums) -> io::Result<()> {
+        self.files.insert(
+            file.path.clone(),
+            (file.size, file.modified, checksums.clone()),
+        );
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_string(&Record {
+            path: file.path.clone(),
+            size: file.size,
+            modified: file.modified,
+            checksums,
+        })?;
+        line.push('\n');
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(line.as_bytes())
+    }
+}
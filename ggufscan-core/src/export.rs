@@ -0,0 +1,224 @@
+//! Writing scan results as plain text, JSON, CSV or a Markdown report.
+
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    format_date, format_size,
+    report::{write_markdown, ReportGroup},
+    unique_size, FileInfo, GgufHeader, ModelFormat, ScanMessage,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Text,
+    Json,
+    Csv,
+    Markdown(ReportGroup),
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Text => "txt",
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Markdown(_) => "md",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportEntry<'a> {
+    path: &'a Path,
+    root: &'a Path,
+    format: ModelFormat,
+    size: u64,
+    /// Modification time in seconds since the Unix epoch
+    modified: Option<u64>,
+    /// Last access time in seconds since the Unix epoch
+    accessed: Option<u64>,
+    gguf_version: Option<u32>,
+    tensor_count: Option<u64>,
+    architecture: Option<&'a str>,
+    quantization: Option<&'static str>,
+    parameter_count: Option<u64>,
+    /// `7B`, `8x7B` and the like
+    size_class: Option<String>,
+    corrupt: Option<&'a str>,
+}
+
+impl<'a> From<&'a FileInfo> for ExportEntry<'a> {
+    fn from(file: &'a FileInfo) -> Self {
+        Self {
+            path: &file.path,
+            root: &file.root,
+            format: file.format,
+            size: file.size,
+            modified: file.modified.map(unix_seconds),
+            accessed: file.accessed.map(unix_seconds),
+            gguf_version: file.header.as_ref().map(|header| header.version),
+            tensor_count: file.header.as_ref().map(|header| header.tensor_count),
+            architecture: file.header.as_ref().and_then(GgufHeader::architecture),
+            quantization: file.header.as_ref().and_then(GgufHeader::quantization),
+            parameter_count: file.header.as_ref().map(|header| header.parameter_count),
+            size_class: file.header.as_ref().and_then(GgufHeader::size_class),
+            corrupt: file.corrupt.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportDocument<'a> {
+    roots: &'a [PathBuf],
+    total_size: u64,
+    files: Vec<ExportEntry<'a>>,
+}
+
+// One line of `--progress ndjson`, a `ScanMessage` with its kind in `event`
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum ExportEvent<'a> {
+    File {
+        #[serde(flatten)]
+        file: ExportEntry<'a>,
+    },
+    CacheLoaded {
+        files: usize,
+    },
+    Directory {
+        path: &'a str,
+    },
+    Progress {
+        done: usize,
+        total: usize,
+        files: usize,
+    },
+    RootProgress {
+        root: &'a Path,
+        done: usize,
+        total: usize,
+        files: usize,
+    },
+    RootDone {
+        root: &'a Path,
+    },
+    Done,
+    CheckingDuplicates,
+    DuplicateGroup {
+        paths: &'a [PathBuf],
+    },
+    DuplicatesDone,
+    Watching,
+    Removed {
+        path: &'a Path,
+    },
+    Cancelled,
+    Error {
+        message: &'a str,
+    },
+}
+
+impl<'a> From<&'a ScanMessage> for ExportEvent<'a> {
+    fn from(message: &'a ScanMessage) -> Self {
+        match message {
+            ScanMessage::File(file) => ExportEvent::File {
+                file: ExportEntry::from(&**file),
+            },
+            ScanMessage::CacheLoaded(files) => ExportEvent::CacheLoaded { files: *files },
+            ScanMessage::Directory(path) => ExportEvent::Directory { path },
+            ScanMessage::Progress(progress) => ExportEvent::Progress {
+                done: progress.done,
+                total: progress.total,
+                files: progress.files,
+            },
+            ScanMessage::RootProgress(root, progress) => ExportEvent::RootProgress {
+                root,
+                done: progress.done,
+                total: progress.total,
+                files: progress.files,
+            },
+            ScanMessage::RootDone(root) => ExportEvent::RootDone { root },
+            ScanMessage::Done => ExportEvent::Done,
+            ScanMessage::CheckingDuplicates => ExportEvent::CheckingDuplicates,
+            ScanMessage::DuplicateGroup(paths) => ExportEvent::DuplicateGroup { paths },
+            ScanMessage::DuplicatesDone => ExportEvent::DuplicatesDone,
+            ScanMessage::Watching => ExportEvent::Watching,
+            ScanMessage::Removed(path) => ExportEvent::Removed { path },
+            ScanMessage::Cancelled => ExportEvent::Cancelled,
+            ScanMessage::Error(message) => ExportEvent::Error { message },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    path: &'a Path,
+    format: ModelFormat,
+    size: u64,
+    modified: String,
+}
+
+pub fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn format_text_line(file: &FileInfo) -> String {
+    format!(
+        "{}\t{}\t{}",
+        format_size(file.size),
+        file.format.label(),
+        file.path.display()
+    )
+}
+
+/// Writes the message as one line of JSON with its kind in `event`, files
+/// have the fields of the JSON export.
+pub fn write_event<W: Write>(writer: &mut W, message: &ScanMessage) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, &ExportEvent::from(message))?;
+    writeln!(writer)
+}
+
+pub fn export_files<W: Write>(
+    writer: &mut W,
+    format: ExportFormat,
+    roots: &[PathBuf],
+    files: &[FileInfo],
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Text => {
+            for file in files {
+                writeln!(writer, "{}", format_text_line(file))?;
+            }
+        }
+        ExportFormat::Json => {
+            let document = ExportDocument {
+                roots,
+                total_size: unique_size(files.iter()),
+                files: files.iter().map(ExportEntry::from).collect(),
+            };
+            serde_json::to_writer_pretty(&mut *writer, &document)?;
+            writeln!(writer)?;
+        }
+        ExportFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for file in files {
+                csv.serialize(CsvRow {
+                    path: &file.path,
+                    format: file.format,
+                    size: file.size,
+                    modified: file.modified.map(format_date).unwrap_or_default(),
+                })?;
+            }
+            csv.flush()?;
+        }
+        ExportFormat::Markdown(group) => write_markdown(writer, group, roots, files)?,
+    }
+    Ok(())
+}
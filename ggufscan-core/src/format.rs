@@ -0,0 +1,273 @@
+//! Model format detection from the leading bytes of a file.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{self, Read, Seek},
+    path::Path,
+};
+
+use crate::{archive::is_archived_model, gguf::GGUF_MAGIC};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModelFormat {
+    #[serde(rename = "gguf")]
+    Gguf,
+    #[serde(rename = "safetensors")]
+    Safetensors,
+    #[serde(rename = "pytorch")]
+    PyTorch,
+    #[serde(rename = "onnx")]
+    Onnx,
+    // The formats llama.cpp used before GGUF, nothing current loads them
+    #[serde(rename = "ggml")]
+    Ggml,
+    // A model compressed with zstd, see [`crate::archive_file`]
+    #[serde(rename = "zstd")]
+    Zstd,
+    // Directories holding a model, reported with the size of everything in
+    // them
+    #[serde(rename = "coreml")]
+    CoreMl,
+    #[serde(rename = "mlx")]
+    Mlx,
+    #[serde(rename = "gptq")]
+    Gptq,
+    #[serde(rename = "awq")]
+    Awq,
+    #[serde(rename = "exl2")]
+    Exl2,
+    // Archives holding GGUF files, only looked into with
+    // `ScanOptions::scan_archives`
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar")]
+    Tar,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+}
+
+impl ModelFormat {
+    pub fn label(self) -> &'static str {
+        match self {
+            ModelFormat::Gguf => "GGUF",
+            ModelFormat::Safetensors => "safetensors",
+            ModelFormat::PyTorch => "PyTorch",
+            ModelFormat::Onnx => "ONNX",
+            ModelFormat::Ggml => "GGML",
+            ModelFormat::Zstd => "zstd",
+            ModelFormat::CoreMl => "Core ML",
+            ModelFormat::Mlx => "MLX",
+            ModelFormat::Gptq => "GPTQ",
+            ModelFormat::Awq => "AWQ",
+            ModelFormat::Exl2 => "exl2",
+            ModelFormat::Zip => "zip",
+            ModelFormat::Tar => "tar",
+            ModelFormat::TarGz => "tar.gz",
+        }
+    }
+
+    /// Whether the model is a directory rather than a file.
+    pub fn is_bundle(self) -> bool {
+        matches!(
+            self,
+            ModelFormat::CoreMl
+                | ModelFormat::Mlx
+                | ModelFormat::Gptq
+                | ModelFormat::Awq
+                | ModelFormat::Exl2
+        )
+    }
+
+    /// Whether the file is an archive with GGUF files inside, the header is
+    /// that of the biggest one.
+    pub fn is_packed(self) -> bool {
+        matches!(
+            self,
+            ModelFormat::Zip | ModelFormat::Tar | ModelFormat::TarGz
+        )
+    }
+}
+
+// What model files are named, besides the blobs of the Hugging Face cache
+// and Ollama which have no extension at all
+const MODEL_EXTENSIONS: &[&str] = &[
+    "gguf",
+    "safetensors",
+    "bin",
+    "pt",
+    "pth",
+    "ckpt",
+    "pkl",
+    "onnx",
+];
+
+// Whether the file is worth opening to look at its leading bytes, going by
+// its name alone
+pub(crate) fn may_be_model(path: &Path) -> bool {
+    path.extension()
+        .is_none_or(|extension| is_model_extension(extension) || is_archived_model(path))
+}
+
+pub(crate) fn is_model_extension(extension: &OsStr) -> bool {
+    MODEL_EXTENSIONS
+        .iter()
+        .any(|known| extension.eq_ignore_ascii_case(known))
+}
+
+// Safetensors files start with the length of their JSON header
+const MAX_SAFETENSORS_HEADER_LEN: u64 = 100 * 1024 * 1024;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+// Where ustar and GNU tar headers have their magic
+const TAR_MAGIC_OFFSET: usize = 257;
+// `ggml`, `ggmf` and `ggjt` written as little-endian u32s, the unversioned
+// format and the two versioned ones that followed it
+const GGML_MAGICS: [&[u8]; 3] = [b"lmgg", b"fmgg", b"tjgg"];
+// Legacy `torch.save` pickles start with protocol 2 followed by a long
+// holding torch's magic number
+const TORCH_PICKLE_MAGIC: &[u8] = b"\x80\x02\x8a\x0a\x6c\xfc\x9c\x46\xf9\x20\x6a\xa8\x50\x19";
+// Entries `torch.save` writes first into `<name>/` inside its zip archives
+const TORCH_ZIP_ENTRIES: &[&str] = &["data.pkl", "byteorder", ".format_version"];
+
+// Zip based checkpoints are told apart from other zip files by the name of
+// the first entry in the archive
+fn is_torch_zip(prefix: &[u8]) -> bool {
+    if !prefix.starts_with(ZIP_MAGIC) || prefix.len() < 30 {
+        return false;
+    }
+    let name_len = u16::from_le_bytes([prefix[26], prefix[27]]) as usize;
+    let Some(name) = prefix.get(30..30 + name_len) else {
+        return false;
+    };
+    let name = String::from_utf8_lossy(name);
+    name.split_once('/')
+        .is_some_and(|(_, entry)| TORCH_ZIP_ENTRIES.contains(&entry))
+}
+
+// Highest IR version accepted, current ONNX releases are at 10
+const MAX_ONNX_IR_VERSION: u64 = 32;
+
+fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+// ONNX models are a bare protobuf `ModelProto`, so the prefix is walked
+// field by field and every field has to be one `ModelProto` defines, starting
+// with `ir_version`
+fn is_onnx_model(prefix: &[u8]) -> bool {
+    let mut offset = 0;
+    let mut fields = 0;
+
+    while offset < prefix.len() {
+        let Some((tag, len)) = read_varint(&prefix[offset..]) else {
+            break;
+        };
+        offset += len;
+        let (field, wire_type) = (tag >> 3, tag & 0x7);
+
+        match (field, wire_type) {
+            // ir_version and model_version
+            (1 | 5, 0) => {
+                let Some((value, len)) = read_varint(&prefix[offset..]) else {
+                    break;
+                };
+                if field == 1 && !(1..=MAX_ONNX_IR_VERSION).contains(&value) {
+                    return false;
+                }
+                offset += len;
+            }
+            // Strings, the graph and the other nested messages
+            (2 | 3 | 4 | 6 | 7 | 8 | 14 | 20 | 21 | 25, 2) => {
+                let Some((value, len)) = read_varint(&prefix[offset..]) else {
+                    break;
+                };
+                offset = offset.saturating_add(len).saturating_add(value as usize);
+            }
+            _ => return false,
+        }
+
+        if fields == 0 && field != 1 {
+            return false;
+        }
+        fields += 1;
+    }
+
+    fields >= 2
+}
+
+// Identifies the model format from the leading bytes of the file, leaving
+// the file positioned at its start
+pub fn detect_format(file: &mut fs::File, file_len: u64) -> io::Result<Option<ModelFormat>> {
+    let mut buffer = [0u8; 512];
+    let mut read = 0;
+    while read < buffer.len() {
+        match file.read(&mut buffer[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    file.rewind()?;
+    Ok(detect_prefix(&buffer[..read], file_len))
+}
+
+// The same from the leading bytes alone, up to 512 of them
+pub(crate) fn detect_prefix(prefix: &[u8], file_len: u64) -> Option<ModelFormat> {
+    if prefix.starts_with(GGUF_MAGIC) {
+        return Some(ModelFormat::Gguf);
+    }
+
+    if GGML_MAGICS.iter().any(|magic| prefix.starts_with(magic)) {
+        return Some(ModelFormat::Ggml);
+    }
+
+    if prefix.len() >= 9 {
+        let header_len = u64::from_le_bytes(prefix[..8].try_into().unwrap());
+        if header_len <= MAX_SAFETENSORS_HEADER_LEN
+            && header_len + 8 <= file_len
+            && prefix[8] == b'{'
+        {
+            return Some(ModelFormat::Safetensors);
+        }
+    }
+
+    if prefix.starts_with(TORCH_PICKLE_MAGIC) || is_torch_zip(prefix) {
+        return Some(ModelFormat::PyTorch);
+    }
+
+    if is_onnx_model(prefix) {
+        return Some(ModelFormat::Onnx);
+    }
+
+    // Any zstd frame, only those named like an archived model are kept
+    if prefix.starts_with(ZSTD_MAGIC) {
+        return Some(ModelFormat::Zstd);
+    }
+
+    // Any archive, only kept when GGUF files are found inside. Torch's own
+    // zip archives were told apart above.
+    if prefix.starts_with(ZIP_MAGIC) {
+        return Some(ModelFormat::Zip);
+    }
+    if prefix
+        .get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5)
+        .is_some_and(|magic| magic == b"ustar")
+    {
+        return Some(ModelFormat::Tar);
+    }
+    if prefix.starts_with(GZIP_MAGIC) {
+        return Some(ModelFormat::TarGz);
+    }
+
+    None
+}
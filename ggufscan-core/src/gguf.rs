@@ -0,0 +1,790 @@
+//! GGUF header parsing.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{
+    scan::open_untouched,
+    units::{format_size, format_size_class},
+};
+
+/// Magic bytes every GGUF file starts with.
+pub const GGUF_MAGIC: &[u8] = b"GGUF";
+
+// Guards against garbage files that merely start with the magic bytes
+const MAX_STRING_LEN: u64 = 64 * 1024 * 1024;
+const MAX_TENSOR_DIMS: u32 = 8;
+// Arrays of arrays, each level only takes a few bytes of the file
+const MAX_ARRAY_DEPTH: u32 = 8;
+// Only the first few items of array values are kept, token lists can hold
+// hundreds of thousands of entries
+const MAX_ARRAY_PREVIEW: usize = 16;
+// Tensor data starts at a multiple of `general.alignment`
+const DEFAULT_ALIGNMENT: u64 = 32;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MetadataValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+    String(String),
+    Array { len: u64, items: Vec<MetadataValue> },
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl MetadataValue {
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            MetadataValue::U8(v) => Some(v.into()),
+            MetadataValue::U16(v) => Some(v.into()),
+            MetadataValue::U32(v) => Some(v.into()),
+            MetadataValue::U64(v) => Some(v),
+            MetadataValue::I8(v) => v.try_into().ok(),
+            MetadataValue::I16(v) => v.try_into().ok(),
+            MetadataValue::I32(v) => v.try_into().ok(),
+            MetadataValue::I64(v) => v.try_into().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MetadataValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MetadataValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataValue::U8(v) => write!(f, "{}", v),
+            MetadataValue::I8(v) => write!(f, "{}", v),
+            MetadataValue::U16(v) => write!(f, "{}", v),
+            MetadataValue::I16(v) => write!(f, "{}", v),
+            MetadataValue::U32(v) => write!(f, "{}", v),
+            MetadataValue::I32(v) => write!(f, "{}", v),
+            MetadataValue::F32(v) => write!(f, "{}", v),
+            MetadataValue::Bool(v) => write!(f, "{}", v),
+            MetadataValue::String(v) => write!(f, "{}", v),
+            MetadataValue::Array { len, items } => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                if *len as usize > items.len() {
+                    write!(f, ", … ({} items)", len)?;
+                }
+                write!(f, "]")
+            }
+            MetadataValue::U64(v) => write!(f, "{}", v),
+            MetadataValue::I64(v) => write!(f, "{}", v),
+            MetadataValue::F64(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufHeader {
+    pub version: u32,
+    pub tensor_count: u64,
+    pub metadata: Vec<(String, MetadataValue)>,
+    pub parameter_count: u64,
+    // ggml type of the tensors holding most of the weights, the quant of
+    // files without `general.file_type`
+    pub tensor_type: Option<u32>,
+    // Where the tensor data ends, the size the file needs to have; unknown
+    // when a tensor has a type we don't know the size of
+    pub data_end: Option<u64>,
+}
+
+/// A tensor as the header lists it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TensorInfo {
+    pub name: String,
+    pub shape: Vec<u64>,
+    pub ggml_type: u32,
+    /// Bytes the data takes, unknown for types we don't know the size of
+    pub size: Option<u64>,
+}
+
+impl TensorInfo {
+    pub fn type_name(&self) -> String {
+        ggml_type_name(self.ggml_type)
+            .map_or_else(|| format!("type {}", self.ggml_type), str::to_string)
+    }
+}
+
+/// The tensors listed in the header of a GGUF file, in file order. They
+/// aren't kept with [`GgufHeader`], a big model has thousands.
+pub fn read_tensors(path: &Path) -> io::Result<Vec<TensorInfo>> {
+    let mut file = io::BufReader::new(open_untouched(path)?);
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if magic != GGUF_MAGIC {
+        return Err(invalid_data("not a GGUF file".to_string()));
+    }
+    let mut tensors = Vec::new();
+    GgufHeader::read(file, Some(&mut tensors))?;
+    Ok(tensors)
+}
+
+impl GgufHeader {
+    // Parses the header from a reader positioned right after the magic
+    pub fn parse<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read(reader, None)
+    }
+
+    fn read<R: Read>(reader: R, mut tensors: Option<&mut Vec<TensorInfo>>) -> io::Result<Self> {
+        let mut reader = GgufReader {
+            reader,
+            version: 0,
+            position: GGUF_MAGIC.len() as u64,
+        };
+        let version = reader.u32()?;
+        if !(1..=3).contains(&version) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported GGUF version {}", version),
+            ));
+        }
+        reader.version = version;
+
+        let tensor_count = reader.count()?;
+        let metadata_count = reader.count()?;
+
+        let mut metadata = Vec::new();
+        for _ in 0..metadata_count {
+            let key = reader.string()?;
+            let value_type = reader.u32()?;
+            let value = reader.value(value_type, 0)?;
+            metadata.push((key, value));
+        }
+
+        let mut parameter_count: u64 = 0;
+        let mut type_elements: Vec<(u32, u64)> = Vec::new();
+        let mut tensors_end: Option<u64> = Some(0);
+        for _ in 0..tensor_count {
+            let name = reader.string()?;
+            let dims = reader.u32()?;
+            if dims > MAX_TENSOR_DIMS {
+                return Err(invalid_data(format!("tensor with {} dimensions", dims)));
+            }
+            let mut shape = Vec::with_capacity(dims as usize);
+            for _ in 0..dims {
+                shape.push(reader.count()?);
+            }
+            let elements = shape
+                .iter()
+                .fold(1u64, |elements, &dim| elements.saturating_mul(dim));
+            let ggml_type = reader.u32()?;
+            let offset = reader.u64()?;
+            let size = ggml_type_size(ggml_type, elements);
+            let end = size.and_then(|size| offset.checked_add(size));
+            if let Some(tensors) = tensors.as_deref_mut() {
+                tensors.push(TensorInfo {
+                    name,
+                    shape,
+                    ggml_type,
+                    size,
+                });
+            }
+            tensors_end = tensors_end.zip(end).map(|(a, b)| a.max(b));
+            parameter_count = parameter_count.saturating_add(elements);
+            match type_elements.iter_mut().find(|(t, _)| *t == ggml_type) {
+                Some((_, count)) => *count = count.saturating_add(elements),
+                None => type_elements.push((ggml_type, elements)),
+            }
+        }
+        let tensor_type = type_elements
+            .into_iter()
+            .max_by_key(|&(_, count)| count)
+            .map(|(ggml_type, _)| ggml_type);
+
+        let alignment = metadata
+            .iter()
+            .find(|(key, _)| key == "general.alignment")
+            .and_then(|(_, value)| value.as_u64())
+            .filter(|&alignment| alignment > 0)
+            .unwrap_or(DEFAULT_ALIGNMENT);
+        let data_start = reader.position.div_ceil(alignment) * alignment;
+        let data_end = tensors_end.and_then(|end| data_start.checked_add(end));
+
+        Ok(Self {
+            version,
+            tensor_count,
+            metadata,
+            parameter_count,
+            tensor_type,
+            data_end,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&MetadataValue> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn architecture(&self) -> Option<&str> {
+        self.get("general.architecture")
+            .and_then(MetadataValue::as_str)
+    }
+
+    // The model it's based on, `general.base_model.0.name` for fine-tunes,
+    // `general.basename` like `Meta-Llama-3.1` or the name of the model
+    pub fn family(&self) -> Option<&str> {
+        [
+            "general.base_model.0.name",
+            "general.basename",
+            "general.name",
+        ]
+        .iter()
+        .filter_map(|key| self.get(key).and_then(MetadataValue::as_str))
+        .find(|family| !family.is_empty())
+    }
+
+    // Tokens the model was trained to attend to, `<arch>.context_length`
+    pub fn context_length(&self) -> Option<u64> {
+        let arch = self.architecture()?;
+        self.get(&format!("{}.context_length", arch))?.as_u64()
+    }
+
+    // `gpt2`, `llama` and the like, the kind of tokenizer the vocabulary is for
+    pub fn tokenizer(&self) -> Option<&str> {
+        self.get("tokenizer.ggml.model")
+            .and_then(MetadataValue::as_str)
+    }
+
+    // Tokens in the vocabulary, `<arch>.vocab_size` when the converter wrote
+    // it and the length of the token list otherwise
+    pub fn vocab_size(&self) -> Option<u64> {
+        let declared = self
+            .architecture()
+            .and_then(|arch| self.get(&format!("{}.vocab_size", arch)))
+            .and_then(MetadataValue::as_u64);
+        declared.or_else(|| match self.get("tokenizer.ggml.tokens")? {
+            MetadataValue::Array { len, .. } => Some(*len),
+            _ => None,
+        })
+    }
+
+    // Version 1, from before GGUF settled in August 2023; current llama.cpp
+    // refuses to load it
+    pub fn is_legacy(&self) -> bool {
+        self.version < 2
+    }
+
+    // What is wrong with a file of `size` bytes with this header, tensors
+    // reaching past its end mean the download or copy was cut off
+    pub fn check_size(&self, size: u64) -> Option<String> {
+        let data_end = self.data_end?;
+        (size < data_end).then(|| {
+            format!(
+                "truncated, {} of {}",
+                format_size(size),
+                format_size(data_end)
+            )
+        })
+    }
+
+    // `general.size_label` as the converter wrote it, `8x7B` for mixtures of
+    // experts, or rounded from the tensor shapes
+    pub fn size_class(&self) -> Option<String> {
+        match self
+            .get("general.size_label")
+            .and_then(MetadataValue::as_str)
+        {
+            Some(label) => Some(label.to_string()),
+            None if self.parameter_count > 0 => Some(format_size_class(self.parameter_count)),
+            None => None,
+        }
+    }
+
+    // `general.file_type` when the converter wrote it, the type most of the
+    // tensors are stored in otherwise
+    pub fn quantization(&self) -> Option<&'static str> {
+        self.get("general.file_type")
+            .and_then(MetadataValue::as_u64)
+            .and_then(file_type_name)
+            .or_else(|| self.tensor_type.and_then(ggml_type_name))
+    }
+}
+
+// Names for the `general.file_type` values written by llama.cpp
+fn file_type_name(file_type: u64) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        _ => return None,
+    })
+}
+
+// Names of the ggml tensor types, which lack the `_S`/`_M`/`_L` mixes of the
+// file types
+fn ggml_type_name(ggml_type: u32) -> Option<&'static str> {
+    Some(match ggml_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        6 => "Q5_0",
+        7 => "Q5_1",
+        8 => "Q8_0",
+        9 => "Q8_1",
+        10 => "Q2_K",
+        11 => "Q3_K",
+        12 => "Q4_K",
+        13 => "Q5_K",
+        14 => "Q6_K",
+        15 => "Q8_K",
+        16 => "IQ2_XXS",
+        17 => "IQ2_XS",
+        18 => "IQ3_XXS",
+        19 => "IQ1_S",
+        20 => "IQ4_NL",
+        21 => "IQ3_S",
+        22 => "IQ2_S",
+        23 => "IQ4_XS",
+        24 => "I8",
+        25 => "I16",
+        26 => "I32",
+        27 => "I64",
+        28 => "F64",
+        29 => "IQ1_M",
+        30 => "BF16",
+        34 => "TQ1_0",
+        35 => "TQ2_0",
+        _ => return None,
+    })
+}
+
+// Bytes taken by `elements` values of the ggml type, which are stored in
+// blocks for the quantized types
+fn ggml_type_size(ggml_type: u32, elements: u64) -> Option<u64> {
+    let (block_len, block_size) = match ggml_type {
+        0 | 26 => (1, 4),
+        1 | 25 | 30 => (1, 2),
+        24 => (1, 1),
+        27 | 28 => (1, 8),
+        2 | 20 => (32, 18),
+        3 => (32, 20),
+        6 => (32, 22),
+        7 => (32, 24),
+        8 => (32, 34),
+        9 => (32, 36),
+        10 => (256, 84),
+        11 | 21 => (256, 110),
+        12 => (256, 144),
+        13 => (256, 176),
+        14 => (256, 210),
+        15 => (256, 292),
+        16 | 35 => (256, 66),
+        17 => (256, 74),
+        18 => (256, 98),
+        19 => (256, 50),
+        22 => (256, 82),
+        23 => (256, 136),
+        29 => (256, 56),
+        34 => (256, 54),
+        _ => return None,
+    };
+    elements.div_ceil(block_len).checked_mul(block_size)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+struct GgufReader<R> {
+    reader: R,
+    version: u32,
+    // Bytes read from the start of the file
+    position: u64,
+}
+
+impl<R: Read> GgufReader<R> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buffer = [0u8; N];
+        self.reader.read_exact(&mut buffer)?;
+        self.position += N as u64;
+        Ok(buffer)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    // Counts and lengths were 32 bit in GGUF v1
+    fn count(&mut self) -> io::Result<u64> {
+        if self.version == 1 {
+            self.u32().map(u64::from)
+        } else {
+            self.u64()
+        }
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.count()?;
+        if len > MAX_STRING_LEN {
+            return Err(invalid_data(format!("string of {} bytes", len)));
+        }
+        let mut buffer = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut buffer)?;
+        if buffer.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.position += len;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+
+    fn skip(&mut self, len: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut (&mut self.reader).take(len), &mut io::sink())?;
+        if skipped != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.position += len;
+        Ok(())
+    }
+
+    // `depth` is how many arrays the value is in
+    fn value(&mut self, value_type: u32, depth: u32) -> io::Result<MetadataValue> {
+        Ok(match value_type {
+            0 => MetadataValue::U8(u8::from_le_bytes(self.bytes()?)),
+            1 => MetadataValue::I8(i8::from_le_bytes(self.bytes()?)),
+            2 => MetadataValue::U16(u16::from_le_bytes(self.bytes()?)),
+            3 => MetadataValue::I16(i16::from_le_bytes(self.bytes()?)),
+            4 => MetadataValue::U32(self.u32()?),
+            5 => MetadataValue::I32(i32::from_le_bytes(self.bytes()?)),
+            6 => MetadataValue::F32(f32::from_le_bytes(self.bytes()?)),
+            7 => MetadataValue::Bool(self.bytes::<1>()?[0] != 0),
+            8 => MetadataValue::String(self.string()?),
+            9 => {
+                if depth >= MAX_ARRAY_DEPTH {
+                    return Err(invalid_data("arrays nested too deep".to_string()));
+                }
+                let item_type = self.u32()?;
+                let len = self.count()?;
+                let mut items = Vec::new();
+                for i in 0..len {
+                    if i as usize >= MAX_ARRAY_PREVIEW {
+                        self.skip_values(item_type, len - i, depth + 1)?;
+                        break;
+                    }
+                    items.push(self.value(item_type, depth + 1)?);
+                }
+                MetadataValue::Array { len, items }
+            }
+            10 => MetadataValue::U64(self.u64()?),
+            11 => MetadataValue::I64(i64::from_le_bytes(self.bytes()?)),
+            12 => MetadataValue::F64(f64::from_le_bytes(self.bytes()?)),
+            _ => return Err(invalid_data(format!("unknown value type {}", value_type))),
+        })
+    }
+
+    fn skip_values(&mut self, value_type: u32, count: u64, depth: u32) -> io::Result<()> {
+        let size: u64 = match value_type {
+            0 | 1 | 7 => 1,
+            2 | 3 => 2,
+            4..=6 => 4,
+            10..=12 => 8,
+            _ => {
+                // Strings and nested arrays have variable sizes
+                for _ in 0..count {
+                    self.value(value_type, depth)?;
+                }
+                return Ok(());
+            }
+        };
+        self.skip(count.saturating_mul(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes a GGUF header, without the magic `parse` expects to be read
+    // already
+    struct Writer {
+        bytes: Vec<u8>,
+        version: u32,
+    }
+
+    impl Writer {
+        fn new(version: u32, tensors: u64, metadata: u64) -> Self {
+            let mut writer = Self {
+                bytes: Vec::new(),
+                version,
+            };
+            writer.u32(version);
+            writer.count(tensors);
+            writer.count(metadata);
+            writer
+        }
+
+        fn u32(&mut self, value: u32) -> &mut Self {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn u64(&mut self, value: u64) -> &mut Self {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn count(&mut self, value: u64) -> &mut Self {
+            if self.version == 1 {
+                self.u32(value as u32)
+            } else {
+                self.u64(value)
+            }
+        }
+
+        fn string(&mut self, value: &str) -> &mut Self {
+            self.count(value.len() as u64);
+            self.bytes.extend_from_slice(value.as_bytes());
+            self
+        }
+
+        fn kv_string(&mut self, key: &str, value: &str) -> &mut Self {
+            self.string(key).u32(8).string(value)
+        }
+
+        fn kv_u32(&mut self, key: &str, value: u32) -> &mut Self {
+            self.string(key).u32(4).u32(value)
+        }
+
+        fn tensor(&mut self, name: &str, shape: &[u64], ggml_type: u32, offset: u64) -> &mut Self {
+            self.string(name).u32(shape.len() as u32);
+            for &dim in shape {
+                self.count(dim);
+            }
+            self.u32(ggml_type).u64(offset)
+        }
+    }
+
+    fn parse(bytes: &[u8]) -> io::Result<GgufHeader> {
+        GgufHeader::parse(bytes)
+    }
+
+    // A llama header with two tensors, 4096 × 32 F16 values and 256 Q4_K ones
+    fn model(version: u32) -> Vec<u8> {
+        let mut writer = Writer::new(version, 2, 3);
+        writer
+            .kv_string("general.architecture", "llama")
+            .kv_u32("general.file_type", 15)
+            .kv_u32("llama.context_length", 4096)
+            .tensor("token_embd.weight", &[4096, 32], 1, 0)
+            .tensor("blk.0.attn_q.weight", &[256], 12, 4096 * 32 * 2);
+        writer.bytes
+    }
+
+    #[test]
+    fn parses_a_header() {
+        let bytes = model(3);
+        let header = parse(&bytes).unwrap();
+        assert_eq!(header.version, 3);
+        assert_eq!(header.tensor_count, 2);
+        assert_eq!(header.architecture(), Some("llama"));
+        assert_eq!(header.quantization(), Some("Q4_K_M"));
+        assert_eq!(header.context_length(), Some(4096));
+        assert_eq!(header.parameter_count, 4096 * 32 + 256);
+        assert_eq!(header.tensor_type, Some(1));
+        // The data starts aligned after the header
+        let data_start = (GGUF_MAGIC.len() + bytes.len()).div_ceil(32) as u64 * 32;
+        assert_eq!(header.data_end, Some(data_start + 4096 * 32 * 2 + 144));
+    }
+
+    #[test]
+    fn parses_32_bit_counts_of_version_1() {
+        let header = parse(&model(1)).unwrap();
+        assert_eq!(header.version, 1);
+        assert!(header.is_legacy());
+        assert_eq!(header.architecture(), Some("llama"));
+        assert_eq!(header.parameter_count, 4096 * 32 + 256);
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        for version in [0, 4] {
+            let error = parse(&Writer::new(version, 0, 0).bytes).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[test]
+    fn fails_on_every_truncation() {
+        let bytes = model(3);
+        for len in 0..bytes.len() {
+            assert!(parse(&bytes[..len]).is_err(), "cut at {}", len);
+        }
+    }
+
+    #[test]
+    fn checks_the_file_size_against_the_tensors() {
+        let header = parse(&model(3)).unwrap();
+        let data_end = header.data_end.unwrap();
+        assert_eq!(header.check_size(data_end), None);
+        assert!(header
+            .check_size(data_end - 1)
+            .unwrap()
+            .starts_with("truncated"));
+    }
+
+    #[test]
+    fn rejects_overlong_strings() {
+        let mut writer = Writer::new(3, 0, 1);
+        writer.u64(MAX_STRING_LEN + 1);
+        let error = parse(&writer.bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_too_many_dimensions() {
+        let mut writer = Writer::new(3, 1, 0);
+        writer.tensor("t", &[1; MAX_TENSOR_DIMS as usize + 1], 0, 0);
+        let error = parse(&writer.bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn limits_nested_arrays() {
+        let nested = |depth: u32| {
+            let mut writer = Writer::new(3, 0, 1);
+            writer.string("nested").u32(9);
+            for _ in 1..depth {
+                writer.u32(9).u64(1);
+            }
+            writer.u32(4).u64(1).u32(7);
+            writer.bytes
+        };
+        assert!(parse(&nested(MAX_ARRAY_DEPTH)).is_ok());
+        let error = parse(&nested(MAX_ARRAY_DEPTH + 1)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // Deep enough to overflow the stack without the limit
+        let error = parse(&nested(1_000_000)).unwrap_err();
+        assert_eq!(error.to_string(), "arrays nested too deep");
+    }
+
+    #[test]
+    fn rejects_unknown_value_types() {
+        let mut writer = Writer::new(3, 0, 1);
+        writer.string("key").u32(13);
+        let error = parse(&writer.bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn keeps_only_the_start_of_long_arrays() {
+        let mut writer = Writer::new(3, 0, 2);
+        writer
+            .string("tokenizer.ggml.tokens")
+            .u32(9)
+            .u32(8)
+            .u64(100);
+        for i in 0..100 {
+            writer.string(&format!("tok{}", i));
+        }
+        writer.string("scores").u32(9).u32(6).u64(1000);
+        writer.bytes.extend_from_slice(&[0; 4000]);
+        let header = parse(&writer.bytes).unwrap();
+        match header.get("tokenizer.ggml.tokens") {
+            Some(MetadataValue::Array { len, items }) => {
+                assert_eq!(*len, 100);
+                assert_eq!(items.len(), MAX_ARRAY_PREVIEW);
+                assert_eq!(items[1].as_str(), Some("tok1"));
+            }
+            other => panic!("expected an array, got {:?}", other),
+        }
+        assert_eq!(header.vocab_size(), Some(100));
+        // The skipped floats were read past
+        assert!(matches!(
+            header.get("scores"),
+            Some(MetadataValue::Array { len: 1000, .. })
+        ));
+    }
+
+    #[test]
+    fn fails_on_arrays_longer_than_the_file() {
+        let mut writer = Writer::new(3, 0, 1);
+        writer.string("scores").u32(9).u32(6).u64(u64::MAX);
+        assert!(parse(&writer.bytes).is_err());
+        let mut writer = Writer::new(3, 0, 1);
+        writer.string("tokens").u32(9).u32(8).u64(u64::MAX);
+        assert!(parse(&writer.bytes).is_err());
+    }
+
+    #[test]
+    fn saturates_huge_tensor_shapes() {
+        let mut writer = Writer::new(3, 1, 0);
+        writer.tensor("t", &[u64::MAX, u64::MAX], 0, 0);
+        let header = parse(&writer.bytes).unwrap();
+        assert_eq!(header.parameter_count, u64::MAX);
+        assert_eq!(header.data_end, None);
+    }
+
+    #[test]
+    fn family_prefers_the_base_model() {
+        let mut writer = Writer::new(3, 0, 3);
+        writer
+            .kv_string("general.name", "Hermes 3 Llama 3.1 8B")
+            .kv_string("general.basename", "Hermes-3")
+            .kv_string("general.base_model.0.name", "Llama 3.1 8B");
+        assert_eq!(parse(&writer.bytes).unwrap().family(), Some("Llama 3.1 8B"));
+        let mut writer = Writer::new(3, 0, 2);
+        writer
+            .kv_string("general.basename", "")
+            .kv_string("general.name", "Phi 3");
+        assert_eq!(parse(&writer.bytes).unwrap().family(), Some("Phi 3"));
+    }
+}
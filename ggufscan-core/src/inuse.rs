@@ -0,0 +1,144 @@
+//! Telling which processes have a file open or mapped into memory, so
+//! deleting a model that is being served can be warned about.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// A process using a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileUser {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// The processes having each of the files open or mapped, as far as the
+/// platform tells: `/proc` on Linux and `lsof` elsewhere on Unix, only
+/// showing processes of other users when running as root. Nothing is found
+/// on Windows. Files nobody uses are left out.
+pub fn file_users(paths: &[PathBuf]) -> HashMap<PathBuf, Vec<FileUser>> {
+    // Processes see the file by its resolved path
+    let wanted: HashMap<PathBuf, &Path> = paths
+        .iter()
+        .filter_map(|path| Some((path.canonicalize().ok()?, path.as_path())))
+        .collect();
+    let mut users: HashMap<PathBuf, Vec<FileUser>> = HashMap::new();
+    if wanted.is_empty() {
+        return users;
+    }
+    for (user, path) in open_files(&wanted) {
+        let Some(&original) = wanted.get(&path) else {
+            continue;
+        };
+        let entry = users.entry(original.to_owned()).or_default();
+        if !entry.contains(&user) {
+            entry.push(user);
+        }
+    }
+    users
+}
+
+// Open file descriptors and mapped files of every process but this one
+#[cfg(target_os = "linux")]
+fn open_files(wanted: &HashMap<PathBuf, &Path>) -> Vec<(FileUser, PathBuf)> {
+    use std::fs;
+
+    let mut found = Vec::new();
+    let Ok(processes) = fs::read_dir("/proc") else {
+        return found;
+    };
+    for process in processes.flatten() {
+        let Some(pid): Option<u32> = process
+            .file_name()
+            .to_str()
+            .and_then(|pid| pid.parse().ok())
+        else {
+            continue;
+        };
+        if pid == std::process::id() {
+            continue;
+        }
+        let dir = process.path();
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir.join("fd"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|fd| fs::read_link(fd.path()).ok())
+            .collect();
+        // A model loaded with mmap may have no descriptor left, only the
+        // mapping. The path is the last field and may contain spaces.
+        if let Ok(maps) = fs::read_to_string(dir.join("maps")) {
+            paths.extend(
+                maps.lines()
+                    .filter_map(|line| line.splitn(6, ' ').nth(5))
+                    .map(str::trim_start)
+                    .filter(|path| path.starts_with('/'))
+                    .map(PathBuf::from),
+            );
+        }
+        paths.retain(|path| wanted.contains_key(path));
+        if paths.is_empty() {
+            continue;
+        }
+        let name = fs::read_to_string(dir.join("comm"))
+            .map(|name| name.trim().to_string())
+            .unwrap_or_else(|_| pid.to_string());
+        for path in paths {
+            found.push((
+                FileUser {
+                    pid,
+                    name: name.clone(),
+                },
+                path,
+            ));
+        }
+    }
+    found
+}
+
+// `lsof -F pcn` prints a `p<pid>` and `c<command>` line per process followed
+// by an `n<path>` line per file
+#[cfg(all(unix, not(target_os = "linux")))]
+fn open_files(wanted: &HashMap<PathBuf, &Path>) -> Vec<(FileUser, PathBuf)> {
+    let mut found = Vec::new();
+    let Ok(output) = std::process::Command::new("lsof")
+        .arg("-F")
+        .arg("pcn")
+        .arg("--")
+        .args(wanted.keys())
+        .stderr(std::process::Stdio::null())
+        .output()
+    else {
+        return found;
+    };
+    let mut user: Option<FileUser> = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let (field, value) = line.split_at(line.len().min(1));
+        match field {
+            "p" => {
+                user = value.parse().ok().map(|pid| FileUser {
+                    pid,
+                    name: String::new(),
+                })
+            }
+            "c" => {
+                if let Some(user) = &mut user {
+                    user.name = value.to_string();
+                }
+            }
+            "n" => {
+                if let Some(user) = user.as_ref().filter(|user| user.pid != std::process::id()) {
+                    found.push((user.clone(), PathBuf::from(value)));
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+#[cfg(not(unix))]
+fn open_files(_wanted: &HashMap<PathBuf, &Path>) -> Vec<(FileUser, PathBuf)> {
+    Vec::new()
+}
@@ -0,0 +1,459 @@
+//! A SQLite database keeping the results of every scan, for looking up past
+//! inventories and how the space taken by models changed over time.
+
+use rusqlite::{params, types::Value, Connection};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    parse_age, parse_size, quarantine::data_dir, unique_size, unix_seconds, FileInfo, GgufHeader,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS scans (
+        id INTEGER PRIMARY KEY,
+        time INTEGER NOT NULL,
+        roots TEXT NOT NULL,
+        files INTEGER NOT NULL,
+        total_size INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        scan INTEGER NOT NULL REFERENCES scans(id) ON DELETE CASCADE,
+        path TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        format TEXT NOT NULL,
+        architecture TEXT,
+        quantization TEXT,
+        parameters INTEGER,
+        modified INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS files_scan ON files(scan);
+";
+
+/// Where the inventory is kept unless another path is given,
+/// `$XDG_DATA_HOME/ggufscan/inventory.sqlite`.
+pub fn default_inventory_path() -> Option<PathBuf> {
+    Some(data_dir()?.join("inventory.sqlite"))
+}
+
+/// One scan as recorded.
+#[derive(Debug, Clone)]
+pub struct ScanRecord {
+    pub id: i64,
+    pub time: SystemTime,
+    pub roots: Vec<PathBuf>,
+    pub files: u64,
+    pub total_size: u64,
+}
+
+/// A file as it was at the time of a scan.
+#[derive(Debug, Clone)]
+pub struct InventoryFile {
+    pub scan: i64,
+    pub path: PathBuf,
+    pub size: u64,
+    pub format: String,
+    pub architecture: Option<String>,
+    pub quantization: Option<String>,
+    pub parameters: Option<u64>,
+    pub modified: Option<SystemTime>,
+}
+
+pub struct Inventory {
+    connection: Connection,
+}
+
+impl Inventory {
+    /// Opens the database, creating it and its directory when missing.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let connection = Connection::open(path).map_err(db_error)?;
+        connection.execute_batch(SCHEMA).map_err(db_error)?;
+        Ok(Self { connection })
+    }
+
+    /// Adds a scan with its files, returning its id.
+    pub fn record_scan(&mut self, roots: &[PathBuf], files: &[FileInfo]) -> io::Result<i64> {
+        let transaction = self.connection.transaction().map_err(db_error)?;
+        let roots: Vec<String> = roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect();
+        transaction
+            .execute(
+                "INSERT INTO scans (time, roots, files, total_size) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    unix_seconds(SystemTime::now()) as i64,
+                    roots.join("\n"),
+                    files.len() as i64,
+                    unique_size(files.iter()) as i64
+                ],
+            )
+            .map_err(db_error)?;
+        let scan = transaction.last_insert_rowid();
+        {
+            let mut insert = transaction
+                .prepare(
+                    "INSERT INTO files (scan, path, size, format, architecture, quantization,
+                        parameters, modified) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )
+                .map_err(db_error)?;
+            for file in files {
+                let header = file.header.as_ref();
+                insert
+                    .execute(params![
+                        scan,
+                        file.path.to_string_lossy(),
+                        file.size as i64,
+                        file.format.label(),
+                        header.and_then(GgufHeader::architecture),
+                        header.and_then(GgufHeader::quantization),
+                        header.map(|header| header.parameter_count as i64),
+                        file.modified.map(|time| unix_seconds(time) as i64)
+                    ])
+                    .map_err(db_error)?;
+            }
+        }
+        transaction.commit().map_err(db_error)?;
+        Ok(scan)
+    }
+
+    /// All recorded scans, oldest first.
+    pub fn scans(&self) -> io::Result<Vec<ScanRecord>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, time, roots, files, total_size FROM scans ORDER BY id")
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                let roots: String = row.get(2)?;
+                Ok(ScanRecord {
+                    id: row.get(0)?,
+                    time: from_unix_seconds(row.get(1)?),
+                    roots: roots.lines().map(PathBuf::from).collect(),
+                    files: row.get::<_, i64>(3)? as u64,
+                    total_size: row.get::<_, i64>(4)? as u64,
+                })
+            })
+            .map_err(db_error)?;
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+
+    /// Files of the scan, the latest one when none is given, matching the
+    /// query, biggest first. Queries are conditions joined by `and` like
+    /// `size > 10GB and arch = llama`, an empty one matches all files.
+    pub fn query(&self, query: &str, scan: Option<i64>) -> io::Result<Vec<InventoryFile>> {
+        let (conditions, mut values) = parse_query(query)?;
+        let scan = match scan {
+            Some(scan) => scan,
+            None => self
+                .connection
+                .query_row("SELECT COALESCE(MAX(id), 0) FROM scans", [], |row| {
+                    row.get(0)
+                })
+                .map_err(db_error)?,
+        };
+        values.insert(0, Value::Integer(scan));
+        let mut sql = "SELECT scan, path, size, format, architecture, quantization, parameters,
+            modified FROM files WHERE scan = ?"
+            .to_string();
+        for condition in conditions {
+            sql.push_str(" AND ");
+            sql.push_str(&condition);
+        }
+        sql.push_str(" ORDER BY size DESC, path");
+
+        let mut statement = self.connection.prepare(&sql).map_err(db_error)?;
+        let rows = statement
+            .query_map(rusqlite::params_from_iter(values), |row| {
+                let path: String = row.get(1)?;
+                Ok(InventoryFile {
+                    scan: row.get(0)?,
+                    path: PathBuf::from(path),
+                    size: row.get::<_, i64>(2)? as u64,
+                    format: row.get(3)?,
+                    architecture: row.get(4)?,
+                    quantization: row.get(5)?,
+                    parameters: row.get::<_, Option<i64>>(6)?.map(|count| count as u64),
+                    modified: row.get::<_, Option<i64>>(7)?.map(from_unix_seconds),
+                })
+            })
+            .map_err(db_error)?;
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+}
+
+// Conditions joined by `and`, each a field, an operator and a value:
+// `size > 10GB and arch = llama`. Sizes are written as on the command line,
+// parameter counts like `7B` and ages like `90d`; text fields compare with
+// `=` and `!=` ignoring case or `~` for containing the value.
+fn parse_query(query: &str) -> io::Result<(Vec<String>, Vec<Value>)> {
+    const OPERATORS: [&str; 7] = [">=", "<=", "!=", ">", "<", "=", "~"];
+
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok((conditions, values));
+    }
+    for condition in split_and(query) {
+        let invalid = || invalid_query(format!("expected e.g. `size > 10GB`, got `{}`", condition));
+        let (at, operator) = OPERATORS
+            .iter()
+            .filter_map(|operator| Some((condition.find(operator)?, *operator)))
+            .min_by_key(|&(at, operator)| (at, std::cmp::Reverse(operator.len())))
+            .ok_or_else(invalid)?;
+        let field = condition[..at].trim().to_lowercase();
+        let value = condition[at + operator.len()..]
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'');
+        if value.is_empty() {
+            return Err(invalid());
+        }
+
+        let (column, value) = match field.as_str() {
+            "size" => (
+                "size",
+                parse_size(value)
+                    .ok_or_else(|| invalid_query(format!("invalid size `{}`", value)))?
+                    as i64,
+            ),
+            "params" | "parameters" => (
+                "parameters",
+                parse_count(value)
+                    .ok_or_else(|| invalid_query(format!("invalid count `{}`", value)))?
+                    as i64,
+            ),
+            // Older is a smaller modification time
+            "age" => {
+                let age = parse_age(value)
+                    .ok_or_else(|| invalid_query(format!("invalid age `{}`", value)))?;
+                let time = SystemTime::now()
+                    .checked_sub(age)
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                let operator = match operator {
+                    ">" => "<",
+                    "<" => ">",
+                    ">=" => "<=",
+                    "<=" => ">=",
+                    operator => operator,
+                };
+                if operator == "~" {
+                    return Err(invalid());
+                }
+                conditions.push(format!("modified {} ?", operator));
+                values.push(Value::Integer(unix_seconds(time) as i64));
+                continue;
+            }
+            "arch" | "architecture" | "quant" | "quantization" | "format" | "path" => {
+                let column = match field.as_str() {
+                    "arch" | "architecture" => "architecture",
+                    "quant" | "quantization" => "quantization",
+                    field => field,
+                };
+                let condition = match operator {
+                    "=" => format!("{} = ? COLLATE NOCASE", column),
+                    "!=" => format!("({0} IS NULL OR {0} != ? COLLATE NOCASE)", column),
+                    "~" => format!("{} LIKE '%' || ? || '%'", column),
+                    _ => {
+                        return Err(invalid_query(format!(
+                            "`{}` only works with `=`, `!=` and `~`",
+                            field
+                        )))
+                    }
+                };
+                conditions.push(condition);
+                values.push(Value::Text(value.to_string()));
+                continue;
+            }
+            _ => {
+                return Err(invalid_query(format!(
+                    "unknown field `{}`, expected size, params, age, arch, quant, format or path",
+                    field
+                )))
+            }
+        };
+        if operator == "~" {
+            return Err(invalid_query(format!("`{}` can't be used with `~`", field)));
+        }
+        conditions.push(format!("{} {} ?", column, operator));
+        values.push(Value::Integer(value));
+    }
+    Ok((conditions, values))
+}
+
+// Splits on `and` between whitespace, in any case
+fn split_and(query: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    for word in query.split_whitespace() {
+        if word.eq_ignore_ascii_case("and") {
+            parts.push(String::new());
+            continue;
+        }
+        let part = parts.last_mut().expect("at least one part");
+        if !part.is_empty() {
+            part.push(' ');
+        }
+        part.push_str(word);
+    }
+    parts
+}
+
+// Counts like `7B`, `500M` or `1.5T`
+fn parse_count(count: &str) -> Option<u64> {
+    let count = count.trim().to_ascii_uppercase();
+    let (number, multiplier) = match count.chars().last()? {
+        'K' => (&count[..count.len() - 1], 1e3),
+        'M' => (&count[..count.len() - 1], 1e6),
+        'B' => (&count[..count.len() - 1], 1e9),
+        'T' => (&count[..count.len() - 1], 1e12),
+        _ => (count.as_str(), 1.0),
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * multiplier) as u64)
+}
+
+fn from_unix_seconds(seconds: i64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(seconds.max(0) as u64)
+}
+
+fn invalid_query(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn db_error(error: rusqlite::Error) -> io::Error {
+    io::Error::other(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conditions(query: &str) -> Vec<String> {
+        parse_query(query).unwrap().0
+    }
+
+    fn error(query: &str) -> String {
+        let error = parse_query(query).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        error.to_string()
+    }
+
+    // An in-memory inventory with one scan of three files
+    fn inventory() -> Inventory {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        let day = 24 * 60 * 60;
+        let now = unix_seconds(SystemTime::now()) as i64;
+        connection
+            .execute_batch(&format!(
+                "INSERT INTO scans VALUES (1, {now}, '/models', 3, 0);
+                INSERT INTO files VALUES
+                    (1, '/models/llama-7b.gguf', 4000000000, 'GGUF', 'llama', 'Q4_K_M', 7000000000, {old}),
+                    (1, '/models/phi-3.gguf', 2000000000, 'GGUF', 'phi3', 'Q8_0', 3800000000, {now}),
+                    (1, '/models/model.safetensors', 16000000000, 'safetensors', NULL, NULL, NULL, NULL);",
+                now = now,
+                old = now - 100 * day,
+            ))
+            .unwrap();
+        Inventory { connection }
+    }
+
+    fn paths(query: &str) -> Vec<String> {
+        inventory()
+            .query(query, None)
+            .unwrap()
+            .into_iter()
+            .map(|file| {
+                file.path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn empty_queries_have_no_conditions() {
+        assert!(conditions("").is_empty());
+        assert!(conditions("  ").is_empty());
+    }
+
+    #[test]
+    fn parses_conditions() {
+        let (conditions, values) = parse_query("size >= 10GB AND arch = 'llama'").unwrap();
+        assert_eq!(conditions, ["size >= ?", "architecture = ? COLLATE NOCASE"]);
+        assert_eq!(
+            values,
+            [Value::Integer(10 << 30), Value::Text("llama".to_string())]
+        );
+        assert_eq!(
+            parse_query("params<7.5b").unwrap(),
+            (
+                vec!["parameters < ?".to_string()],
+                vec![Value::Integer(7_500_000_000)]
+            )
+        );
+    }
+
+    #[test]
+    fn takes_the_first_and_longest_operator() {
+        assert_eq!(conditions("size<=1GB"), ["size <= ?"]);
+        assert_eq!(conditions("size != 1GB"), ["size != ?"]);
+        assert_eq!(
+            parse_query("path ~ a=b").unwrap().1,
+            [Value::Text("a=b".to_string())]
+        );
+    }
+
+    #[test]
+    fn flips_comparisons_of_ages() {
+        assert_eq!(conditions("age > 90d"), ["modified < ?"]);
+        assert_eq!(conditions("age <= 1w"), ["modified >= ?"]);
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        assert!(error("size").contains("expected e.g."));
+        assert!(error("size >").contains("expected e.g."));
+        assert!(error("size > big").contains("invalid size"));
+        assert!(error("params > many").contains("invalid count"));
+        assert!(error("age > old").contains("invalid age"));
+        assert!(error("age ~ 1d").contains("expected e.g."));
+        assert!(error("size ~ 1GB").contains("can't be used with `~`"));
+        assert!(error("arch > llama").contains("only works with"));
+        assert!(error("name = llama").contains("unknown field"));
+        assert!(error("size > 1GB and").contains("expected e.g."));
+    }
+
+    #[test]
+    fn parses_counts() {
+        assert_eq!(parse_count("7B"), Some(7_000_000_000));
+        assert_eq!(parse_count("500m"), Some(500_000_000));
+        assert_eq!(parse_count("1.5T"), Some(1_500_000_000_000));
+        assert_eq!(parse_count("1234"), Some(1234));
+        assert_eq!(parse_count("B"), None);
+        assert_eq!(parse_count("7é"), None);
+    }
+
+    #[test]
+    fn queries_the_latest_scan() {
+        assert_eq!(
+            paths(""),
+            ["model.safetensors", "llama-7b.gguf", "phi-3.gguf"]
+        );
+        assert_eq!(paths("size > 3GB and format = gguf"), ["llama-7b.gguf"]);
+        assert_eq!(paths("arch = LLAMA"), ["llama-7b.gguf"]);
+        assert_eq!(paths("arch != llama"), ["model.safetensors", "phi-3.gguf"]);
+        assert_eq!(paths("quant ~ Q8"), ["phi-3.gguf"]);
+        assert_eq!(paths("params < 7B"), ["phi-3.gguf"]);
+        assert_eq!(paths("age > 30d"), ["llama-7b.gguf"]);
+        assert!(inventory().query("", Some(2)).unwrap().is_empty());
+    }
+}
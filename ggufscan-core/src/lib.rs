@@ -0,0 +1,89 @@
+//! Finds model files (GGUF and the older GGML, safetensors, PyTorch and
+//! ONNX, also archived with zstd) and model directories (Core ML, MLX, GPTQ,
+//! AWQ and exl2) on disk or in S3-compatible object storage and parses their
+//! headers, the engine behind the `ggufscan` TUI.
+//!
+//! ```no_run
+//! use ggufscan_core::{ScanMessage, ScanOptions, Scanner};
+//!
+//! let options = ScanOptions {
+//!     roots: vec!["/models".into()],
+//!     excludes: globset::GlobSet::empty(),
+//!     find_duplicates: false,
+//!     cache: None,
+//!     watch: false,
+//!     one_file_system: false,
+//!     follow_symlinks: false,
+//!     skip_network_mounts: false,
+//!     default_excludes: true,
+//!     max_depth: None,
+//!     min_size: 0,
+//!     older_than: None,
+//!     deep: false,
+//!     files: Vec::new(),
+//!     priority_dirs: Vec::new(),
+//!     scan_archives: false,
+//! };
+//! for message in Scanner::new(options).spawn() {
+//!     if let ScanMessage::File(file) = message {
+//!         println!("{}", file.path.display());
+//!     }
+//! }
+//! ```
+
+mod archive;
+mod bundle;
+mod cache;
+mod checksum;
+mod export;
+mod format;
+mod gguf;
+mod inuse;
+mod inventory;
+mod links;
+mod mounts;
+mod packed;
+mod percent;
+mod quarantine;
+mod relocate;
+mod remote;
+mod remove;
+mod report;
+mod s3;
+mod scan;
+mod source;
+mod units;
+mod verify;
+mod watch;
+
+pub use archive::{
+    archive_file, default_archive_dir, is_archived_model, restore_archive, ARCHIVE_EXTENSION,
+};
+pub use cache::default_cache_path;
+pub use checksum::{checksum_file, default_checksum_cache_path, ChecksumCache, Checksums};
+pub use export::{export_files, format_text_line, unix_seconds, write_event, ExportFormat};
+pub use format::{detect_format, ModelFormat};
+pub use gguf::{read_tensors, GgufHeader, MetadataValue, TensorInfo, GGUF_MAGIC};
+pub use inuse::{file_users, FileUser};
+pub use inventory::{default_inventory_path, Inventory, InventoryFile, ScanRecord};
+pub use links::{reclaimable_size, unique_size, FileId};
+pub use mounts::default_roots;
+pub use percent::percent_decode;
+pub use quarantine::{
+    default_quarantine_dir, purge_quarantined, quarantine_file, quarantined_files,
+    restore_quarantined, QuarantinedFile,
+};
+pub use relocate::move_file;
+pub use remote::{remote_path, write_remote_message, RemoteScanner};
+pub use remove::{remove_dir, remove_file, restore_from_trash};
+pub use report::ReportGroup;
+pub use s3::{delete_object, is_s3_path, S3Scanner};
+pub use scan::{FileInfo, ScanControl, ScanMessage, ScanOptions, ScanProgress, Scanner, Shard};
+pub use source::{known_model_dirs, lmstudio_model_dirs, HfRevision, ModelSource, RevisionRemoval};
+pub use units::{
+    format_date, format_day, format_param_count, format_size, format_size_class, parse_age,
+    parse_size,
+};
+pub use verify::{hf_candidates, sha256_file, verify_file, Verification};
+
+pub use globset;
@@ -0,0 +1,52 @@
+//! Hard link and symlink aware size totals.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+
+use crate::FileInfo;
+
+// Device and inode, shared by all hard links to the same file
+pub type FileId = (u64, u64);
+
+#[cfg(unix)]
+pub(crate) fn file_id(metadata: &fs::Metadata) -> (Option<FileId>, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (Some((metadata.dev(), metadata.ino())), metadata.nlink())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_id(_metadata: &fs::Metadata) -> (Option<FileId>, u64) {
+    (None, 1)
+}
+
+// Disk space used by the files, counting hard links to the same file once
+// and skipping symlinks
+pub fn unique_size<'a>(files: impl Iterator<Item = &'a FileInfo>) -> u64 {
+    let mut seen = HashSet::new();
+    files
+        .filter(|file| !file.symlink)
+        .filter(|file| file.file_id.is_none_or(|id| seen.insert(id)))
+        .map(|file| file.size)
+        .sum()
+}
+
+// Space actually freed by deleting the files, a hard linked file only goes
+// away once all of its links are deleted
+pub fn reclaimable_size<'a>(files: impl Iterator<Item = &'a FileInfo>) -> u64 {
+    let mut total = 0;
+    let mut linked: HashMap<FileId, (u64, u64, u64)> = HashMap::new();
+    for file in files.filter(|file| !file.symlink) {
+        match file.file_id {
+            Some(id) => linked.entry(id).or_insert((file.size, file.links, 0)).2 += 1,
+            None => total += file.size,
+        }
+    }
+    total
+        + linked
+            .into_values()
+            .filter(|&(_, links, count)| count >= links)
+            .map(|(size, _, _)| size)
+            .sum::<u64>()
+}
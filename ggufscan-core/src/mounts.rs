@@ -0,0 +1,209 @@
+//! Finding the filesystems to scan, and the network and removable ones to
+//! stay off while scanning.
+
+use std::path::PathBuf;
+
+/// What "the whole filesystem" is: `/`, or every drive letter there is on
+/// Windows.
+pub fn default_roots() -> Vec<PathBuf> {
+    #[cfg(windows)]
+    {
+        // Empty card readers and optical drives don't answer, so only the
+        // drives with a filesystem in them are scanned
+        ('A'..='Z')
+            .map(|letter| PathBuf::from(format!("{}:\\", letter)))
+            .filter(|drive| std::fs::metadata(drive).is_ok())
+            .collect()
+    }
+    #[cfg(not(windows))]
+    {
+        vec![PathBuf::from("/")]
+    }
+}
+
+// Filesystem types served over the network, as listed in the mount table
+const NETWORK_FS_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smbfs",
+    "smb3",
+    "afpfs",
+    "webdav",
+    "davfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.s3fs",
+    "fuse.gcsfuse",
+];
+
+// Filesystems the kernel makes up, nothing on them is a model and reading
+// them is slow or fails. Snaps are squashfs images mounted once per revision.
+const VIRTUAL_FS_TYPES: &[&str] = &[
+    "proc",
+    "sysfs",
+    "devtmpfs",
+    "devpts",
+    "devfs",
+    "cgroup",
+    "cgroup2",
+    "securityfs",
+    "debugfs",
+    "tracefs",
+    "configfs",
+    "pstore",
+    "efivarfs",
+    "bpf",
+    "mqueue",
+    "hugetlbfs",
+    "fusectl",
+    "binfmt_misc",
+    "autofs",
+    "squashfs",
+];
+
+// Where the pseudo-filesystems and the snap and flatpak trees are, also when
+// the mount table can't be read
+#[cfg(target_os = "linux")]
+const VIRTUAL_PATHS: &[&str] = &[
+    "/proc",
+    "/sys",
+    "/dev",
+    "/run",
+    "/snap",
+    "/var/lib/snapd",
+    "/var/lib/flatpak",
+];
+#[cfg(target_os = "macos")]
+const VIRTUAL_PATHS: &[&str] = &["/dev", "/System/Volumes", "/private/var/vm"];
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const VIRTUAL_PATHS: &[&str] = &[];
+
+/// Pseudo-filesystems of the system like `/proc` and `/sys` and the snap
+/// mounts, skipped unless asked not to.
+pub(crate) fn virtual_mounts() -> Vec<PathBuf> {
+    let mut mounts: Vec<PathBuf> = VIRTUAL_PATHS.iter().map(PathBuf::from).collect();
+    for mount in mount_table() {
+        // Below one of the paths already
+        if VIRTUAL_FS_TYPES.contains(&mount.fs_type.as_str())
+            && !mounts.iter().any(|path| mount.point.starts_with(path))
+        {
+            mounts.push(mount.point);
+        }
+    }
+    mounts
+}
+
+/// Mount points of network filesystems and removable drives.
+pub(crate) fn network_and_removable_mounts() -> Vec<PathBuf> {
+    mount_table()
+        .into_iter()
+        .filter(|mount| NETWORK_FS_TYPES.contains(&mount.fs_type.as_str()) || mount.removable())
+        .map(|mount| mount.point)
+        .collect()
+}
+
+struct Mount {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    device: String,
+    point: PathBuf,
+    fs_type: String,
+}
+
+impl Mount {
+    // USB sticks, SD cards and the like, the kernel flags the whole disk
+    #[cfg(target_os = "linux")]
+    fn removable(&self) -> bool {
+        let Some(name) = self.device.strip_prefix("/dev/") else {
+            return false;
+        };
+        let Ok(dev) = std::fs::canonicalize(PathBuf::from("/sys/class/block").join(name)) else {
+            return false;
+        };
+        // Partitions don't have the flag, their disk is the parent
+        [dev.join("removable"), dev.join("../removable")]
+            .iter()
+            .find_map(|flag| std::fs::read_to_string(flag).ok())
+            .is_some_and(|flag| flag.trim() == "1")
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn removable(&self) -> bool {
+        false
+    }
+}
+
+// `/proc/mounts` lines are `<device> <mount point> <type> <options> 0 0`
+// with spaces and the like escaped as octal
+#[cfg(target_os = "linux")]
+fn mount_table() -> Vec<Mount> {
+    let Ok(mounts) = std::fs::read_to_string("/proc/mounts") else {
+        return Vec::new();
+    };
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let (device, point, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+            Some(Mount {
+                device: unescape_octal(device),
+                point: PathBuf::from(unescape_octal(point)),
+                fs_type: fs_type.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn unescape_octal(field: &str) -> String {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut rest = field.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let code = tail
+            .get(..3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match code {
+            Some(code) if byte == b'\\' => {
+                bytes.push(code);
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// `mount` prints `<device> on <mount point> (<type>, <options>)`
+#[cfg(target_os = "macos")]
+fn mount_table() -> Vec<Mount> {
+    let Ok(output) = std::process::Command::new("mount").output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (device, rest) = line.split_once(" on ")?;
+            let (point, options) = rest.rsplit_once(" (")?;
+            let fs_type = options.split([',', ')']).next()?;
+            Some(Mount {
+                device: device.to_string(),
+                point: PathBuf::from(point),
+                fs_type: fs_type.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn mount_table() -> Vec<Mount> {
+    Vec::new()
+}
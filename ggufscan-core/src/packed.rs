@@ -0,0 +1,713 @@
+//! GGUF files inside zip and tar archives, found by reading the archive's
+//! directory or entry headers and the GGUF header of each entry, never the
+//! tensor data.
+
+use flate2::read::{DeflateDecoder, GzDecoder};
+use std::{
+    fs,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{GgufHeader, ModelFormat, GGUF_MAGIC};
+
+// GGUF files in the archive, biggest first
+#[derive(Debug)]
+pub(crate) struct PackedModel {
+    pub(crate) name: String,
+    pub(crate) size: u64,
+    pub(crate) header: io::Result<GgufHeader>,
+}
+
+// Whether the file is named like an archive `--scan-archives` looks into
+pub(crate) fn is_pack_name(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let name = name.to_ascii_lowercase();
+    [".zip", ".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+fn is_gguf_name(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".gguf")
+}
+
+pub(crate) fn packed_models(
+    file: &mut fs::File,
+    format: ModelFormat,
+) -> io::Result<Vec<PackedModel>> {
+    let mut models = match format {
+        ModelFormat::Zip => zip_models(file)?,
+        ModelFormat::Tar => tar_models(BufReader::new(file), false, |reader, len| {
+            reader.seek_relative(i64::try_from(len).map_err(|_| invalid_tar())?)
+        })?,
+        // The archive is decompressed from the start up to the first GGUF
+        // file, going past it would mean decompressing all of its tensors
+        ModelFormat::TarGz => {
+            tar_models(BufReader::new(GzDecoder::new(file)), true, |reader, len| {
+                io::copy(&mut reader.take(len), &mut io::sink()).map(drop)
+            })?
+        }
+        _ => Vec::new(),
+    };
+    models.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    Ok(models)
+}
+
+// The header of an entry read from its start
+fn read_header(mut reader: impl Read) -> io::Result<GgufHeader> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != GGUF_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a GGUF file",
+        ));
+    }
+    GgufHeader::parse(BufReader::new(reader))
+}
+
+const ZIP_END_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_END_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP_ENTRY_SIGNATURE: u32 = 0x0201_4b50;
+const ZIP_LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+// The end record is 22 bytes followed by a comment of up to 64 KiB
+const ZIP_END_SEARCH_LEN: u64 = 22 + 0xffff;
+const ZIP_STORED: u16 = 0;
+const ZIP_DEFLATED: u16 = 8;
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn invalid_zip(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("broken zip, {}", message),
+    )
+}
+
+// The central directory at the end of the archive lists every entry with
+// where its local header is, zip64 records take over for archives and
+// entries past 4 GiB
+fn zip_models(file: &mut fs::File) -> io::Result<Vec<PackedModel>> {
+    let len = file.metadata()?.len();
+    let tail_len = len.min(ZIP_END_SEARCH_LEN);
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = vec![0u8; tail_len as usize];
+    file.read_exact(&mut tail)?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&at| u32_at(&tail, at) == ZIP_END_SIGNATURE)
+        .ok_or_else(|| invalid_zip("no end of central directory"))?;
+
+    let mut entries = u64::from(u16_at(&tail, end + 10));
+    let mut directory_len = u64::from(u32_at(&tail, end + 12));
+    let mut directory_offset = u64::from(u32_at(&tail, end + 16));
+    if end >= 20 && u32_at(&tail, end - 20) == ZIP64_LOCATOR_SIGNATURE {
+        file.seek(SeekFrom::Start(u64_at(&tail, end - 12)))?;
+        let mut record = [0u8; 56];
+        file.read_exact(&mut record)?;
+        if u32_at(&record, 0) != ZIP64_END_SIGNATURE {
+            return Err(invalid_zip("no zip64 end of central directory"));
+        }
+        entries = u64_at(&record, 32);
+        directory_len = u64_at(&record, 40);
+        directory_offset = u64_at(&record, 48);
+    }
+    if directory_offset.saturating_add(directory_len) > len {
+        return Err(invalid_zip("central directory past the end"));
+    }
+
+    file.seek(SeekFrom::Start(directory_offset))?;
+    let mut directory = vec![0u8; directory_len as usize];
+    file.read_exact(&mut directory)?;
+    let mut at = 0;
+    let mut models = Vec::new();
+    for _ in 0..entries {
+        if at + 46 > directory.len() || u32_at(&directory, at) != ZIP_ENTRY_SIGNATURE {
+            return Err(invalid_zip("central directory cut off"));
+        }
+        let method = u16_at(&directory, at + 10);
+        let mut compressed = u64::from(u32_at(&directory, at + 20));
+        let mut size = u64::from(u32_at(&directory, at + 24));
+        let name_len = usize::from(u16_at(&directory, at + 28));
+        let extra_len = usize::from(u16_at(&directory, at + 30));
+        let comment_len = usize::from(u16_at(&directory, at + 32));
+        let mut offset = u64::from(u32_at(&directory, at + 42));
+        let name_start = at + 46;
+        let extra_start = name_start + name_len;
+        let next = extra_start + extra_len + comment_len;
+        if next > directory.len() {
+            return Err(invalid_zip("central directory cut off"));
+        }
+        let name = String::from_utf8_lossy(&directory[name_start..extra_start]).into_owned();
+
+        // The zip64 extra field holds the values that didn't fit, in this
+        // order
+        let mut extra = &directory[extra_start..extra_start + extra_len];
+        while extra.len() >= 4 {
+            let (id, field_len) = (u16_at(extra, 0), usize::from(u16_at(extra, 2)));
+            let field = &extra[4..(4 + field_len).min(extra.len())];
+            if id == 0x0001 {
+                let mut values = field.chunks_exact(8).map(|value| u64_at(value, 0));
+                for target in [&mut size, &mut compressed, &mut offset] {
+                    if *target == u64::from(u32::MAX) {
+                        *target = values.next().unwrap_or(*target);
+                    }
+                }
+            }
+            extra = &extra[(4 + field_len).min(extra.len())..];
+        }
+        at = next;
+
+        if !is_gguf_name(&name) {
+            continue;
+        }
+        let header = zip_entry_header(file, offset, compressed, method);
+        models.push(PackedModel { name, size, header });
+    }
+    Ok(models)
+}
+
+fn zip_entry_header(
+    file: &mut fs::File,
+    offset: u64,
+    compressed: u64,
+    method: u16,
+) -> io::Result<GgufHeader> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut local = [0u8; 30];
+    file.read_exact(&mut local)?;
+    if u32_at(&local, 0) != ZIP_LOCAL_SIGNATURE {
+        return Err(invalid_zip("no local header"));
+    }
+    let skip = u64::from(u16_at(&local, 26)) + u64::from(u16_at(&local, 28));
+    file.seek(SeekFrom::Current(skip as i64))?;
+    let data = (&mut *file).take(compressed);
+    match method {
+        ZIP_STORED => read_header(data),
+        ZIP_DEFLATED => read_header(DeflateDecoder::new(data)),
+        method => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("zip compression method {}", method),
+        )),
+    }
+}
+
+const TAR_BLOCK_LEN: u64 = 512;
+
+// Sizes are octal text, or big-endian binary behind a set high bit for
+// files of 8 GiB and more
+fn tar_number(field: &[u8]) -> io::Result<u64> {
+    if field.first().is_some_and(|byte| byte & 0x80 != 0) {
+        return Ok(field[1..]
+            .iter()
+            .fold(0u64, |value, &byte| (value << 8) | u64::from(byte)));
+    }
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| invalid_tar())
+}
+
+fn invalid_tar() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "broken tar header")
+}
+
+fn tar_text(field: &[u8]) -> String {
+    let end = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+// Entries follow each other, a 512 byte header and the data padded to a
+// whole block. GNU long names and pax records come as entries of their own
+// before the one they are about. The data of other entries is skipped with
+// `skip`, with `first_only` the listing ends at the first GGUF file.
+fn tar_models<R: Read>(
+    mut reader: R,
+    first_only: bool,
+    mut skip: impl FnMut(&mut R, u64) -> io::Result<()>,
+) -> io::Result<Vec<PackedModel>> {
+    let mut models = Vec::new();
+    let mut long_name: Option<String> = None;
+    let mut pax_size: Option<u64> = None;
+    let mut block = [0u8; TAR_BLOCK_LEN as usize];
+    loop {
+        match reader.read_exact(&mut block) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        // Two empty blocks end the archive
+        if block.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let size = pax_size
+            .take()
+            .map_or_else(|| tar_number(&block[124..136]), Ok)?;
+        let padded = size.div_ceil(TAR_BLOCK_LEN).saturating_mul(TAR_BLOCK_LEN);
+        let kind = block[156];
+        let mut name = tar_text(&block[..100]);
+        if &block[257..262] == b"ustar" {
+            let prefix = tar_text(&block[345..500]);
+            if !prefix.is_empty() {
+                name = format!("{}/{}", prefix, name);
+            }
+        }
+        if let Some(long_name) = long_name.take() {
+            name = long_name;
+        }
+
+        match kind {
+            b'L' | b'x' => {
+                let mut data = Vec::new();
+                (&mut reader).take(padded).read_to_end(&mut data)?;
+                data.truncate(size as usize);
+                if kind == b'L' {
+                    long_name = Some(tar_text(&data));
+                } else {
+                    for (key, value) in pax_records(&data) {
+                        match key.as_str() {
+                            "path" => long_name = Some(value),
+                            "size" => pax_size = value.parse().ok(),
+                            _ => {}
+                        }
+                    }
+                }
+                continue;
+            }
+            b'0' | b'\0' if is_gguf_name(&name) => {
+                let mut data = (&mut reader).take(padded);
+                let header = read_header(&mut data);
+                let rest = data.limit();
+                models.push(PackedModel { name, size, header });
+                if first_only {
+                    break;
+                }
+                skip(&mut reader, rest)?;
+            }
+            _ => skip(&mut reader, padded)?,
+        }
+    }
+    Ok(models)
+}
+
+// `<length> <key>=<value>\n` records
+fn pax_records(data: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(|record| {
+            let (_, record) = record.split_once(' ')?;
+            let (key, value) = record.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder},
+        Compression,
+    };
+    use std::{io::Write, path::PathBuf};
+
+    // A header with no tensors and the architecture as its only metadata
+    fn gguf(architecture: &str, padding: usize) -> Vec<u8> {
+        let mut bytes = GGUF_MAGIC.to_vec();
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        let string = |bytes: &mut Vec<u8>, text: &str| {
+            bytes.extend_from_slice(&(text.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(text.as_bytes());
+        };
+        string(&mut bytes, "general.architecture");
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+        string(&mut bytes, architecture);
+        bytes.resize(bytes.len() + padding, 0);
+        bytes
+    }
+
+    struct ZipEntry<'a> {
+        name: &'a str,
+        data: &'a [u8],
+        method: u16,
+        zip64: bool,
+    }
+
+    fn entry<'a>(name: &'a str, data: &'a [u8]) -> ZipEntry<'a> {
+        ZipEntry {
+            name,
+            data,
+            method: ZIP_STORED,
+            zip64: false,
+        }
+    }
+
+    // Local headers and data, then the central directory and its end
+    // record, zip64 ones too when any entry uses them
+    fn zip(entries: &[ZipEntry]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut directory = Vec::new();
+        for entry in entries {
+            let offset = bytes.len() as u64;
+            let data = match entry.method {
+                ZIP_DEFLATED => {
+                    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(entry.data).unwrap();
+                    encoder.finish().unwrap()
+                }
+                _ => entry.data.to_vec(),
+            };
+            bytes.extend_from_slice(&ZIP_LOCAL_SIGNATURE.to_le_bytes());
+            bytes.extend_from_slice(&[0; 22]);
+            bytes.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&0u16.to_le_bytes());
+            bytes.extend_from_slice(entry.name.as_bytes());
+            bytes.extend_from_slice(&data);
+
+            let (fixed, extra) = if entry.zip64 {
+                let mut extra = vec![0x01, 0x00, 24, 0x00];
+                for value in [entry.data.len() as u64, data.len() as u64, offset] {
+                    extra.extend_from_slice(&value.to_le_bytes());
+                }
+                ([u32::MAX; 3], extra)
+            } else {
+                (
+                    [data.len() as u32, entry.data.len() as u32, offset as u32],
+                    Vec::new(),
+                )
+            };
+            directory.extend_from_slice(&ZIP_ENTRY_SIGNATURE.to_le_bytes());
+            directory.extend_from_slice(&[0; 6]);
+            directory.extend_from_slice(&entry.method.to_le_bytes());
+            directory.extend_from_slice(&[0; 8]);
+            directory.extend_from_slice(&fixed[0].to_le_bytes());
+            directory.extend_from_slice(&fixed[1].to_le_bytes());
+            directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&fixed[2].to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+            directory.extend_from_slice(&extra);
+        }
+        let directory_offset = bytes.len() as u64;
+        bytes.extend_from_slice(&directory);
+        if entries.iter().any(|entry| entry.zip64) {
+            let record_offset = bytes.len() as u64;
+            bytes.extend_from_slice(&ZIP64_END_SIGNATURE.to_le_bytes());
+            bytes.extend_from_slice(&[0; 28]);
+            for value in [
+                entries.len() as u64,
+                directory.len() as u64,
+                directory_offset,
+            ] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+            bytes.extend_from_slice(&0u32.to_le_bytes());
+            bytes.extend_from_slice(&record_offset.to_le_bytes());
+            bytes.extend_from_slice(&1u32.to_le_bytes());
+        }
+        bytes.extend_from_slice(&ZIP_END_SIGNATURE.to_le_bytes());
+        bytes.extend_from_slice(&[0; 6]);
+        bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(directory_offset as u32).to_le_bytes());
+        // A comment the end record has to be searched past
+        bytes.extend_from_slice(&7u16.to_le_bytes());
+        bytes.extend_from_slice(b"comment");
+        bytes
+    }
+
+    fn tar_header(name: &str, size: u64, kind: u8) -> Vec<u8> {
+        let mut block = vec![0u8; TAR_BLOCK_LEN as usize];
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        block[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+        block[156] = kind;
+        block
+    }
+
+    fn tar_entry(tar: &mut Vec<u8>, mut header: Vec<u8>, data: &[u8]) {
+        tar.append(&mut header);
+        tar.extend_from_slice(data);
+        let padded = tar.len().div_ceil(TAR_BLOCK_LEN as usize) * TAR_BLOCK_LEN as usize;
+        tar.resize(padded, 0);
+    }
+
+    fn tar_end(tar: &mut Vec<u8>) {
+        tar.resize(tar.len() + 2 * TAR_BLOCK_LEN as usize, 0);
+    }
+
+    // Writes the archive to a temporary file to list it like the scan does
+    fn models(name: &str, bytes: &[u8], format: ModelFormat) -> io::Result<Vec<PackedModel>> {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("ggufscan-test-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let models = packed_models(&mut fs::File::open(&path).unwrap(), format);
+        fs::remove_file(&path).unwrap();
+        models
+    }
+
+    fn listing(models: &[PackedModel]) -> Vec<(&str, u64, Option<&str>)> {
+        models
+            .iter()
+            .map(|model| {
+                let header = model.header.as_ref().ok();
+                (
+                    model.name.as_str(),
+                    model.size,
+                    header.and_then(GgufHeader::architecture),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recognizes_archive_names() {
+        for name in ["a.zip", "a.TAR", "a.tar.gz", "a.tgz"] {
+            assert!(is_pack_name(Path::new(name)), "{}", name);
+        }
+        for name in ["a.gguf", "a.gz", "zip", "a.tar.xz"] {
+            assert!(!is_pack_name(Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn lists_zip_entries() {
+        let (llama, phi) = (gguf("llama", 1000), gguf("phi3", 10));
+        let bytes = zip(&[
+            entry("README.md", b"# models"),
+            entry("phi.gguf", &phi),
+            ZipEntry {
+                method: ZIP_DEFLATED,
+                ..entry("dir/llama.GGUF", &llama)
+            },
+        ]);
+        let models = models("entries.zip", &bytes, ModelFormat::Zip).unwrap();
+        assert_eq!(
+            listing(&models),
+            [
+                ("dir/llama.GGUF", llama.len() as u64, Some("llama")),
+                ("phi.gguf", phi.len() as u64, Some("phi3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_zip64_records() {
+        let llama = gguf("llama", 0);
+        let bytes = zip(&[
+            entry("a.txt", b"text"),
+            ZipEntry {
+                zip64: true,
+                ..entry("llama.gguf", &llama)
+            },
+        ]);
+        let models = models("zip64.zip", &bytes, ModelFormat::Zip).unwrap();
+        assert_eq!(
+            listing(&models),
+            [("llama.gguf", llama.len() as u64, Some("llama"))]
+        );
+    }
+
+    #[test]
+    fn keeps_zip_entries_whose_header_fails() {
+        let bytes = zip(&[
+            entry("text.gguf", b"not a model"),
+            ZipEntry {
+                method: 14,
+                ..entry("lzma.gguf", &gguf("llama", 0))
+            },
+        ]);
+        let models = models("failing.zip", &bytes, ModelFormat::Zip).unwrap();
+        assert_eq!(models.len(), 2);
+        let error = |name| {
+            let model = models.iter().find(|model| model.name == name).unwrap();
+            model.header.as_ref().unwrap_err().kind()
+        };
+        assert_eq!(error("lzma.gguf"), io::ErrorKind::Unsupported);
+        assert_eq!(error("text.gguf"), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_broken_zips() {
+        let bytes = zip(&[entry("llama.gguf", &gguf("llama", 0))]);
+        let error = |name, bytes: &[u8]| models(name, bytes, ModelFormat::Zip).unwrap_err();
+
+        assert!(error("empty.zip", &[]).to_string().contains("no end"));
+        let end = bytes.len() - 29;
+        // More entries than the directory holds
+        let mut more = bytes.clone();
+        more[end + 10] = 2;
+        assert!(error("more.zip", &more).to_string().contains("cut off"));
+        // A directory reaching past the end of the file
+        let mut past = bytes.clone();
+        past[end + 12..end + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(error("past.zip", &past)
+            .to_string()
+            .contains("past the end"));
+        // Cuts losing the end record
+        for len in [bytes.len() / 2, bytes.len() - 30] {
+            assert!(models("cut.zip", &bytes[..len], ModelFormat::Zip).is_err());
+        }
+    }
+
+    #[test]
+    fn lists_tar_entries() {
+        let (llama, phi) = (gguf("llama", 1000), gguf("phi3", 10));
+        let long_name = format!("{}/llama.gguf", "nested".repeat(30));
+        let mut tar = Vec::new();
+        tar_entry(&mut tar, tar_header("README.md", 8, b'0'), b"# models");
+        tar_entry(&mut tar, tar_header("dir/", 0, b'5'), &[]);
+        tar_entry(
+            &mut tar,
+            tar_header("././@LongLink", long_name.len() as u64 + 1, b'L'),
+            format!("{}\0", long_name).as_bytes(),
+        );
+        tar_entry(
+            &mut tar,
+            tar_header("cut", llama.len() as u64, b'0'),
+            &llama,
+        );
+        let mut ustar = tar_header("phi.gguf", phi.len() as u64, b'\0');
+        ustar[257..262].copy_from_slice(b"ustar");
+        ustar[345..351].copy_from_slice(b"models");
+        tar_entry(&mut tar, ustar, &phi);
+        tar_end(&mut tar);
+
+        let models = models("entries.tar", &tar, ModelFormat::Tar).unwrap();
+        assert_eq!(
+            listing(&models),
+            [
+                (long_name.as_str(), llama.len() as u64, Some("llama")),
+                ("models/phi.gguf", phi.len() as u64, Some("phi3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_pax_records() {
+        let llama = gguf("llama", 100);
+        let records = format!(
+            "30 path=models/pax-llama.gguf\n{} size={}\n",
+            9 + llama.len().to_string().len(),
+            llama.len()
+        );
+        let mut tar = Vec::new();
+        tar_entry(
+            &mut tar,
+            tar_header("PaxHeader", records.len() as u64, b'x'),
+            records.as_bytes(),
+        );
+        // The size in the header itself is replaced by the pax one
+        tar_entry(&mut tar, tar_header("short", 1, b'0'), &llama);
+        tar_entry(&mut tar, tar_header("after.gguf", 4, b'0'), b"GGUF");
+        tar_end(&mut tar);
+
+        let models = models("pax.tar", &tar, ModelFormat::Tar).unwrap();
+        assert_eq!(
+            listing(&models),
+            [
+                ("models/pax-llama.gguf", llama.len() as u64, Some("llama")),
+                ("after.gguf", 4, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_compressed_tars_at_the_first_model() {
+        let (llama, phi) = (gguf("llama", 0), gguf("phi3", 1000));
+        let mut tar = Vec::new();
+        tar_entry(&mut tar, tar_header("README.md", 8, b'0'), b"# models");
+        tar_entry(
+            &mut tar,
+            tar_header("llama.gguf", llama.len() as u64, b'0'),
+            &llama,
+        );
+        tar_entry(
+            &mut tar,
+            tar_header("phi.gguf", phi.len() as u64, b'0'),
+            &phi,
+        );
+        tar_end(&mut tar);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar).unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        let models = models("models.tar.gz", &bytes, ModelFormat::TarGz).unwrap();
+        assert_eq!(
+            listing(&models),
+            [("llama.gguf", llama.len() as u64, Some("llama"))]
+        );
+    }
+
+    #[test]
+    fn tars_without_an_end_block_end_at_the_end_of_the_file() {
+        let llama = gguf("llama", 0);
+        let mut tar = Vec::new();
+        tar_entry(
+            &mut tar,
+            tar_header("llama.gguf", llama.len() as u64, b'0'),
+            &llama,
+        );
+        let models = models("unended.tar", &tar, ModelFormat::Tar).unwrap();
+        assert_eq!(models.len(), 1);
+    }
+
+    #[test]
+    fn survives_huge_tar_sizes() {
+        let mut binary = tar_header("huge.bin", 0, b'0');
+        binary[124..136].fill(0xff);
+        let mut tar = Vec::new();
+        tar_entry(&mut tar, binary, &[]);
+        tar_end(&mut tar);
+        assert!(models("binary.tar", &tar, ModelFormat::Tar).is_err());
+
+        let records = format!("27 size={}\n", u64::MAX);
+        let mut tar = Vec::new();
+        tar_entry(
+            &mut tar,
+            tar_header("PaxHeader", records.len() as u64, b'x'),
+            records.as_bytes(),
+        );
+        tar_entry(
+            &mut tar,
+            tar_header("huge.gguf", 0, b'0'),
+            &gguf("llama", 0),
+        );
+        tar_end(&mut tar);
+        assert!(models("pax-size.tar", &tar, ModelFormat::Tar).is_err());
+    }
+
+    #[test]
+    fn parses_tar_numbers() {
+        assert_eq!(tar_number(b"00000001750\0").unwrap(), 1000);
+        assert_eq!(tar_number(b" 1750 \0").unwrap(), 1000);
+        assert_eq!(tar_number(b"\0\0\0\0").unwrap(), 0);
+        let mut binary = [0u8; 12];
+        binary[0] = 0x80;
+        binary[7..].copy_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(tar_number(&binary).unwrap(), 8 << 30);
+        assert!(tar_number(b"12389\0").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Moving deleted files aside instead of removing them, under the path they
+//! had so they can be put back.
+
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::relocate::move_file;
+
+/// `$XDG_DATA_HOME/ggufscan/quarantine`, `%LOCALAPPDATA%` on Windows and
+/// `~/.local/share` otherwise.
+pub fn default_quarantine_dir() -> Option<PathBuf> {
+    Some(data_dir()?.join("quarantine"))
+}
+
+// `$XDG_DATA_HOME/ggufscan`, where the files of our own that aren't a cache
+// are kept
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })?;
+    Some(dir.join("ggufscan"))
+}
+
+/// A file in the quarantine directory and where it came from.
+#[derive(Debug, Clone)]
+pub struct QuarantinedFile {
+    pub path: PathBuf,
+    pub original: PathBuf,
+    pub size: u64,
+}
+
+/// Moves the file or directory into `quarantine` below its absolute path,
+/// `/models/a.gguf` ends up as `<quarantine>/models/a.gguf`.
+pub fn quarantine_file(path: &Path, quarantine: &Path) -> io::Result<PathBuf> {
+    let dest = quarantine_path(path, quarantine)?;
+    let dest_dir = dest
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    fs::create_dir_all(dest_dir)?;
+
+    // Directories are only renamed, copying a snapshot of symlinks across
+    // drives isn't worth it
+    if path.symlink_metadata()?.is_dir() {
+        if dest.symlink_metadata().is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", dest.display()),
+            ));
+        }
+        fs::rename(path, &dest)?;
+        Ok(dest)
+    } else {
+        move_file(path, dest_dir, |_| {})
+    }
+}
+
+/// Puts a file moved away by [`quarantine_file`] back where it was.
+pub fn restore_quarantined(original: &Path, quarantine: &Path) -> io::Result<()> {
+    let path = quarantine_path(original, quarantine)?;
+    let dest_dir = original
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    fs::create_dir_all(dest_dir)?;
+    if path.symlink_metadata()?.is_dir() {
+        if original.symlink_metadata().is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", original.display()),
+            ));
+        }
+        fs::rename(&path, original)?;
+    } else {
+        move_file(&path, dest_dir, |_| {})?;
+    }
+    remove_empty_parents(&path, quarantine);
+    Ok(())
+}
+
+/// Every file in the quarantine directory, empty when there is none.
+pub fn quarantined_files(quarantine: &Path) -> io::Result<Vec<QuarantinedFile>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![quarantine.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && dir == quarantine => break,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.path().symlink_metadata()?;
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+                continue;
+            }
+            let Some(original) = original_path(&entry.path(), quarantine) else {
+                continue;
+            };
+            files.push(QuarantinedFile {
+                path: entry.path(),
+                original,
+                size: metadata.len(),
+            });
+        }
+    }
+    files.sort_by(|a, b| a.original.cmp(&b.original));
+    Ok(files)
+}
+
+/// Removes a quarantined file for good, along with the directories left
+/// empty.
+pub fn purge_quarantined(file: &QuarantinedFile, quarantine: &Path) -> io::Result<()> {
+    fs::remove_file(&file.path)?;
+    remove_empty_parents(&file.path, quarantine);
+    Ok(())
+}
+
+pub(crate) fn quarantine_path(path: &Path, quarantine: &Path) -> io::Result<PathBuf> {
+    let mut dest = quarantine.to_path_buf();
+    for component in std::path::absolute(path)?.components() {
+        match component {
+            // `C:` becomes a `C` directory
+            Component::Prefix(prefix) => dest.push(
+                prefix
+                    .as_os_str()
+                    .to_string_lossy()
+                    .replace([':', '\\', '?', '.'], ""),
+            ),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => {
+                if dest != quarantine {
+                    dest.pop();
+                }
+            }
+            Component::Normal(name) => dest.push(name),
+        }
+    }
+    Ok(dest)
+}
+
+pub(crate) fn original_path(path: &Path, quarantine: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(quarantine).ok()?;
+    if cfg!(windows) {
+        let mut components = relative.components();
+        let drive = components.next()?.as_os_str().to_string_lossy();
+        Some(PathBuf::from(format!("{}:\\", drive)).join(components.as_path()))
+    } else {
+        Some(Path::new("/").join(relative))
+    }
+}
+
+pub(crate) fn remove_empty_parents(path: &Path, quarantine: &Path) {
+    let mut dir = path.parent();
+    while let Some(parent) = dir {
+        if parent == quarantine
+            || !parent.starts_with(quarantine)
+            || fs::remove_dir(parent).is_err()
+        {
+            break;
+        }
+        dir = parent.parent();
+    }
+}
@@ -0,0 +1,72 @@
+//! Moving files to another directory, on another drive too.
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const COPY_CHUNK_LEN: usize = 8 * 1024 * 1024;
+
+/// Moves the file into `dest_dir` and returns its new path. Across
+/// filesystems it is copied and the original removed afterwards, `progress`
+/// is called with the number of bytes copied so far.
+pub fn move_file(
+    path: &Path,
+    dest_dir: &Path,
+    mut progress: impl FnMut(u64),
+) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    let dest = dest_dir.join(name);
+    if dest.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", dest.display()),
+        ));
+    }
+
+    match fs::rename(path, &dest) {
+        Ok(()) => return Ok(dest),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(e),
+    }
+    copy_file(path, &dest, &mut progress)?;
+    fs::remove_file(path)?;
+    Ok(dest)
+}
+
+fn copy_file(from: &Path, to: &Path, progress: &mut impl FnMut(u64)) -> io::Result<()> {
+    let mut reader = fs::File::open(from)?;
+    let metadata = reader.metadata()?;
+    let mut writer = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)?;
+
+    let result = (|| {
+        let mut buffer = vec![0u8; COPY_CHUNK_LEN];
+        let mut copied = 0;
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read])?;
+            copied += read as u64;
+            progress(copied);
+        }
+        writer.set_permissions(metadata.permissions())?;
+        if let Ok(modified) = metadata.modified() {
+            writer.set_modified(modified)?;
+        }
+        writer.sync_all()
+    })();
+
+    // A partial copy is worse than none
+    if result.is_err() {
+        fs::remove_file(to).ok();
+    }
+    result
+}
@@ -0,0 +1,360 @@
+//! Scanning another machine over SSH. `ggufscan` runs there with
+//! `--remote-helper` and writes every scan message as a line of JSON, whole
+//! headers included, which are read back here as if the scan ran locally.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    scan::CHANNEL_CAPACITY, FileId, FileInfo, GgufHeader, ModelFormat, ModelSource, ScanControl,
+    ScanMessage, ScanProgress, Shard,
+};
+
+// How often a cancelled scan checks whether to stop the remote side, which
+// may be quiet for a long time while hashing or watching
+const CANCEL_INTERVAL: Duration = Duration::from_millis(100);
+
+// What the helper sends of a file, the shard and duplicate group are worked
+// out again on this side
+#[derive(Serialize, Deserialize)]
+struct RemoteFile {
+    path: PathBuf,
+    root: PathBuf,
+    size: u64,
+    modified: Option<SystemTime>,
+    accessed: Option<SystemTime>,
+    format: ModelFormat,
+    header: Option<GgufHeader>,
+    file_id: Option<FileId>,
+    links: u64,
+    symlink: bool,
+    readonly: bool,
+    source: Option<ModelSource>,
+    corrupt: Option<String>,
+    packed: Vec<String>,
+}
+
+// One line of the helper's output
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum RemoteEvent {
+    File(Box<RemoteFile>),
+    CacheLoaded {
+        files: usize,
+    },
+    Directory {
+        path: String,
+    },
+    Progress(ScanProgress),
+    RootProgress {
+        root: PathBuf,
+        progress: ScanProgress,
+    },
+    RootDone {
+        root: PathBuf,
+    },
+    Done,
+    CheckingDuplicates,
+    DuplicateGroup {
+        paths: Vec<PathBuf>,
+    },
+    DuplicatesDone,
+    Watching,
+    Removed {
+        path: PathBuf,
+    },
+    Cancelled,
+    Error {
+        message: String,
+    },
+}
+
+impl From<&ScanMessage> for RemoteEvent {
+    fn from(message: &ScanMessage) -> Self {
+        match message {
+            ScanMessage::File(file) => RemoteEvent::File(Box::new(RemoteFile {
+                path: file.path.clone(),
+                root: file.root.clone(),
+                size: file.size,
+                modified: file.modified,
+                accessed: file.accessed,
+                format: file.format,
+                header: file.header.clone(),
+                file_id: file.file_id,
+                links: file.links,
+                symlink: file.symlink,
+                readonly: file.readonly,
+                source: file.source.clone(),
+                corrupt: file.corrupt.clone(),
+                packed: file.packed.clone(),
+            })),
+            ScanMessage::CacheLoaded(files) => RemoteEvent::CacheLoaded { files: *files },
+            ScanMessage::Directory(path) => RemoteEvent::Directory { path: path.clone() },
+            ScanMessage::Progress(progress) => RemoteEvent::Progress(*progress),
+            ScanMessage::RootProgress(root, progress) => RemoteEvent::RootProgress {
+                root: root.clone(),
+                progress: *progress,
+            },
+            ScanMessage::RootDone(root) => RemoteEvent::RootDone { root: root.clone() },
+            ScanMessage::Done => RemoteEvent::Done,
+            ScanMessage::CheckingDuplicates => RemoteEvent::CheckingDuplicates,
+            ScanMessage::DuplicateGroup(paths) => RemoteEvent::DuplicateGroup {
+                paths: paths.clone(),
+            },
+            ScanMessage::DuplicatesDone => RemoteEvent::DuplicatesDone,
+            ScanMessage::Watching => RemoteEvent::Watching,
+            ScanMessage::Removed(path) => RemoteEvent::Removed { path: path.clone() },
+            ScanMessage::Cancelled => RemoteEvent::Cancelled,
+            ScanMessage::Error(message) => RemoteEvent::Error {
+                message: message.clone(),
+            },
+        }
+    }
+}
+
+impl RemoteEvent {
+    // Paths of the other machine become `host:/path`
+    fn into_message(self, host: &str) -> ScanMessage {
+        let local = |path: &Path| remote_path(host, path);
+        match self {
+            RemoteEvent::File(file) => {
+                let path = local(&file.path);
+                let shard = Shard::parse(&path, file.header.as_ref());
+                let source = file.source.map(|source| match source {
+                    ModelSource::HuggingFace {
+                        repo,
+                        repo_dir,
+                        revisions,
+                    } => ModelSource::HuggingFace {
+                        repo,
+                        repo_dir: local(&repo_dir),
+                        revisions,
+                    },
+                    source => source,
+                });
+                ScanMessage::File(Box::new(FileInfo {
+                    path,
+                    size: file.size,
+                    root: local(&file.root),
+                    modified: file.modified,
+                    accessed: file.accessed,
+                    format: file.format,
+                    header: file.header,
+                    duplicate_group: None,
+                    file_id: file.file_id,
+                    links: file.links,
+                    symlink: file.symlink,
+                    readonly: file.readonly,
+                    source,
+                    shard,
+                    corrupt: file.corrupt,
+                    packed: file.packed,
+                }))
+            }
+            RemoteEvent::CacheLoaded { files } => ScanMessage::CacheLoaded(files),
+            RemoteEvent::Directory { path } => ScanMessage::Directory(format!("{}:{}", host, path)),
+            RemoteEvent::Progress(progress) => ScanMessage::Progress(progress),
+            RemoteEvent::RootProgress { root, progress } => {
+                ScanMessage::RootProgress(local(&root), progress)
+            }
+            RemoteEvent::RootDone { root } => ScanMessage::RootDone(local(&root)),
+            RemoteEvent::Done => ScanMessage::Done,
+            RemoteEvent::CheckingDuplicates => ScanMessage::CheckingDuplicates,
+            RemoteEvent::DuplicateGroup { paths } => {
+                ScanMessage::DuplicateGroup(paths.iter().map(|path| local(path)).collect())
+            }
+            RemoteEvent::DuplicatesDone => ScanMessage::DuplicatesDone,
+            RemoteEvent::Watching => ScanMessage::Watching,
+            RemoteEvent::Removed { path } => ScanMessage::Removed(local(&path)),
+            RemoteEvent::Cancelled => ScanMessage::Cancelled,
+            RemoteEvent::Error { message } => ScanMessage::Error(format!("{}: {}", host, message)),
+        }
+    }
+}
+
+/// Writes the message as one line for a [`RemoteScanner`] on the other end
+/// of the connection to read.
+pub fn write_remote_message<W: Write>(writer: &mut W, message: &ScanMessage) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, &RemoteEvent::from(message))?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// How a path on another machine is shown, `host:/path` like `scp` takes
+/// it. Nothing on this machine is found under it.
+pub fn remote_path(host: &str, path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}:{}", host, path.display()))
+}
+
+/// Scans directories of another machine by running `ggufscan` there over
+/// `ssh`, which has to log in without asking for a password.
+pub struct RemoteScanner {
+    host: String,
+    roots: Vec<PathBuf>,
+    helper: String,
+    args: Vec<String>,
+    control: ScanControl,
+}
+
+impl RemoteScanner {
+    /// `helper` is the `ggufscan` to run on `host`, `args` are passed to it
+    /// along with the roots, e.g. `--min-size 1G`.
+    pub fn new(host: String, roots: Vec<PathBuf>, helper: String, args: Vec<String>) -> Self {
+        Self {
+            host,
+            roots,
+            helper,
+            args,
+            control: ScanControl::default(),
+        }
+    }
+
+    /// A handle to pause or cancel the scan with. Pausing stops reading,
+    /// which holds up the other side once the connection is full.
+    pub fn control(&self) -> ScanControl {
+        self.control.clone()
+    }
+
+    pub fn spawn(self) -> Receiver<ScanMessage> {
+        let (tx, rx) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        thread::spawn(move || self.run(tx));
+        rx
+    }
+
+    // The command goes through the remote shell, so every word is quoted.
+    // `~/` stays outside the quotes to be expanded there.
+    fn command(&self) -> String {
+        let mut words = vec![shell_quote(&self.helper), "--remote-helper".to_string()];
+        words.extend(self.args.iter().map(|arg| shell_quote(arg)));
+        words.push("--".to_string());
+        for root in &self.roots {
+            let root = root.to_string_lossy();
+            words.push(match root.strip_prefix("~/") {
+                Some(rest) => format!("~/{}", shell_quote(rest)),
+                None => shell_quote(&root),
+            });
+        }
+        words.join(" ")
+    }
+
+    fn run(&self, tx: SyncSender<ScanMessage>) {
+        let child = Command::new("ssh")
+            .arg("-o")
+            .arg("BatchMode=yes")
+            .arg("--")
+            .arg(&self.host)
+            .arg(self.command())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                tx.send(ScanMessage::Error(format!("Error running ssh: {}", e)))
+                    .ok();
+                tx.send(ScanMessage::Cancelled).ok();
+                return;
+            }
+        };
+        let stdout = child.stdout.take().expect("piped stdout");
+        let mut stderr = child.stderr.take().expect("piped stderr");
+        let errors = thread::spawn(move || {
+            let mut errors = String::new();
+            stderr.read_to_string(&mut errors).ok();
+            errors
+        });
+        let child = Arc::new(Mutex::new(child));
+        let finished = Arc::new(AtomicBool::new(false));
+        let stopper = stop_when_cancelled(child.clone(), self.control.clone(), finished.clone());
+
+        let mut ended = false;
+        let mut invalid = false;
+        for line in BufReader::new(stdout).lines() {
+            self.control.wait_while_paused();
+            let Ok(line) = line else {
+                break;
+            };
+            let message = match serde_json::from_str::<RemoteEvent>(&line) {
+                Ok(event) => event.into_message(&self.host),
+                // The first line that isn't ours is told, the rest is
+                // likely more of the same
+                Err(_) if invalid => continue,
+                Err(_) => {
+                    invalid = true;
+                    ScanMessage::Error(format!(
+                        "Unexpected output from {} on {}: {}",
+                        self.helper, self.host, line
+                    ))
+                }
+            };
+            ended |= matches!(message, ScanMessage::Cancelled);
+            if tx.send(message).is_err() {
+                child.lock().unwrap().kill().ok();
+                break;
+            }
+        }
+
+        let status = child.lock().unwrap().wait();
+        finished.store(true, Ordering::Relaxed);
+        stopper.join().ok();
+        let errors = errors.join().unwrap_or_default();
+        if self.control.is_cancelled() {
+            tx.send(ScanMessage::Cancelled).ok();
+            return;
+        }
+        if ended {
+            return;
+        }
+        match status {
+            Ok(status) if status.success() => return,
+            Ok(status) => {
+                let errors = errors.trim();
+                tx.send(ScanMessage::Error(if errors.is_empty() {
+                    format!("ssh {} failed, {}", self.host, status)
+                } else {
+                    format!("ssh {}: {}", self.host, errors)
+                }))
+                .ok();
+            }
+            Err(e) => {
+                tx.send(ScanMessage::Error(format!("Error running ssh: {}", e)))
+                    .ok();
+            }
+        }
+        // Whatever the scan had left to do isn't coming anymore
+        tx.send(ScanMessage::Cancelled).ok();
+    }
+}
+
+// Kills `ssh` once the scan is cancelled, which also ends the reading above
+fn stop_when_cancelled(
+    child: Arc<Mutex<Child>>,
+    control: ScanControl,
+    finished: Arc<AtomicBool>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        while !finished.load(Ordering::Relaxed) {
+            if control.is_cancelled() {
+                child.lock().unwrap().kill().ok();
+                return;
+            }
+            thread::sleep(CANCEL_INTERVAL);
+        }
+    })
+}
+
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
@@ -0,0 +1,94 @@
+//! Deleting files, to the trash by default.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// Moves the file to the system trash unless a permanent delete was requested
+pub fn remove_file(path: &Path, permanent: bool) -> io::Result<()> {
+    if permanent {
+        fs::remove_file(path)
+    } else {
+        trash::delete(path).map_err(io::Error::other)
+    }
+}
+
+pub fn remove_dir(path: &Path, permanent: bool) -> io::Result<()> {
+    if permanent {
+        fs::remove_dir_all(path)
+    } else {
+        trash::delete(path).map_err(io::Error::other)
+    }
+}
+
+/// Puts paths moved to the trash by [`remove_file`] and [`remove_dir`] back
+/// where they were, the most recently trashed item is restored for each.
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+pub fn restore_from_trash(paths: &[PathBuf]) -> io::Result<()> {
+    use std::collections::HashMap;
+
+    // The trash keeps absolute paths, the parent directories are still there
+    let originals: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| {
+            let parent = path
+                .parent()
+                .and_then(|parent| fs::canonicalize(parent).ok());
+            match (parent, path.file_name()) {
+                (Some(parent), Some(name)) => parent.join(name),
+                _ => path.clone(),
+            }
+        })
+        .collect();
+
+    let mut latest: HashMap<PathBuf, trash::TrashItem> = HashMap::new();
+    for item in trash::os_limited::list().map_err(io::Error::other)? {
+        let original = item.original_path();
+        if !originals.contains(&original) {
+            continue;
+        }
+        if latest
+            .get(&original)
+            .is_none_or(|other| other.time_deleted < item.time_deleted)
+        {
+            latest.insert(original, item);
+        }
+    }
+
+    if latest.len() < originals.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} of {} items are no longer in the trash",
+                originals.len() - latest.len(),
+                originals.len()
+            ),
+        ));
+    }
+    trash::os_limited::restore_all(latest.into_values()).map_err(io::Error::other)
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+pub fn restore_from_trash(_paths: &[PathBuf]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "restoring from the trash isn't supported on this platform",
+    ))
+}
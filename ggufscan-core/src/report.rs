@@ -0,0 +1,153 @@
+//! Markdown inventory of the scanned files, for pasting into a wiki.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{format_date, format_day, format_size, unique_size, FileInfo, GgufHeader};
+
+/// What the sections of the report are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportGroup {
+    Directory,
+    Architecture,
+}
+
+impl ReportGroup {
+    fn label(self) -> &'static str {
+        match self {
+            ReportGroup::Directory => "Directory",
+            ReportGroup::Architecture => "Architecture",
+        }
+    }
+
+    // Paths go in code spans, underscores and asterisks in file names aren't
+    // markup
+    fn title(self, key: &str) -> String {
+        match self {
+            ReportGroup::Directory => format!("`{}`", escape(key)),
+            ReportGroup::Architecture => escape(key),
+        }
+    }
+
+    fn key(self, file: &FileInfo) -> String {
+        match self {
+            ReportGroup::Directory => file
+                .path
+                .parent()
+                .unwrap_or(Path::new(""))
+                .display()
+                .to_string(),
+            ReportGroup::Architecture => file
+                .header
+                .as_ref()
+                .and_then(GgufHeader::architecture)
+                .unwrap_or("unknown")
+                .to_string(),
+        }
+    }
+}
+
+pub(crate) fn write_markdown<W: Write>(
+    writer: &mut W,
+    group: ReportGroup,
+    roots: &[PathBuf],
+    files: &[FileInfo],
+) -> io::Result<()> {
+    let mut groups: BTreeMap<String, Vec<&FileInfo>> = BTreeMap::new();
+    for file in files {
+        groups.entry(group.key(file)).or_default().push(file);
+    }
+    // Biggest groups first, that is where the space goes
+    let mut groups: Vec<(String, Vec<&FileInfo>, u64)> = groups
+        .into_iter()
+        .map(|(key, files)| {
+            let size = unique_size(files.iter().copied());
+            (key, files, size)
+        })
+        .collect();
+    groups.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+
+    let roots: Vec<String> = roots
+        .iter()
+        .map(|root| format!("`{}`", root.display()))
+        .collect();
+    writeln!(writer, "# Model inventory")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "{} model files, {} in {} as of {}.",
+        files.len(),
+        format_size(unique_size(files.iter())),
+        roots.join(", "),
+        format_date(SystemTime::now())
+    )?;
+    writeln!(writer)?;
+
+    writeln!(writer, "| {} | Files | Size |", group.label())?;
+    writeln!(writer, "| --- | ---: | ---: |")?;
+    for (key, files, size) in &groups {
+        writeln!(
+            writer,
+            "| {} | {} | {} |",
+            group.title(key),
+            files.len(),
+            format_size(*size)
+        )?;
+    }
+
+    for (key, mut files, size) in groups {
+        files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "## {} ({} files, {})",
+            group.title(&key),
+            files.len(),
+            format_size(size)
+        )?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "| File | Format | Architecture | Quant | Params | Size | Modified |"
+        )?;
+        writeln!(writer, "| --- | --- | --- | --- | --- | ---: | --- |")?;
+        for file in files {
+            // Files are listed by name below their directory and by path
+            // otherwise
+            let name = match group {
+                ReportGroup::Directory => file
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                ReportGroup::Architecture => file.path.display().to_string(),
+            };
+            let header = file.header.as_ref();
+            writeln!(
+                writer,
+                "| `{}` | {} | {} | {} | {} | {} | {} |",
+                escape(&name),
+                file.format.label(),
+                escape(header.and_then(GgufHeader::architecture).unwrap_or("-")),
+                header.and_then(GgufHeader::quantization).unwrap_or("-"),
+                escape(
+                    &header
+                        .and_then(GgufHeader::size_class)
+                        .unwrap_or("-".to_string())
+                ),
+                format_size(file.size),
+                file.modified.map(format_day).unwrap_or("-".to_string())
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// A `|` would end the table cell
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|")
+}
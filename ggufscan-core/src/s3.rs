@@ -0,0 +1,925 @@
+//! Model files in S3 and compatible object storage like MinIO. Objects are
+//! listed, the leading bytes of those named like a model are read with a
+//! ranged GET for their format and GGUF header, never the tensor data.
+//! Requests are signed with the credentials the AWS CLI would use.
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+    },
+    thread,
+    time::SystemTime,
+};
+
+use crate::{
+    archive::is_archived_model,
+    format::detect_prefix,
+    percent::percent_decode,
+    scan::{parsed_header, CHANNEL_CAPACITY},
+    FileInfo, GgufHeader, ModelFormat, ScanControl, ScanMessage, ScanOptions, ScanProgress, Shard,
+    GGUF_MAGIC,
+};
+
+const SCHEME: &str = "s3://";
+// Bytes asked for from the start of an object, only as many as the header
+// takes are read before the connection is dropped
+const HEADER_RANGE: u64 = 256 * 1024 * 1024;
+// Enough for the format to be told from
+const PREFIX_LEN: u64 = 512;
+// Even requests for the unsigned default region work with MinIO
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// Whether the path is an object storage location, `s3://bucket/prefix`.
+pub fn is_s3_path(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with(SCHEME))
+}
+
+// `s3://bucket/key`, the key is a prefix for the roots of a scan
+struct S3Location {
+    bucket: String,
+    key: String,
+}
+
+impl S3Location {
+    fn parse(path: &Path) -> io::Result<Self> {
+        let location = path
+            .to_str()
+            .and_then(|path| path.strip_prefix(SCHEME))
+            .filter(|location| !location.is_empty() && !location.starts_with('/'))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not an s3://bucket/prefix location", path.display()),
+                )
+            })?;
+        let (bucket, key) = location.split_once('/').unwrap_or((location, ""));
+        Ok(Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    }
+
+    // Keys below the prefix as if it were a directory, `models` doesn't
+    // take in `models-old/`
+    fn list_prefix(&self) -> String {
+        if self.key.is_empty() || self.key.ends_with('/') {
+            self.key.clone()
+        } else {
+            format!("{}/", self.key)
+        }
+    }
+
+    fn object_path(&self, key: &str) -> PathBuf {
+        PathBuf::from(format!("{}{}/{}", SCHEME, self.bucket, key))
+    }
+}
+
+struct Credentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+// An object as it is listed
+struct S3Object {
+    key: String,
+    size: u64,
+    modified: Option<SystemTime>,
+    etag: String,
+}
+
+struct S3Client {
+    agent: ureq::Agent,
+    // `https://host[:port]`, with a custom endpoint the bucket goes into the
+    // path, on AWS into the host name
+    endpoint: Option<String>,
+    region: String,
+    credentials: Credentials,
+}
+
+impl S3Client {
+    // Configured like the AWS CLI: `AWS_ACCESS_KEY_ID` and the other
+    // variables first, then the profile in `~/.aws/credentials` and
+    // `~/.aws/config`. `AWS_ENDPOINT_URL` points at MinIO and the like.
+    fn from_env() -> io::Result<Self> {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let profile = env("AWS_PROFILE").unwrap_or_else(|| "default".to_string());
+        let aws_dir = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".aws"));
+        let credentials_file = env("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .or_else(|| aws_dir.as_ref().map(|dir| dir.join("credentials")));
+        let config_file = env("AWS_CONFIG_FILE")
+            .map(PathBuf::from)
+            .or_else(|| aws_dir.as_ref().map(|dir| dir.join("config")));
+        let mut stored = credentials_file
+            .map(|path| ini_section(&path, &profile))
+            .unwrap_or_default();
+        let config_section = if profile == "default" {
+            profile.clone()
+        } else {
+            format!("profile {}", profile)
+        };
+        let mut config = config_file
+            .map(|path| ini_section(&path, &config_section))
+            .unwrap_or_default();
+
+        let credentials = match (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key), Some(secret_key)) => Credentials {
+                access_key,
+                secret_key,
+                session_token: env("AWS_SESSION_TOKEN"),
+            },
+            _ => Credentials {
+                access_key: stored.remove("aws_access_key_id").ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "No S3 credentials, set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY \
+                         or add them to ~/.aws/credentials",
+                    )
+                })?,
+                secret_key: stored.remove("aws_secret_access_key").unwrap_or_default(),
+                session_token: stored.remove("aws_session_token"),
+            },
+        };
+        let region = env("AWS_REGION")
+            .or_else(|| env("AWS_DEFAULT_REGION"))
+            .or_else(|| config.remove("region"))
+            .unwrap_or_else(|| DEFAULT_REGION.to_string());
+        let endpoint = env("AWS_ENDPOINT_URL_S3")
+            .or_else(|| env("AWS_ENDPOINT_URL"))
+            .or_else(|| config.remove("endpoint_url"))
+            .map(|endpoint| endpoint.trim_end_matches('/').to_string());
+
+        let config = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .build();
+        Ok(Self {
+            agent: ureq::Agent::new_with_config(config),
+            endpoint,
+            region,
+            credentials,
+        })
+    }
+
+    // Signed with AWS Signature Version 4, the payload isn't hashed as there
+    // is none
+    fn request(
+        &self,
+        method: &str,
+        bucket: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        range: Option<u64>,
+    ) -> io::Result<ureq::http::Response<ureq::Body>> {
+        let (base, path) = match &self.endpoint {
+            Some(endpoint) => (endpoint.clone(), format!("/{}/{}", bucket, key)),
+            None => (
+                format!("https://{}.s3.{}.amazonaws.com", bucket, self.region),
+                format!("/{}", key),
+            ),
+        };
+        let host = base
+            .split_once("://")
+            .map_or(base.as_str(), |(_, host)| host)
+            .to_string();
+        let path = uri_encode(&path, false);
+        let query = canonical_query(query);
+
+        let now = chrono::Utc::now();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", "UNSIGNED-PAYLOAD".to_string()),
+            ("x-amz-date", timestamp.clone()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical = CanonicalRequest {
+            method,
+            path: &path,
+            query: &query,
+            headers: &headers,
+            payload_hash: "UNSIGNED-PAYLOAD",
+        };
+        let signed_headers = canonical.signed_headers();
+        let signature = canonical.signature(&self.credentials.secret_key, &self.region, &timestamp);
+        let scope = scope(&timestamp, &self.region);
+
+        let url = if query.is_empty() {
+            format!("{}{}", base, path)
+        } else {
+            format!("{}{}?{}", base, path, query)
+        };
+        let mut request = match method {
+            "DELETE" => self.agent.delete(&url),
+            _ => self.agent.get(&url),
+        };
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        request = request.header(
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.credentials.access_key, scope, signed_headers, signature
+            ),
+        );
+        if let Some(range) = range {
+            request = request.header("range", format!("bytes=0-{}", range - 1));
+        }
+        let mut response = request.call().map_err(io::Error::other)?;
+        if !response.status().is_success() {
+            // The error document names what went wrong, e.g.
+            // `SignatureDoesNotMatch` or `NoSuchBucket`
+            let body = response.body_mut().read_to_string().unwrap_or_default();
+            let reason = xml_value(&body, "Message")
+                .or_else(|| xml_value(&body, "Code"))
+                .map(xml_unescape)
+                .unwrap_or_else(|| response.status().to_string());
+            let kind = match response.status().as_u16() {
+                403 => io::ErrorKind::PermissionDenied,
+                404 => io::ErrorKind::NotFound,
+                _ => io::ErrorKind::Other,
+            };
+            return Err(io::Error::new(kind, reason));
+        }
+        Ok(response)
+    }
+
+    // One page of up to 1000 keys and the token for the next one
+    fn list(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        token: Option<&str>,
+    ) -> io::Result<(Vec<S3Object>, Option<String>)> {
+        let mut query = vec![("list-type", "2"), ("encoding-type", "url")];
+        if !prefix.is_empty() {
+            query.push(("prefix", prefix));
+        }
+        if let Some(token) = token {
+            query.push(("continuation-token", token));
+        }
+        let mut response = self.request("GET", bucket, "", &query, None)?;
+        let body = response
+            .body_mut()
+            .read_to_string()
+            .map_err(io::Error::other)?;
+        Ok(parse_listing(&body))
+    }
+}
+
+// The objects of a ListObjectsV2 response and the token for the next page
+// when it is cut off
+fn parse_listing(body: &str) -> (Vec<S3Object>, Option<String>) {
+    let objects = xml_blocks(body, "Contents")
+        .filter_map(|object| {
+            Some(S3Object {
+                // Keys come URL-encoded with `encoding-type=url`
+                key: percent_decode(xml_value(object, "Key")?),
+                size: xml_value(object, "Size")?.parse().ok()?,
+                modified: xml_value(object, "LastModified")
+                    .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+                    .map(SystemTime::from),
+                etag: xml_value(object, "ETag")
+                    .map(xml_unescape)
+                    .unwrap_or_default(),
+            })
+        })
+        .collect();
+    let next = (xml_value(body, "IsTruncated") == Some("true"))
+        .then(|| xml_value(body, "NextContinuationToken").map(xml_unescape))
+        .flatten();
+    (objects, next)
+}
+
+/// Deletes the object at `s3://bucket/key` for good, object storage has no
+/// trash to move it into.
+pub fn delete_object(path: &Path) -> io::Result<()> {
+    let location = S3Location::parse(path)?;
+    if location.key.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is a bucket, not an object", path.display()),
+        ));
+    }
+    S3Client::from_env()?
+        .request("DELETE", &location.bucket, &location.key, &[], None)
+        .map(|_| ())
+}
+
+/// Scans `s3://bucket/prefix` roots for model files, taking the depth,
+/// size, age and exclude options of a local scan. Duplicates are found by
+/// the ETags of the objects rather than by reading them.
+pub struct S3Scanner {
+    options: ScanOptions,
+    control: ScanControl,
+}
+
+impl S3Scanner {
+    pub fn new(options: ScanOptions) -> Self {
+        Self {
+            options,
+            control: ScanControl::default(),
+        }
+    }
+
+    pub fn control(&self) -> ScanControl {
+        self.control.clone()
+    }
+
+    pub fn spawn(self) -> Receiver<ScanMessage> {
+        let (tx, rx) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        thread::spawn(move || self.run(tx));
+        rx
+    }
+
+    pub fn run(&self, tx: SyncSender<ScanMessage>) {
+        let client = match S3Client::from_env() {
+            Ok(client) => Some(client),
+            Err(e) => {
+                tx.send(ScanMessage::Error(e.to_string())).ok();
+                None
+            }
+        };
+        let mut found = Vec::new();
+        for root in &self.options.roots {
+            if let Some(client) = &client {
+                if let Err(e) = self.scan_root(client, root, &mut found, &tx) {
+                    tx.send(ScanMessage::Error(format!(
+                        "Error listing {}: {}",
+                        root.display(),
+                        e
+                    )))
+                    .ok();
+                }
+            }
+            if self.control.is_cancelled() {
+                tx.send(ScanMessage::Cancelled).ok();
+                return;
+            }
+            tx.send(ScanMessage::RootDone(root.clone())).ok();
+        }
+        tx.send(ScanMessage::Done).ok();
+
+        if !self.options.find_duplicates {
+            return;
+        }
+        // Equal ETags of equal sizes are the same content, also for
+        // multipart uploads with the same part size
+        tx.send(ScanMessage::CheckingDuplicates).ok();
+        let mut by_etag: HashMap<(u64, String), Vec<PathBuf>> = HashMap::new();
+        for (path, size, etag) in found {
+            if !etag.is_empty() {
+                by_etag.entry((size, etag)).or_default().push(path);
+            }
+        }
+        for paths in by_etag.into_values().filter(|paths| paths.len() > 1) {
+            tx.send(ScanMessage::DuplicateGroup(paths)).ok();
+        }
+        tx.send(ScanMessage::DuplicatesDone).ok();
+    }
+
+    // The headers of a page of objects are read in parallel while the next
+    // page waits
+    fn scan_root(
+        &self,
+        client: &S3Client,
+        root: &Path,
+        found: &mut Vec<(PathBuf, u64, String)>,
+        tx: &SyncSender<ScanMessage>,
+    ) -> io::Result<()> {
+        let location = S3Location::parse(root)?;
+        let prefix = location.list_prefix();
+        let (listed, read, wanted) = (
+            AtomicUsize::new(0),
+            AtomicUsize::new(0),
+            AtomicUsize::new(0),
+        );
+        let progress = || ScanProgress {
+            done: read.load(Ordering::Relaxed),
+            total: wanted.load(Ordering::Relaxed),
+            files: listed.load(Ordering::Relaxed),
+        };
+        let mut token = None;
+        loop {
+            self.control.wait_while_paused();
+            if self.control.is_cancelled() {
+                return Ok(());
+            }
+            let (objects, next) = client.list(&location.bucket, &prefix, token.as_deref())?;
+            listed.fetch_add(objects.len(), Ordering::Relaxed);
+            let objects: Vec<(PathBuf, S3Object)> = objects
+                .into_iter()
+                .map(|object| (location.object_path(&object.key), object))
+                .filter(|(path, object)| self.wants(root, path, object))
+                .collect();
+            wanted.fetch_add(objects.len(), Ordering::Relaxed);
+            tx.send(ScanMessage::Progress(progress())).ok();
+
+            let files: Vec<(PathBuf, u64, String)> = objects
+                .into_par_iter()
+                .filter_map(|(path, object)| {
+                    self.control.wait_while_paused();
+                    if self.control.is_cancelled() {
+                        return None;
+                    }
+                    tx.send(ScanMessage::Directory(path.display().to_string()))
+                        .ok();
+                    let file = inspect_object(client, &location.bucket, &object, &path, root, tx);
+                    read.fetch_add(1, Ordering::Relaxed);
+                    tx.send(ScanMessage::Progress(progress())).ok();
+                    match file {
+                        Ok(Some(file)) => {
+                            tx.send(ScanMessage::File(Box::new(file))).ok();
+                            Some((path, object.size, object.etag))
+                        }
+                        Ok(None) => None,
+                        Err(e) => {
+                            tx.send(ScanMessage::Error(format!(
+                                "Error reading {}: {}",
+                                path.display(),
+                                e
+                            )))
+                            .ok();
+                            None
+                        }
+                    }
+                })
+                .collect();
+            found.extend(files);
+
+            match next {
+                Some(next) => token = Some(next),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    // Keys ending in `/` are the folders some tools create, empty objects
+    // can't be range-read
+    fn wants(&self, root: &Path, path: &Path, object: &S3Object) -> bool {
+        !object.key.ends_with('/')
+            && object.size > 0
+            && self.options.within_depth(path)
+            && !self.options.excludes_path(root, path)
+            && self.options.wants_used(object.size, object.modified)
+            && self.options.may_read(path)
+    }
+}
+
+// Archives and model directories aren't looked into, that would take more
+// requests per object than the header
+fn inspect_object(
+    client: &S3Client,
+    bucket: &str,
+    object: &S3Object,
+    path: &Path,
+    root: &Path,
+    tx: &SyncSender<ScanMessage>,
+) -> io::Result<Option<FileInfo>> {
+    let response = client.request("GET", bucket, &object.key, &[], Some(HEADER_RANGE))?;
+    let mut body = response.into_body().into_reader();
+    let mut prefix = Vec::new();
+    (&mut body).take(PREFIX_LEN).read_to_end(&mut prefix)?;
+    let Some(format) = detect_prefix(&prefix, object.size) else {
+        return Ok(None);
+    };
+    match format {
+        ModelFormat::Zip | ModelFormat::Tar | ModelFormat::TarGz => return Ok(None),
+        ModelFormat::Zstd if !is_archived_model(path) => return Ok(None),
+        _ => {}
+    }
+
+    let mut corrupt = None;
+    let header = if format == ModelFormat::Gguf {
+        let reader = io::Cursor::new(prefix.split_off(GGUF_MAGIC.len())).chain(body);
+        let header = GgufHeader::parse(BufReader::new(reader));
+        parsed_header(header, path, &mut corrupt, tx)
+    } else {
+        None
+    };
+    corrupt = corrupt.or_else(|| header.as_ref()?.check_size(object.size));
+    Ok(Some(FileInfo {
+        path: path.to_owned(),
+        size: object.size,
+        root: root.to_owned(),
+        modified: object.modified,
+        accessed: None,
+        format,
+        shard: Shard::parse(path, header.as_ref()),
+        header,
+        duplicate_group: None,
+        file_id: None,
+        links: 1,
+        symlink: false,
+        readonly: false,
+        source: None,
+        corrupt,
+        packed: Vec::new(),
+    }))
+}
+
+// `key = value` lines below `[section]` in the AWS CLI's files
+fn ini_section(path: &Path, section: &str) -> HashMap<String, String> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    let mut values = HashMap::new();
+    let mut inside = false;
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            inside = name.trim() == section;
+        } else if let Some((key, value)) = line.split_once('=').filter(|_| inside) {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    values
+}
+
+// Everything but unreserved characters is percent-encoded, slashes are kept
+// in paths
+fn uri_encode(text: &str, encode_slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            byte => write!(encoded, "%{:02X}", byte).unwrap(),
+        }
+    }
+    encoded
+}
+
+// The contents of each `<tag>…</tag>`, the listings are flat enough not to
+// need an XML parser
+fn xml_blocks<'a>(xml: &'a str, tag: &str) -> impl Iterator<Item = &'a str> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut rest = xml;
+    std::iter::from_fn(move || {
+        let start = rest.find(&open)? + open.len();
+        let len = rest[start..].find(&close)?;
+        let block = &rest[start..start + len];
+        rest = &rest[start + len + close.len()..];
+        Some(block)
+    })
+}
+
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    xml_blocks(xml, tag).next()
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+// What AWS Signature Version 4 signs of a request, its headers in lowercase
+// and sorted by name
+struct CanonicalRequest<'a> {
+    method: &'a str,
+    path: &'a str,
+    query: &'a str,
+    headers: &'a [(&'a str, String)],
+    payload_hash: &'a str,
+}
+
+impl CanonicalRequest<'_> {
+    fn signed_headers(&self) -> String {
+        self.headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
+    // In hex, `timestamp` is what `x-amz-date` holds
+    fn signature(&self, secret_key: &str, region: &str, timestamp: &str) -> String {
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            self.method,
+            self.path,
+            self.query,
+            self.headers
+                .iter()
+                .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+                .collect::<String>(),
+            self.signed_headers(),
+            self.payload_hash
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope(timestamp, region),
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = signing_key(secret_key, &timestamp[..8], region, "s3");
+        hex(&hmac(&key, string_to_sign.as_bytes()))
+    }
+}
+
+fn scope(timestamp: &str, region: &str) -> String {
+    format!("{}/{}/s3/aws4_request", &timestamp[..8], region)
+}
+
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let mut key = hmac(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    for part in [region, service, "aws4_request"] {
+        key = hmac(&key, part.as_bytes());
+    }
+    key
+}
+
+// Names and values encoded and sorted
+fn canonical_query(query: &[(&str, &str)]) -> String {
+    let mut query: Vec<(String, String)> = query
+        .iter()
+        .map(|(name, value)| (uri_encode(name, true), uri_encode(value, true)))
+        .collect();
+    query.sort();
+    query
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    inner.update(data);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{:02x}", byte).unwrap();
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The examples of the AWS documentation on signing S3 requests
+    const SECRET_KEY: &str = "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY";
+    const EMPTY_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    const TIMESTAMP: &str = "20130524T000000Z";
+
+    fn example_headers(extra: &[(&'static str, &str)]) -> Vec<(&'static str, String)> {
+        let mut headers = vec![("host", "examplebucket.s3.amazonaws.com".to_string())];
+        headers.extend(extra.iter().map(|&(name, value)| (name, value.to_string())));
+        headers.push(("x-amz-content-sha256", EMPTY_HASH.to_string()));
+        headers.push(("x-amz-date", TIMESTAMP.to_string()));
+        headers
+    }
+
+    #[test]
+    fn signs_the_get_object_example() {
+        let headers = example_headers(&[("range", "bytes=0-9")]);
+        let request = CanonicalRequest {
+            method: "GET",
+            path: &uri_encode("/test.txt", false),
+            query: "",
+            headers: &headers,
+            payload_hash: EMPTY_HASH,
+        };
+        assert_eq!(
+            request.signed_headers(),
+            "host;range;x-amz-content-sha256;x-amz-date"
+        );
+        assert_eq!(
+            request.signature(SECRET_KEY, "us-east-1", TIMESTAMP),
+            "f0e8bdb87c964420e857bd35b5d6ed310bd44f0170aba48dd91039c6036bdb41"
+        );
+    }
+
+    #[test]
+    fn signs_the_list_objects_example() {
+        let headers = example_headers(&[]);
+        let query = canonical_query(&[("prefix", "J"), ("max-keys", "2")]);
+        assert_eq!(query, "max-keys=2&prefix=J");
+        let request = CanonicalRequest {
+            method: "GET",
+            path: "/",
+            query: &query,
+            headers: &headers,
+            payload_hash: EMPTY_HASH,
+        };
+        assert_eq!(
+            request.signature(SECRET_KEY, "us-east-1", TIMESTAMP),
+            "34b48302e7b5fa45bde8084f4b7868a86f0a534bc59db6670ed5711ef69dc6f7"
+        );
+        assert_eq!(
+            scope(TIMESTAMP, "us-east-1"),
+            "20130524/us-east-1/s3/aws4_request"
+        );
+    }
+
+    #[test]
+    fn derives_the_signing_key_example() {
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    // RFC 4231 test cases 1, 2, 3 and 6
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        let cases: [(&[u8], &[u8], &str); 4] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                &[0xaa; 20],
+                &[0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+        ];
+        for (key, data, expected) in cases {
+            assert_eq!(hex(&hmac(key, data)), expected);
+        }
+    }
+
+    #[test]
+    fn encodes_uris() {
+        assert_eq!(uri_encode("a-Z_0.~", true), "a-Z_0.~");
+        assert_eq!(uri_encode("/my models/é+", false), "/my%20models/%C3%A9%2B");
+        assert_eq!(uri_encode("a/b", true), "a%2Fb");
+        assert_eq!(
+            canonical_query(&[("list-type", "2"), ("continuation-token", "1/a=")]),
+            "continuation-token=1%2Fa%3D&list-type=2"
+        );
+    }
+
+    #[test]
+    fn parses_listings() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>models</Name>
+  <Prefix>llama/</Prefix>
+  <KeyCount>2</KeyCount>
+  <MaxKeys>1000</MaxKeys>
+  <EncodingType>url</EncodingType>
+  <IsTruncated>true</IsTruncated>
+  <Contents>
+    <Key>llama/Llama+3%2B1+8B%25.gguf</Key>
+    <LastModified>2024-07-23T10:00:00.000Z</LastModified>
+    <ETag>&quot;d41d8cd98f00b204e9800998ecf8427e-12&quot;</ETag>
+    <Size>4920739232</Size>
+    <StorageClass>STANDARD</StorageClass>
+  </Contents>
+  <Contents>
+    <Key>llama/%C3%A9t%C3%A9/model.safetensors</Key>
+    <LastModified>not a date</LastModified>
+    <Size>16</Size>
+  </Contents>
+  <Contents>
+    <Key>llama/no-size.gguf</Key>
+  </Contents>
+  <NextContinuationToken>1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=&amp;</NextContinuationToken>
+</ListBucketResult>"#;
+        let (objects, next) = parse_listing(body);
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].key, "llama/Llama 3+1 8B%.gguf");
+        assert_eq!(objects[0].size, 4_920_739_232);
+        assert_eq!(
+            objects[0].modified,
+            Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_721_728_800))
+        );
+        assert_eq!(objects[0].etag, "\"d41d8cd98f00b204e9800998ecf8427e-12\"");
+        assert_eq!(objects[1].key, "llama/été/model.safetensors");
+        assert_eq!(objects[1].modified, None);
+        assert_eq!(objects[1].etag, "");
+        assert_eq!(
+            next.as_deref(),
+            Some("1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=&")
+        );
+
+        let last = body.replace(
+            "<IsTruncated>true</IsTruncated>",
+            "<IsTruncated>false</IsTruncated>",
+        );
+        assert_eq!(parse_listing(&last).1, None);
+        assert!(parse_listing("<ListBucketResult/>").0.is_empty());
+    }
+
+    #[test]
+    fn reads_error_documents() {
+        let body = "<Error><Code>NoSuchKey</Code><Message>The key &lt;a&gt; does not \
+                    exist</Message></Error>";
+        assert_eq!(xml_value(body, "Code"), Some("NoSuchKey"));
+        assert_eq!(
+            xml_value(body, "Message").map(xml_unescape).as_deref(),
+            Some("The key <a> does not exist")
+        );
+        assert_eq!(xml_value(body, "Resource"), None);
+        assert_eq!(xml_unescape("&amp;lt;"), "&lt;");
+    }
+
+    #[test]
+    fn parses_locations() {
+        let location = |path: &str| S3Location::parse(Path::new(path)).unwrap();
+
+        let bucket = location("s3://bucket");
+        assert_eq!(
+            (bucket.bucket.as_str(), bucket.key.as_str()),
+            ("bucket", "")
+        );
+        assert_eq!(bucket.list_prefix(), "");
+        assert_eq!(
+            bucket.object_path("a.gguf"),
+            PathBuf::from("s3://bucket/a.gguf")
+        );
+
+        let prefix = location("s3://bucket/models/");
+        assert_eq!(prefix.key, "models/");
+        assert_eq!(prefix.list_prefix(), "models/");
+        assert_eq!(location("s3://bucket/models").list_prefix(), "models/");
+        assert_eq!(location("s3://bucket/a/b.gguf").key, "a/b.gguf");
+
+        for path in [
+            "s3://",
+            "s3:///models",
+            "/models",
+            "S3://bucket",
+            "bucket/models",
+        ] {
+            let error = S3Location::parse(Path::new(path)).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{}", path);
+        }
+        assert!(is_s3_path(Path::new("s3://bucket")));
+        assert!(!is_s3_path(Path::new("/s3://bucket")));
+    }
+
+    #[test]
+    fn reads_ini_sections() {
+        let path = std::env::temp_dir().join(format!("ggufscan-test-{}-aws", std::process::id()));
+        fs::write(
+            &path,
+            "[default]\nregion = eu-west-1\n\n[ profile work ]\nregion=us-east-2\n\
+             endpoint_url = http://minio:9000 \n[other]\nregion = ap-south-1\n",
+        )
+        .unwrap();
+        let default = ini_section(&path, "default");
+        let work = ini_section(&path, "profile work");
+        let missing = ini_section(&path, "missing");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(default.get("region").map(String::as_str), Some("eu-west-1"));
+        assert_eq!(default.len(), 1);
+        assert_eq!(work.get("region").map(String::as_str), Some("us-east-2"));
+        assert_eq!(
+            work.get("endpoint_url").map(String::as_str),
+            Some("http://minio:9000")
+        );
+        assert!(missing.is_empty());
+        assert!(ini_section(Path::new("/nonexistent/credentials"), "default").is_empty());
+    }
+}
@@ -0,0 +1,1254 @@
+//! Walking the scan roots and reporting the model files found.
+
+use globset::GlobSet;
+use ignore::WalkBuilder;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    archive::is_archived_model,
+    bundle::{bundle_contents, detect_bundle},
+    cache::{CachedFile, ScanCache},
+    detect_format,
+    format::may_be_model,
+    links::file_id,
+    mounts::{network_and_removable_mounts, virtual_mounts},
+    packed::{is_pack_name, packed_models},
+    source::{known_model_dirs, SourceResolver},
+    watch::RootWatcher,
+    FileId, GgufHeader, MetadataValue, ModelFormat, ModelSource, GGUF_MAGIC,
+};
+
+// Messages held by a channel before the sending side waits for the
+// receiving one, so a slow consumer doesn't pile up a huge filesystem's worth
+pub(crate) const CHANNEL_CAPACITY: usize = 1024;
+// Each walker thread reports where it is at most this often, the rest of the
+// directories are only counted
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What to scan and how.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub roots: Vec<PathBuf>,
+    pub excludes: GlobSet,
+    pub find_duplicates: bool,
+    /// Scan cache to start from and update, see [`default_cache_path`]
+    ///
+    /// [`default_cache_path`]: crate::default_cache_path
+    pub cache: Option<PathBuf>,
+    /// Keep reporting changes below the roots after the scan is done
+    pub watch: bool,
+    /// Don't cross into other filesystems mounted below the roots
+    pub one_file_system: bool,
+    /// Descend into symlinked directories, each directory is walked once
+    /// however many links lead to it
+    pub follow_symlinks: bool,
+    /// Skip network filesystems and removable drives mounted below the
+    /// roots, a root on one is still scanned
+    pub skip_network_mounts: bool,
+    /// Skip the pseudo-filesystems like `/proc`, `/sys` and `/dev` and the
+    /// snap and flatpak trees, a root in one is still scanned
+    pub default_excludes: bool,
+    /// How many directory levels below the roots to descend, files directly
+    /// in a root are at depth 1
+    pub max_depth: Option<usize>,
+    /// Smaller files are skipped without being read
+    pub min_size: u64,
+    /// Files modified or read more recently than this are skipped
+    pub older_than: Option<Duration>,
+    /// Look at the leading bytes of every file, not only of those named
+    /// like a model file or without an extension
+    pub deep: bool,
+    /// Look at these files instead of walking the roots, e.g. a list from
+    /// another tool. Each is reported below the root it is in, and the cache
+    /// is neither read nor updated.
+    pub files: Vec<PathBuf>,
+    /// Directories below the roots walked before the rest of them, along
+    /// with the model stores of [`known_model_dirs`], so the models there
+    /// show up first
+    ///
+    /// [`known_model_dirs`]: crate::known_model_dirs
+    pub priority_dirs: Vec<PathBuf>,
+    /// Look for GGUF files inside zip and tar archives, which are reported
+    /// with the header of the biggest one
+    pub scan_archives: bool,
+}
+
+impl ScanOptions {
+    // Globs are matched against the full path as well as the bare file name,
+    // so both `/proc` and `*.Backup` work as expected
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.excludes.is_match(path)
+            || path
+                .file_name()
+                .is_some_and(|name| self.excludes.is_match(name))
+    }
+
+    // Whether a path below one of the roots is within the depth limit
+    pub(crate) fn within_depth(&self, path: &Path) -> bool {
+        let Some(max_depth) = self.max_depth else {
+            return true;
+        };
+        self.roots
+            .iter()
+            .filter_map(|root| path.strip_prefix(root).ok())
+            .any(|relative| relative.components().count() <= max_depth)
+    }
+
+    // Whether a file passes the size and age limits, checked before it is
+    // opened
+    pub(crate) fn wants(&self, metadata: &fs::Metadata) -> bool {
+        let last_used = [metadata.modified().ok(), metadata.accessed().ok()]
+            .into_iter()
+            .flatten()
+            .max();
+        self.wants_used(metadata.len(), last_used)
+    }
+
+    pub(crate) fn wants_used(&self, size: u64, last_used: Option<SystemTime>) -> bool {
+        let old_enough = || {
+            self.older_than.is_none_or(|age| {
+                last_used
+                    .and_then(|time| time.elapsed().ok())
+                    .is_some_and(|elapsed| elapsed >= age)
+            })
+        };
+        size >= self.min_size && old_enough()
+    }
+
+    // Whether the file is worth opening, going by its name alone
+    pub(crate) fn may_read(&self, path: &Path) -> bool {
+        self.deep || may_be_model(path) || (self.scan_archives && is_pack_name(path))
+    }
+
+    // Some files are left out without a look, so a directory being walked
+    // doesn't mean all of its models were seen
+    pub(crate) fn skips_unread(&self) -> bool {
+        self.min_size > 0 || self.older_than.is_some()
+    }
+
+    // Whether the path or any directory between it and the root is excluded
+    pub(crate) fn excludes_path(&self, root: &Path, path: &Path) -> bool {
+        path.ancestors()
+            .take_while(|path| path.starts_with(root))
+            .any(|path| self.is_excluded(path))
+    }
+}
+
+/// A model file found by the scan.
+#[derive(Debug)]
+pub struct FileInfo {
+    pub path: PathBuf,
+    pub size: u64,
+    pub root: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub format: ModelFormat,
+    pub header: Option<GgufHeader>,
+    // Files with identical content share the same group id
+    pub duplicate_group: Option<usize>,
+    pub file_id: Option<FileId>,
+    pub links: u64,
+    pub symlink: bool,
+    // Write-protected, `rm` would ask before removing it
+    pub readonly: bool,
+    // Where the file came from when it lives in a known model store
+    pub source: Option<ModelSource>,
+    pub shard: Option<Shard>,
+    // Why the file can't be loaded, a header that doesn't parse or tensor
+    // data cut off
+    pub corrupt: Option<String>,
+    // GGUF files inside an archive, biggest first
+    pub packed: Vec<String>,
+}
+
+impl FileInfo {
+    /// When the file was last modified or read, whichever is later.
+    pub fn last_used(&self) -> Option<SystemTime> {
+        self.modified.max(self.accessed)
+    }
+}
+
+// Part of a split model, `<name>-00001-of-00005.gguf`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shard {
+    // Path with the part number left out, shared by all parts of the model
+    pub group: PathBuf,
+    pub number: u32,
+    pub count: u32,
+}
+
+impl Shard {
+    pub fn parse(path: &Path, header: Option<&GgufHeader>) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        let (stem, extension) = name.rsplit_once('.')?;
+        let (rest, count) = stem.rsplit_once("-of-")?;
+        let (base, number) = rest.rsplit_once('-')?;
+        if number.len() != 5 || count.len() != 5 {
+            return None;
+        }
+        let (number, mut count): (u32, u32) = (number.parse().ok()?, count.parse().ok()?);
+
+        // The split metadata written by gguf-split wins over the file name
+        if let Some(split_count) = header
+            .and_then(|header| header.get("split.count"))
+            .and_then(MetadataValue::as_u64)
+        {
+            count = u32::try_from(split_count).ok()?;
+        }
+        if number == 0 || number > count {
+            return None;
+        }
+
+        let group = path.with_file_name(format!("{}-*-of-{:05}.{}", base, count, extension));
+        Some(Self {
+            group,
+            number,
+            count,
+        })
+    }
+}
+
+/// Progress and results of a running scan, `Done` is sent once every root
+/// was walked and the duplicate check follows unless it was turned off.
+/// A cancelled scan ends with `Cancelled` at any point.
+/// Every file is reported once, whether it came from the cache or the walk.
+#[derive(Debug)]
+pub enum ScanMessage {
+    File(Box<FileInfo>),
+    // Number of files reported from the cache before the walk starts
+    CacheLoaded(usize),
+    Directory(String),
+    Progress(ScanProgress),
+    // How far the walk of one root got, sent along with `Progress` when
+    // there are several roots
+    RootProgress(PathBuf, ScanProgress),
+    // Every directory below the root was walked
+    RootDone(PathBuf),
+    Done,
+    CheckingDuplicates,
+    DuplicateGroup(Vec<PathBuf>),
+    DuplicatesDone,
+    // Sent once changes are being watched for, files found after that are
+    // reported again whenever they change
+    Watching,
+    // The path and everything below it is gone
+    Removed(PathBuf),
+    // The scan was stopped through its ScanControl, nothing follows
+    Cancelled,
+    Error(String),
+}
+
+/// How far the walk got, sent along with the directories.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ScanProgress {
+    /// Estimated share of the work done, `done` out of `total`
+    pub done: usize,
+    pub total: usize,
+    /// Files looked at so far, model or not
+    pub files: usize,
+}
+
+/// Pauses, resumes or cancels the scan of the [`Scanner`] it came from, the
+/// walk and the duplicate check stop reading from the disk while paused.
+#[derive(Debug, Clone, Default)]
+pub struct ScanControl {
+    paused: Arc<(Mutex<bool>, Condvar)>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ScanControl {
+    pub fn pause(&self) {
+        *self.paused.0.lock().unwrap() = true;
+    }
+
+    pub fn resume(&self) {
+        *self.paused.0.lock().unwrap() = false;
+        self.paused.1.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.0.lock().unwrap()
+    }
+
+    /// Stops the scan, it ends with [`ScanMessage::Cancelled`] instead of
+    /// running to completion.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.resume();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn wait_while_paused(&self) {
+        let (paused, resumed) = &*self.paused;
+        let _guard = resumed
+            .wait_while(paused.lock().unwrap(), |paused| *paused)
+            .unwrap();
+    }
+}
+
+// Reads through to the inner reader, holding off while the scan is paused
+struct PausableReader<'a, R> {
+    inner: R,
+    control: &'a ScanControl,
+}
+
+impl<R: Read> Read for PausableReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.control.wait_while_paused();
+        if self.control.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "scan cancelled"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// Scans the roots of its options for model files.
+pub struct Scanner {
+    options: ScanOptions,
+    control: ScanControl,
+}
+
+impl Scanner {
+    pub fn new(options: ScanOptions) -> Self {
+        Self {
+            options,
+            control: ScanControl::default(),
+        }
+    }
+
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// A handle to pause the scan with, also after it was spawned.
+    pub fn control(&self) -> ScanControl {
+        self.control.clone()
+    }
+
+    /// Runs the scan on a background thread, returning the message stream.
+    /// The scan waits for messages to be received once the stream is full.
+    pub fn spawn(self) -> Receiver<ScanMessage> {
+        let (tx, rx) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        thread::spawn(move || self.run(tx));
+        rx
+    }
+
+    /// Runs the scan on the current thread, sending its messages to `tx`.
+    pub fn run(&self, tx: SyncSender<ScanMessage>) {
+        scan_directory(&self.options, &self.control, tx);
+    }
+}
+
+// State shared by the walker threads of one scan
+struct ScanContext<'a> {
+    options: &'a ScanOptions,
+    control: &'a ScanControl,
+    sources: SourceResolver,
+    cache: ScanCache,
+    // Cached files already reported before the walk started
+    preloaded: HashSet<PathBuf>,
+    // Directories walked during this scan, for the next one
+    dirs: Mutex<Vec<(PathBuf, SystemTime)>>,
+    // Device and inode of the directories walked when following symlinks,
+    // a link back up the tree or a second link to the same place is skipped
+    walked_dirs: Mutex<HashSet<FileId>>,
+    skipped_mounts: Vec<PathBuf>,
+    // Model stores walked before the roots they are in, by the index of
+    // the root, and left out of its walk
+    priority: Vec<(usize, PathBuf)>,
+    // Directories counted towards the estimate and files looked at
+    dirs_done: AtomicUsize,
+    files_seen: AtomicUsize,
+    total_work: usize,
+}
+
+// How far the walk of one root got
+struct RootWork {
+    total: usize,
+    // Whether every directory counts towards the estimate or only the
+    // top-level ones
+    count_all: bool,
+    done: AtomicUsize,
+    files: AtomicUsize,
+}
+
+impl RootWork {
+    fn progress(&self) -> ScanProgress {
+        ScanProgress {
+            done: self.done.load(Ordering::Relaxed).min(self.total),
+            total: self.total,
+            files: self.files.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl ScanContext<'_> {
+    // The directories the last scan walked below the root are the best guess
+    // of how much there is to do, without a cache only its top-level
+    // directories are counted. Returns the estimate and whether every
+    // directory counts towards it.
+    fn estimate_work(&self, root: &Path) -> (usize, bool) {
+        let cached = self.cache.dirs_below(root, self.options);
+        if cached > 0 {
+            return (cached, true);
+        }
+        let top_level = fs::read_dir(root)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+                    .filter(|entry| {
+                        let path = entry.path();
+                        !self.options.is_excluded(&path) && !self.skipped_mounts.contains(&path)
+                    })
+                    .count()
+            })
+            .unwrap_or(0);
+        (top_level, false)
+    }
+
+    fn progress(&self) -> ScanProgress {
+        ScanProgress {
+            done: self.dirs_done.load(Ordering::Relaxed).min(self.total_work),
+            total: self.total_work,
+            files: self.files_seen.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn scan_directory(options: &ScanOptions, control: &ScanControl, tx: SyncSender<ScanMessage>) {
+    let (worker_tx, worker_rx) = mpsc::sync_channel(CHANNEL_CAPACITY);
+    let tx_clone = tx.clone();
+
+    let forwarder = thread::spawn(move || {
+        let mut found = Vec::new();
+        let mut cached = Vec::new();
+        for message in worker_rx {
+            // Bundles are sized up again by every walk, no single mtime
+            // tells they are unchanged, and aren't hashed
+            match &message {
+                ScanMessage::File(file_info) if !file_info.format.is_bundle() => {
+                    // Links to the same file are never reported as duplicates
+                    if !file_info.symlink {
+                        found.push((file_info.path.clone(), file_info.size, file_info.file_id));
+                    }
+                    cached.push(CachedFile::from(&**file_info));
+                }
+                _ => {}
+            }
+            tx_clone.send(message).ok();
+        }
+        (found, cached)
+    });
+
+    let listed = !options.files.is_empty();
+    let mut context = ScanContext {
+        options,
+        control,
+        sources: SourceResolver::new(),
+        cache: options
+            .cache
+            .as_deref()
+            .filter(|_| !listed)
+            .map(ScanCache::load)
+            .unwrap_or_default(),
+        preloaded: HashSet::new(),
+        dirs: Mutex::default(),
+        walked_dirs: Mutex::default(),
+        skipped_mounts: options
+            .skip_network_mounts
+            .then(network_and_removable_mounts)
+            .into_iter()
+            .chain(options.default_excludes.then(virtual_mounts))
+            .flatten()
+            .filter(|mount| !options.roots.iter().any(|root| root.starts_with(mount)))
+            .collect(),
+        priority: Vec::new(),
+        dirs_done: AtomicUsize::new(0),
+        files_seen: AtomicUsize::new(0),
+        total_work: 0,
+    };
+    if !listed {
+        context.priority = priority_walks(options, &context.skipped_mounts);
+    }
+
+    // Cached files that are still there unchanged are reported right away,
+    // the walk below only picks up what changed since the last scan
+    let preloaded = if listed {
+        Vec::new()
+    } else {
+        preload_cached(&context)
+    };
+    if options.cache.is_some() && !listed {
+        tx.send(ScanMessage::CacheLoaded(preloaded.len())).ok();
+    }
+    for file_info in preloaded {
+        context.preloaded.insert(file_info.path.clone());
+        worker_tx.send(ScanMessage::File(Box::new(file_info))).ok();
+    }
+
+    let roots: Vec<RootWork> = options
+        .roots
+        .iter()
+        .filter(|_| !listed)
+        .map(|root| {
+            let (total, count_all) = context.estimate_work(root);
+            RootWork {
+                total,
+                count_all,
+                done: AtomicUsize::new(0),
+                files: AtomicUsize::new(0),
+            }
+        })
+        .collect();
+    context.total_work = if listed {
+        options.files.len()
+    } else {
+        roots.iter().map(|work| work.total).sum()
+    };
+    tx.send(ScanMessage::Progress(context.progress())).ok();
+
+    if listed {
+        scan_files(&context, worker_tx.clone());
+    } else {
+        // The model stores first, for their models to show up within the
+        // first moments, then one walker per root, all feeding the same
+        // channel
+        thread::scope(|scope| {
+            for (index, dir) in &context.priority {
+                let (root, work) = (&options.roots[*index], &roots[*index]);
+                let worker_tx = worker_tx.clone();
+                let context = &context;
+                scope.spawn(move || scan_root(root, dir, work, context, worker_tx));
+            }
+        });
+        thread::scope(|scope| {
+            for (root, work) in options.roots.iter().zip(&roots) {
+                let worker_tx = worker_tx.clone();
+                let context = &context;
+                scope.spawn(move || scan_root(root, root, work, context, worker_tx));
+            }
+        });
+    }
+
+    // The last directories of the walk may not have been reported
+    worker_tx
+        .send(ScanMessage::Progress(context.progress()))
+        .ok();
+    drop(worker_tx);
+    let (found, cached) = forwarder.join().unwrap_or_default();
+
+    // Directories of a walk cut short may have files left unseen, the cache
+    // of the last complete scan stays as it is
+    if control.is_cancelled() {
+        tx.send(ScanMessage::Cancelled).ok();
+        return;
+    }
+
+    if let Some(path) = options.cache.as_ref().filter(|_| !listed) {
+        let dirs = context.dirs.into_inner().unwrap_or_default();
+        if let Err(e) = context.cache.save(path, options, cached, dirs) {
+            tx.send(ScanMessage::Error(format!(
+                "Error writing scan cache {}: {}",
+                path.display(),
+                e
+            )))
+            .ok();
+        }
+    }
+    tx.send(ScanMessage::Done).ok();
+
+    // Started before hashing so nothing changing meanwhile is missed
+    let watcher = (options.watch && !listed)
+        .then(|| match RootWatcher::start(options) {
+            Ok(watcher) => {
+                tx.send(ScanMessage::Watching).ok();
+                Some(watcher)
+            }
+            Err(e) => {
+                tx.send(ScanMessage::Error(format!("Error watching files: {}", e)))
+                    .ok();
+                None
+            }
+        })
+        .flatten();
+
+    if options.find_duplicates {
+        tx.send(ScanMessage::CheckingDuplicates).ok();
+        let groups = find_duplicates(found, control);
+        if control.is_cancelled() {
+            tx.send(ScanMessage::Cancelled).ok();
+            return;
+        }
+        for group in groups {
+            tx.send(ScanMessage::DuplicateGroup(group)).ok();
+        }
+        tx.send(ScanMessage::DuplicatesDone).ok();
+    }
+
+    if let Some(watcher) = watcher {
+        watcher.run(options, &tx);
+    }
+}
+
+// Cached files below the roots whose size and mtime still match
+fn preload_cached(context: &ScanContext) -> Vec<FileInfo> {
+    let options = context.options;
+    context
+        .cache
+        .files()
+        .filter_map(|cached| {
+            let root = options
+                .roots
+                .iter()
+                .find(|root| cached.path.starts_with(root))?;
+            if options.excludes_path(root, &cached.path)
+                || !options.within_depth(&cached.path)
+                || context
+                    .skipped_mounts
+                    .iter()
+                    .any(|mount| cached.path.starts_with(mount))
+                || (!options.follow_symlinks && behind_symlink(root, &cached.path))
+                || (cached.format.is_packed() && !options.scan_archives)
+            {
+                return None;
+            }
+            let metadata = fs::metadata(&cached.path).ok()?;
+            if metadata.len() != cached.size
+                || metadata.modified().ok() != cached.modified
+                || !options.wants(&metadata)
+            {
+                return None;
+            }
+            let mut file_info = file_info(
+                &cached.path,
+                root,
+                &metadata,
+                cached.format,
+                cached.header.clone(),
+                cached.corrupt.clone(),
+                &context.sources,
+            );
+            file_info.packed = cached.packed.clone();
+            Some(file_info)
+        })
+        .collect()
+}
+
+// Whether a directory between the root and the file is a symlink, files
+// found through one by a scan following them aren't seen by one that doesn't
+fn behind_symlink(root: &Path, path: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| *dir != root && dir.starts_with(root))
+        .any(|dir| dir.is_symlink())
+}
+
+// Only files sharing their size with another file are hashed
+fn find_duplicates(
+    files: Vec<(PathBuf, u64, Option<FileId>)>,
+    control: &ScanControl,
+) -> Vec<Vec<PathBuf>> {
+    let mut seen = HashSet::new();
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, size, file_id) in files {
+        if size > 0 && file_id.is_none_or(|id| seen.insert(id)) {
+            by_size.entry(size).or_default().push(path);
+        }
+    }
+
+    let candidates: Vec<(u64, PathBuf)> = by_size
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(size, paths)| paths.into_iter().map(move |path| (size, path)))
+        .collect();
+
+    let hashed: Vec<((u64, blake3::Hash), PathBuf)> = candidates
+        .into_par_iter()
+        .filter_map(|(size, path)| {
+            hash_file(&path, control)
+                .ok()
+                .map(|hash| ((size, hash), path))
+        })
+        .collect();
+
+    let mut by_hash: HashMap<(u64, blake3::Hash), Vec<PathBuf>> = HashMap::new();
+    for (key, path) in hashed {
+        by_hash.entry(key).or_default().push(path);
+    }
+    by_hash
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .collect()
+}
+
+fn hash_file(path: &Path, control: &ScanControl) -> io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(PausableReader {
+        inner: open_untouched(path)?,
+        control,
+    })?;
+    Ok(hasher.finalize())
+}
+
+// Model stores below the roots that aren't excluded or on a skipped mount,
+// a store inside another one is walked along with it. With a depth limit
+// the roots are walked as they are.
+fn priority_walks(options: &ScanOptions, skipped_mounts: &[PathBuf]) -> Vec<(usize, PathBuf)> {
+    if options.max_depth.is_some() {
+        return Vec::new();
+    }
+    let device = |path: &Path| {
+        fs::metadata(path)
+            .ok()
+            .and_then(|metadata| file_id(&metadata).0)
+            .map(|(device, _)| device)
+    };
+    let mut walks: Vec<(usize, PathBuf)> = Vec::new();
+    for dir in known_model_dirs()
+        .into_iter()
+        .chain(options.priority_dirs.iter().cloned())
+    {
+        let Some(index) = options
+            .roots
+            .iter()
+            .position(|root| dir.starts_with(root) && *root != dir)
+        else {
+            continue;
+        };
+        let root = &options.roots[index];
+        if options.excludes_path(root, &dir)
+            || skipped_mounts.iter().any(|mount| dir.starts_with(mount))
+            || (options.one_file_system && device(&dir) != device(root))
+            || walks.iter().any(|(_, walked)| dir.starts_with(walked))
+        {
+            continue;
+        }
+        walks.retain(|(_, walked)| !walked.starts_with(&dir));
+        walks.push((index, dir));
+    }
+    walks
+}
+
+// Walks `start`, the root itself or a model store below it
+fn scan_root(
+    root: &Path,
+    start: &Path,
+    work: &RootWork,
+    context: &ScanContext,
+    worker_tx: SyncSender<ScanMessage>,
+) {
+    let filter_options = context.options.clone();
+    let skipped_mounts = context.skipped_mounts.clone();
+    // The stores were walked already
+    let walked_first: Vec<PathBuf> = if start == root {
+        context
+            .priority
+            .iter()
+            .map(|(_, dir)| dir.clone())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let start_depth = start
+        .strip_prefix(root)
+        .map_or(0, |relative| relative.components().count());
+    let multiple_roots = context.options.roots.len() > 1;
+    let walker = WalkBuilder::new(start)
+        .hidden(false)
+        .ignore(false)
+        .git_ignore(false)
+        .same_file_system(context.options.one_file_system)
+        .follow_links(context.options.follow_symlinks)
+        .max_depth(context.options.max_depth)
+        .threads(num_cpus::get())
+        .filter_entry(move |entry| {
+            !filter_options.is_excluded(entry.path())
+                && !skipped_mounts.iter().any(|mount| mount == entry.path())
+                && !walked_first.iter().any(|dir| dir == entry.path())
+        })
+        .build_parallel();
+
+    walker.run(|| {
+        let worker_tx = worker_tx.clone();
+        // Entries of a directory mostly arrive one after another on the same
+        // thread, so its cache state is only looked up once
+        let mut parent_state: Option<(PathBuf, bool)> = None;
+        let mut last_progress: Option<Instant> = None;
+        Box::new(move |entry| {
+            context.control.wait_while_paused();
+            if context.control.is_cancelled() {
+                return ignore::WalkState::Quit;
+            }
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => return ignore::WalkState::Continue,
+            };
+
+            let path = entry.path();
+            let depth = start_depth + entry.depth();
+            // The type comes with the directory listing, only links need a
+            // stat to tell where they point. Links to directories are only
+            // walked when following them, and then the type is the target's.
+            let (is_dir, is_file) = match entry.file_type() {
+                Some(kind) if kind.is_symlink() => (false, path.is_file()),
+                Some(kind) => (kind.is_dir(), kind.is_file()),
+                None => (path.is_dir(), path.is_file()),
+            };
+
+            // Send directory updates
+            if is_dir {
+                if context.options.follow_symlinks {
+                    let id = entry
+                        .metadata()
+                        .ok()
+                        .and_then(|metadata| file_id(&metadata).0);
+                    if id.is_some_and(|id| !context.walked_dirs.lock().unwrap().insert(id)) {
+                        return ignore::WalkState::Skip;
+                    }
+                }
+                // Without a cached estimate a top-level directory counts as
+                // done once its walk starts
+                if work.count_all || depth == 1 {
+                    context.dirs_done.fetch_add(1, Ordering::Relaxed);
+                    work.done.fetch_add(1, Ordering::Relaxed);
+                }
+                // Dropped rather than waited for when the channel is full,
+                // the next one tells the same
+                if last_progress.is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL) {
+                    last_progress = Some(Instant::now());
+                    if let Some(path_str) = path.to_str() {
+                        worker_tx
+                            .try_send(ScanMessage::Directory(path_str.to_string()))
+                            .ok();
+                    }
+                    worker_tx
+                        .try_send(ScanMessage::Progress(context.progress()))
+                        .ok();
+                    if multiple_roots {
+                        worker_tx
+                            .try_send(ScanMessage::RootProgress(root.to_owned(), work.progress()))
+                            .ok();
+                    }
+                }
+                // A bundle is one entry, what is inside isn't walked
+                if let Some(format) = detect_bundle(path) {
+                    report_bundle(path, root, format, context, &worker_tx);
+                    return ignore::WalkState::Skip;
+                }
+                // Directories at the depth limit weren't looked into
+                let complete = context
+                    .options
+                    .max_depth
+                    .is_none_or(|max_depth| depth < max_depth)
+                    && !context.options.skips_unread();
+                if let Some(modified) = dir_modified(path).filter(|_| complete) {
+                    context
+                        .dirs
+                        .lock()
+                        .unwrap()
+                        .push((path.to_owned(), modified));
+                }
+            }
+
+            // Check if it's a file in one of the known model formats
+            if is_file {
+                context.files_seen.fetch_add(1, Ordering::Relaxed);
+                work.files.fetch_add(1, Ordering::Relaxed);
+            }
+            if is_file && !context.preloaded.contains(path) {
+                if !context.options.may_read(path) {
+                    return ignore::WalkState::Continue;
+                }
+                // Files of unchanged directories the cache doesn't know
+                // aren't models, they were inspected by an earlier scan. A
+                // deep scan looks at the ones earlier scans went by the name
+                // of again, and archives are looked into again when asked
+                // to, earlier scans may not have.
+                let parent = path.parent().unwrap_or(path);
+                if parent_state.as_ref().is_none_or(|(dir, _)| dir != parent) {
+                    let unchanged = context.cache.is_unchanged_dir(parent, dir_modified(parent));
+                    parent_state = Some((parent.to_owned(), unchanged));
+                }
+                let unchanged = parent_state
+                    .as_ref()
+                    .is_some_and(|(_, unchanged)| *unchanged);
+                let archive = context.options.scan_archives && is_pack_name(path);
+                if unchanged && !context.options.deep && !archive && !context.cache.contains(path) {
+                    return ignore::WalkState::Continue;
+                }
+
+                match inspect_file(path, root, context.options, &context.sources, &worker_tx) {
+                    Ok(Some(file_info)) => {
+                        worker_tx.send(ScanMessage::File(Box::new(file_info))).ok();
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        worker_tx
+                            .send(ScanMessage::Error(format!(
+                                "Error reading file {}: {}",
+                                path.display(),
+                                e
+                            )))
+                            .ok();
+                    }
+                }
+            }
+
+            ignore::WalkState::Continue
+        })
+    });
+
+    if start == root && !context.control.is_cancelled() {
+        worker_tx.send(ScanMessage::RootDone(root.to_owned())).ok();
+    }
+}
+
+fn report_bundle(
+    path: &Path,
+    root: &Path,
+    format: ModelFormat,
+    context: &ScanContext,
+    worker_tx: &SyncSender<ScanMessage>,
+) {
+    match inspect_bundle(path, root, format, context.options, &context.sources) {
+        Ok(Some(file_info)) => {
+            worker_tx.send(ScanMessage::File(Box::new(file_info))).ok();
+        }
+        Ok(None) => {}
+        Err(e) => {
+            worker_tx
+                .send(ScanMessage::Error(format!(
+                    "Error reading directory {}: {}",
+                    path.display(),
+                    e
+                )))
+                .ok();
+        }
+    }
+}
+
+// Looks at the listed files instead of walking the roots, each counts as a
+// directory of the estimate. Directories in the list are passed over unless
+// they are a bundle.
+fn scan_files(context: &ScanContext, worker_tx: SyncSender<ScanMessage>) {
+    let options = context.options;
+    options.files.par_iter().for_each_init(
+        || (worker_tx.clone(), None::<Instant>),
+        |(worker_tx, last_progress), path| {
+            context.control.wait_while_paused();
+            if context.control.is_cancelled() {
+                return;
+            }
+            context.dirs_done.fetch_add(1, Ordering::Relaxed);
+            if last_progress.is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL) {
+                *last_progress = Some(Instant::now());
+                worker_tx
+                    .try_send(ScanMessage::Progress(context.progress()))
+                    .ok();
+            }
+            if options.is_excluded(path) {
+                return;
+            }
+            let root = options
+                .roots
+                .iter()
+                .find(|root| path.starts_with(root))
+                .map_or(path.parent().unwrap_or(path), PathBuf::as_path);
+            if path.is_dir() {
+                if let Some(format) = detect_bundle(path) {
+                    report_bundle(path, root, format, context, worker_tx);
+                }
+                return;
+            }
+            context.files_seen.fetch_add(1, Ordering::Relaxed);
+
+            match inspect_file(path, root, options, &context.sources, worker_tx) {
+                Ok(Some(file_info)) => {
+                    worker_tx.send(ScanMessage::File(Box::new(file_info))).ok();
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    worker_tx
+                        .send(ScanMessage::Error(format!(
+                            "Error reading file {}: {}",
+                            path.display(),
+                            e
+                        )))
+                        .ok();
+                }
+            }
+        },
+    );
+}
+
+// Opens the file for reading without updating its access time where the
+// platform allows it, so scanning doesn't make old models look used
+#[cfg(target_os = "linux")]
+pub(crate) fn open_untouched(path: &Path) -> io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    match fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOATIME)
+        .open(path)
+    {
+        // Only allowed for the owner of the file
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => fs::File::open(path),
+        result => result,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn open_untouched(path: &Path) -> io::Result<fs::File> {
+    fs::File::open(path)
+}
+
+fn dir_modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+pub(crate) fn inspect_file(
+    path: &Path,
+    root: &Path,
+    options: &ScanOptions,
+    sources: &SourceResolver,
+    worker_tx: &SyncSender<ScanMessage>,
+) -> io::Result<Option<FileInfo>> {
+    let metadata = fs::metadata(path)?;
+    if !options.wants(&metadata) {
+        return Ok(None);
+    }
+    let mut file = open_untouched(path)?;
+    let Some(format) = detect_format(&mut file, metadata.len())? else {
+        return Ok(None);
+    };
+    if format == ModelFormat::Zstd && !is_archived_model(path) {
+        return Ok(None);
+    }
+
+    let mut corrupt = None;
+    let mut packed = Vec::new();
+    let header = match format {
+        ModelFormat::Gguf => {
+            file.seek(SeekFrom::Start(GGUF_MAGIC.len() as u64))?;
+            let header = GgufHeader::parse(io::BufReader::new(file));
+            parsed_header(header, path, &mut corrupt, worker_tx)
+        }
+        ModelFormat::Zip | ModelFormat::Tar | ModelFormat::TarGz => {
+            if !options.scan_archives {
+                return Ok(None);
+            }
+            // Anything that doesn't list like an archive isn't one
+            let mut models = match packed_models(&mut file, format) {
+                Ok(models) if !models.is_empty() => models,
+                Ok(_) => return Ok(None),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => return Ok(None),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            packed = models.iter().map(|model| model.name.clone()).collect();
+            let biggest = models.remove(0);
+            let header = parsed_header(
+                biggest.header,
+                &path.join(&biggest.name),
+                &mut corrupt,
+                worker_tx,
+            );
+            // Compared with the size of the file in the archive, not that of
+            // the archive
+            corrupt = corrupt.or_else(|| header.as_ref()?.check_size(biggest.size));
+            header
+        }
+        ModelFormat::Safetensors
+        | ModelFormat::PyTorch
+        | ModelFormat::Onnx
+        | ModelFormat::Ggml
+        | ModelFormat::Zstd
+        | ModelFormat::CoreMl
+        | ModelFormat::Mlx
+        | ModelFormat::Gptq
+        | ModelFormat::Awq
+        | ModelFormat::Exl2 => None,
+    };
+
+    let mut file_info = file_info(path, root, &metadata, format, header, corrupt, sources);
+    file_info.packed = packed;
+    Ok(Some(file_info))
+}
+
+pub(crate) fn parsed_header(
+    header: io::Result<GgufHeader>,
+    path: &Path,
+    corrupt: &mut Option<String>,
+    worker_tx: &SyncSender<ScanMessage>,
+) -> Option<GgufHeader> {
+    match header {
+        Ok(header) => Some(header),
+        Err(e) => {
+            // Newer versions may well be fine, we just can't tell
+            *corrupt = match e.kind() {
+                io::ErrorKind::UnexpectedEof => Some("header cut off".to_string()),
+                io::ErrorKind::Unsupported => None,
+                _ => Some(format!("broken header, {}", e)),
+            };
+            worker_tx
+                .send(ScanMessage::Error(format!(
+                    "Error parsing GGUF header of {}: {}",
+                    path.display(),
+                    e
+                )))
+                .ok();
+            None
+        }
+    }
+}
+
+// A model directory as one file, found by [`detect_bundle`]
+pub(crate) fn inspect_bundle(
+    path: &Path,
+    root: &Path,
+    format: ModelFormat,
+    options: &ScanOptions,
+    sources: &SourceResolver,
+) -> io::Result<Option<FileInfo>> {
+    let contents = bundle_contents(path)?;
+    if !options.wants_used(contents.size, contents.modified.max(contents.accessed)) {
+        return Ok(None);
+    }
+    let metadata = fs::metadata(path)?;
+    Ok(Some(FileInfo {
+        path: path.to_owned(),
+        size: contents.size,
+        root: root.to_owned(),
+        modified: contents.modified,
+        accessed: contents.accessed,
+        format,
+        header: None,
+        duplicate_group: None,
+        // Only told apart, the link count of a directory is that of its
+        // subdirectories
+        file_id: file_id(&metadata).0,
+        links: 1,
+        symlink: path.is_symlink(),
+        readonly: metadata.permissions().readonly(),
+        source: sources.resolve(path),
+        shard: None,
+        corrupt: None,
+        packed: Vec::new(),
+    }))
+}
+
+fn file_info(
+    path: &Path,
+    root: &Path,
+    metadata: &fs::Metadata,
+    format: ModelFormat,
+    header: Option<GgufHeader>,
+    corrupt: Option<String>,
+    sources: &SourceResolver,
+) -> FileInfo {
+    let (file_id, links) = file_id(metadata);
+    let shard = Shard::parse(path, header.as_ref());
+    let corrupt = corrupt.or_else(|| {
+        header
+            .as_ref()
+            .filter(|_| !format.is_packed())?
+            .check_size(metadata.len())
+    });
+
+    FileInfo {
+        path: path.to_owned(),
+        size: metadata.len(),
+        root: root.to_owned(),
+        modified: metadata.modified().ok(),
+        accessed: metadata.accessed().ok(),
+        format,
+        header,
+        duplicate_group: None,
+        file_id,
+        links,
+        symlink: path.is_symlink(),
+        readonly: metadata.permissions().readonly(),
+        source: sources.resolve(path),
+        shard,
+        corrupt,
+        packed: Vec::new(),
+    }
+}
+
+impl From<&FileInfo> for CachedFile {
+    fn from(file: &FileInfo) -> Self {
+        Self {
+            path: file.path.clone(),
+            size: file.size,
+            modified: file.modified,
+            format: file.format,
+            header: file.header.clone(),
+            corrupt: file.corrupt.clone(),
+            packed: file.packed.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(split_count: u64) -> GgufHeader {
+        GgufHeader {
+            version: 3,
+            tensor_count: 0,
+            metadata: vec![("split.count".to_string(), MetadataValue::U64(split_count))],
+            parameter_count: 0,
+            tensor_type: None,
+            data_end: None,
+        }
+    }
+
+    #[test]
+    fn parses_shard_names() {
+        let shard = Shard::parse(Path::new("/m/llama-00002-of-00003.gguf"), None).unwrap();
+        assert_eq!((shard.number, shard.count), (2, 3));
+        assert_eq!(shard.group, Path::new("/m/llama-*-of-00003.gguf"));
+        for name in [
+            "llama.gguf",
+            "llama-2-of-3.gguf",
+            "llama-00000-of-00003.gguf",
+            "llama-00004-of-00003.gguf",
+            "llama-0000a-of-00003.gguf",
+        ] {
+            assert!(Shard::parse(Path::new(name), None).is_none(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn prefers_the_split_count_of_the_header() {
+        let path = Path::new("/m/llama-00002-of-00003.gguf");
+        let shard = Shard::parse(path, Some(&header(4))).unwrap();
+        assert_eq!(shard.count, 4);
+        assert_eq!(shard.group, Path::new("/m/llama-*-of-00004.gguf"));
+        assert!(Shard::parse(path, Some(&header(1))).is_none());
+        // Counts past u32 would wrap around into another group
+        assert!(Shard::parse(path, Some(&header(u64::from(u32::MAX) + 4))).is_none());
+    }
+}
@@ -0,0 +1,405 @@
+//! Mapping files in known model stores (Ollama, Hugging Face, LM Studio)
+//! back to the models they belong to.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ModelSource {
+    // Models whose manifests reference the blob
+    Ollama {
+        models: Vec<String>,
+    },
+    // LM Studio keeps downloads in `<models>/<publisher>/<model>/`
+    LmStudio {
+        publisher: String,
+        model: String,
+    },
+    // Hugging Face hub cache, revisions are the snapshots using the file
+    HuggingFace {
+        repo: String,
+        repo_dir: PathBuf,
+        revisions: Vec<HfRevision>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HfRevision {
+    pub id: String,
+    // Refs such as `main` pointing at the revision, none for stale revisions
+    pub refs: Vec<String>,
+}
+
+impl HfRevision {
+    pub fn is_stale(&self) -> bool {
+        self.refs.is_empty()
+    }
+
+    pub fn label(&self) -> String {
+        let short: String = self.id.chars().take(7).collect();
+        if self.is_stale() {
+            format!("{} (stale)", short)
+        } else {
+            format!("{} ({})", short, self.refs.join(", "))
+        }
+    }
+}
+
+impl ModelSource {
+    pub fn label(&self) -> String {
+        match self {
+            ModelSource::Ollama { models } if models.is_empty() => {
+                "ollama: unreferenced".to_string()
+            }
+            ModelSource::Ollama { models } => format!("ollama: {}", models.join(", ")),
+            ModelSource::LmStudio { publisher, model } => {
+                format!("lmstudio: {}/{}", publisher, model)
+            }
+            ModelSource::HuggingFace {
+                repo, revisions, ..
+            } => {
+                let revisions: Vec<String> = revisions.iter().map(HfRevision::label).collect();
+                format!("hf: {}@{}", repo, revisions.join(", "))
+            }
+        }
+    }
+}
+
+const OLLAMA_REGISTRY: &str = "registry.ollama.ai";
+const OLLAMA_LIBRARY: &str = "library";
+
+// Maps blob digests to the model names referencing them, for a single
+// Ollama models directory
+type OllamaIndex = HashMap<String, Vec<String>>;
+
+// Resolves files in known model stores back to model names, the parsed
+// manifests are cached per store while the walker threads share it
+pub(crate) struct SourceResolver {
+    ollama: Mutex<HashMap<PathBuf, Arc<OllamaIndex>>>,
+    huggingface: Mutex<HashMap<PathBuf, Arc<HfRepo>>>,
+    lmstudio: Vec<PathBuf>,
+}
+
+impl SourceResolver {
+    pub(crate) fn new() -> Self {
+        Self {
+            ollama: Mutex::default(),
+            huggingface: Mutex::default(),
+            lmstudio: lmstudio_model_dirs(),
+        }
+    }
+
+    pub(crate) fn resolve(&self, path: &Path) -> Option<ModelSource> {
+        self.resolve_ollama(path)
+            .or_else(|| self.resolve_huggingface(path))
+            .or_else(|| self.resolve_lmstudio(path))
+    }
+
+    fn resolve_lmstudio(&self, path: &Path) -> Option<ModelSource> {
+        let relative = self
+            .lmstudio
+            .iter()
+            .find_map(|dir| path.strip_prefix(dir).ok())?;
+        let mut parts = relative.iter().filter_map(|part| part.to_str());
+        let (publisher, model) = (parts.next()?, parts.next()?);
+        // The file itself has to be below the model folder
+        parts.next()?;
+        Some(ModelSource::LmStudio {
+            publisher: publisher.to_string(),
+            model: model.to_string(),
+        })
+    }
+
+    // Hub cache repos are `models--<org>--<name>` holding the file contents
+    // in `blobs/` and one directory of symlinks per revision in `snapshots/`
+    fn resolve_huggingface(&self, path: &Path) -> Option<ModelSource> {
+        let parts: Vec<&std::ffi::OsStr> = path.iter().collect();
+        let (index, repo) = parts.windows(2).enumerate().find_map(|(i, pair)| {
+            let name = pair[0].to_str()?;
+            let repo = HF_REPO_PREFIXES
+                .iter()
+                .find_map(|prefix| name.strip_prefix(prefix))?;
+            (pair[1] == "blobs" || pair[1] == "snapshots").then(|| (i, repo.replacen("--", "/", 1)))
+        })?;
+        let repo_dir: PathBuf = parts[..=index].iter().collect();
+        let entry = parts.get(index + 2)?.to_str()?;
+
+        let hf_repo = {
+            let mut cache = self.huggingface.lock().unwrap();
+            cache
+                .entry(repo_dir.clone())
+                .or_insert_with(|| Arc::new(read_hf_repo(&repo_dir)))
+                .clone()
+        };
+
+        let revision_ids = if parts[index + 1] == "snapshots" {
+            vec![entry.to_string()]
+        } else {
+            hf_repo
+                .blob_revisions
+                .get(entry)
+                .cloned()
+                .unwrap_or_default()
+        };
+        let revisions = revision_ids
+            .into_iter()
+            .map(|id| HfRevision {
+                refs: hf_repo.refs.get(&id).cloned().unwrap_or_default(),
+                id,
+            })
+            .collect();
+
+        Some(ModelSource::HuggingFace {
+            repo,
+            repo_dir,
+            revisions,
+        })
+    }
+
+    // Blobs live in `<models>/blobs/sha256-<hex>`, next to `<models>/manifests`
+    fn resolve_ollama(&self, path: &Path) -> Option<ModelSource> {
+        let name = path.file_name()?.to_str()?;
+        let digest = name.strip_prefix("sha256-")?;
+        let blobs = path.parent()?;
+        if blobs.file_name()? != "blobs" {
+            return None;
+        }
+        let models_dir = blobs.parent()?;
+
+        let index = {
+            let mut cache = self.ollama.lock().unwrap();
+            cache
+                .entry(models_dir.to_owned())
+                .or_insert_with(|| Arc::new(read_ollama_manifests(&models_dir.join("manifests"))))
+                .clone()
+        };
+
+        let models = index
+            .get(&format!("sha256:{}", digest))
+            .cloned()
+            .unwrap_or_default();
+        Some(ModelSource::Ollama { models })
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+// LM Studio's default models folders, the current one and the one used by
+// older releases, plus a custom downloads folder set in its settings
+pub fn lmstudio_model_dirs() -> Vec<PathBuf> {
+    let Some(home) = home_dir() else {
+        return Vec::new();
+    };
+
+    let mut dirs = vec![
+        home.join(".lmstudio").join("models"),
+        home.join(".cache").join("lm-studio").join("models"),
+    ];
+    for settings in [
+        home.join(".lmstudio").join("settings.json"),
+        home.join(".cache").join("lm-studio").join("settings.json"),
+    ] {
+        let Ok(settings) = fs::read(settings) else {
+            continue;
+        };
+        let Ok(settings) = serde_json::from_slice::<serde_json::Value>(&settings) else {
+            continue;
+        };
+        if let Some(folder) = settings["downloadsFolder"].as_str() {
+            dirs.push(PathBuf::from(folder));
+        }
+    }
+
+    dirs.retain(|dir| dir.is_dir());
+    dirs.dedup();
+    dirs
+}
+
+/// Where Ollama, the Hugging Face hub cache and LM Studio keep their models,
+/// by default or as their environment variables say, those that exist.
+pub fn known_model_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = std::env::var_os("OLLAMA_MODELS") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(dir) = std::env::var_os("HF_HUB_CACHE") {
+        dirs.push(PathBuf::from(dir));
+    } else if let Some(dir) = std::env::var_os("HF_HOME") {
+        dirs.push(PathBuf::from(dir).join("hub"));
+    }
+    if let Some(home) = home_dir() {
+        dirs.push(home.join(".ollama").join("models"));
+        dirs.push(home.join(".cache").join("huggingface").join("hub"));
+    }
+    // The Linux install runs Ollama as a user of its own
+    #[cfg(target_os = "linux")]
+    dirs.push(PathBuf::from("/usr/share/ollama/.ollama/models"));
+    dirs.extend(lmstudio_model_dirs());
+
+    let mut seen = HashSet::new();
+    dirs.retain(|dir| dir.is_dir() && seen.insert(dir.clone()));
+    dirs
+}
+
+const HF_REPO_PREFIXES: &[&str] = &["models--", "datasets--", "spaces--"];
+
+#[derive(Debug, Default)]
+struct HfRepo {
+    // Revision to the refs pointing at it
+    refs: HashMap<String, Vec<String>>,
+    // Blob name to the revisions whose snapshots link to it
+    blob_revisions: HashMap<String, Vec<String>>,
+}
+
+fn read_hf_repo(repo_dir: &Path) -> HfRepo {
+    let mut repo = HfRepo::default();
+
+    let refs_dir = repo_dir.join("refs");
+    for path in files_under(&refs_dir) {
+        let (Ok(revision), Ok(name)) = (fs::read_to_string(&path), path.strip_prefix(&refs_dir))
+        else {
+            continue;
+        };
+        repo.refs
+            .entry(revision.trim().to_string())
+            .or_default()
+            .push(name.display().to_string());
+    }
+
+    let Ok(snapshots) = fs::read_dir(repo_dir.join("snapshots")) else {
+        return repo;
+    };
+    for snapshot in snapshots.flatten() {
+        let revision = snapshot.file_name().to_string_lossy().into_owned();
+        for link in files_under(&snapshot.path()) {
+            let Ok(target) = fs::read_link(&link) else {
+                continue;
+            };
+            if let Some(blob) = target.file_name() {
+                repo.blob_revisions
+                    .entry(blob.to_string_lossy().into_owned())
+                    .or_default()
+                    .push(revision.clone());
+            }
+        }
+    }
+    repo
+}
+
+// Everything below `dir` that isn't a directory, without following symlinks
+fn files_under(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_owned()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => pending.push(entry.path()),
+                Ok(_) => files.push(entry.path()),
+                Err(_) => {}
+            }
+        }
+    }
+    files
+}
+
+// Files to delete for removing a whole snapshot from the hub cache
+pub struct RevisionRemoval {
+    pub repo: String,
+    pub revision: String,
+    pub snapshot_dir: PathBuf,
+    // Blobs no other revision links to
+    pub blobs: Vec<PathBuf>,
+    pub size: u64,
+}
+
+impl RevisionRemoval {
+    pub fn plan(repo: &str, repo_dir: &Path, revision: &str) -> Self {
+        let hf_repo = read_hf_repo(repo_dir);
+        let blobs: Vec<PathBuf> = hf_repo
+            .blob_revisions
+            .iter()
+            .filter(|(_, revisions)| revisions.iter().all(|r| r == revision))
+            .map(|(blob, _)| repo_dir.join("blobs").join(blob))
+            .collect();
+        let size = blobs
+            .iter()
+            .filter_map(|blob| fs::metadata(blob).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        Self {
+            repo: repo.to_string(),
+            revision: revision.to_string(),
+            snapshot_dir: repo_dir.join("snapshots").join(revision),
+            blobs,
+            size,
+        }
+    }
+}
+
+fn read_ollama_manifests(manifests: &Path) -> OllamaIndex {
+    let mut index = OllamaIndex::new();
+    let mut pending = vec![manifests.to_owned()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let Some(model) = ollama_model_name(manifests, &path) else {
+                continue;
+            };
+            let Ok(manifest) = fs::read(&path) else {
+                continue;
+            };
+            let Ok(manifest) = serde_json::from_slice::<serde_json::Value>(&manifest) else {
+                continue;
+            };
+            let layers = manifest["layers"].as_array().into_iter().flatten();
+            for digest in layers.filter_map(|layer| layer["digest"].as_str()) {
+                index
+                    .entry(digest.to_string())
+                    .or_default()
+                    .push(model.clone());
+            }
+        }
+    }
+
+    for models in index.values_mut() {
+        models.sort();
+        models.dedup();
+    }
+    index
+}
+
+// Manifests are stored as `<registry>/<namespace>/<model>/<tag>`, models
+// from the default library are shown the way `ollama list` does
+fn ollama_model_name(manifests: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(manifests).ok()?;
+    let parts: Vec<&str> = relative.iter().filter_map(|part| part.to_str()).collect();
+    let [registry, namespace, model, tag] = parts.as_slice() else {
+        return None;
+    };
+    Some(match (*registry, *namespace) {
+        (OLLAMA_REGISTRY, OLLAMA_LIBRARY) => format!("{}:{}", model, tag),
+        (OLLAMA_REGISTRY, namespace) => format!("{}/{}:{}", namespace, model, tag),
+        (registry, namespace) => format!("{}/{}/{}:{}", registry, namespace, model, tag),
+    })
+}
@@ -0,0 +1,157 @@
+//! Human readable sizes, counts and dates.
+
+use std::time::{Duration, SystemTime};
+
+pub fn format_param_count(count: u64) -> String {
+    const BILLION: f64 = 1e9;
+    const MILLION: f64 = 1e6;
+
+    let count = count as f64;
+    if count >= BILLION {
+        format!("{:.2}B", count / BILLION)
+    } else if count >= MILLION {
+        format!("{:.1}M", count / MILLION)
+    } else {
+        format!("{}", count)
+    }
+}
+
+// The size a model is known by, `7B` for 6.74 billion parameters; small
+// models keep a decimal so 1.5B and 0.5B stay apart
+pub fn format_size_class(count: u64) -> String {
+    const BILLION: f64 = 1e9;
+    const MILLION: f64 = 1e6;
+
+    let count = count as f64;
+    if count >= 3.0 * BILLION {
+        format!("{:.0}B", count / BILLION)
+    } else if count >= 0.95 * BILLION {
+        format!("{:.1}B", count / BILLION)
+    } else if count >= MILLION {
+        format!("{:.0}M", count / MILLION)
+    } else {
+        format!("{:.0}K", count / 1e3)
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    const GB: u64 = 1024 * 1024 * 1024;
+    const MB: u64 = 1024 * 1024;
+
+    if size >= GB {
+        format!("{:.2} GB", size as f64 / GB as f64)
+    } else if size >= MB {
+        format!("{:.2} MB", size as f64 / MB as f64)
+    } else {
+        format!("{} B", size)
+    }
+}
+
+/// Local date and time, `2024-05-17 14:03:51`.
+pub fn format_date(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Local>::from(time)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Local date only, `2024-05-17`.
+pub fn format_day(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Local>::from(time)
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// Parses sizes like `100M`, `1.5G` or `4096`, the units are powers of 1024
+/// to match [`format_size`].
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_ascii_uppercase();
+    let number_len = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(number_len);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1024,
+        "M" => 1024 * 1024,
+        "G" => 1024 * 1024 * 1024,
+        "T" => 1024 * 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Parses ages like `12h`, `90d`, `8w`, `6m` or `1y`, a month is 30 days and
+/// a year 365.
+pub fn parse_age(age: &str) -> Option<Duration> {
+    let age = age.trim().to_ascii_lowercase();
+    // The unit is the last character, which needn't be ASCII in a typo
+    let (unit_start, _) = age.char_indices().next_back()?;
+    let (number, unit) = age.split_at(unit_start);
+    let number: f64 = number.parse().ok()?;
+    let hours = match unit {
+        "h" => 1.0,
+        "d" => 24.0,
+        "w" => 7.0 * 24.0,
+        "m" => 30.0 * 24.0,
+        "y" => 365.0 * 24.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(number * hours * 3600.0).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 3600;
+    const DAY: u64 = 24 * HOUR;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("100M"), Some(100 * 1024 * 1024));
+        assert_eq!(parse_size("1.5G"), Some(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size(" 2kib "), Some(2048));
+        assert_eq!(parse_size("10GB"), Some(10 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1T"), Some(1024 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("10X"), None);
+        assert_eq!(parse_size("-5M"), None);
+        assert_eq!(parse_size("1..5G"), None);
+        assert_eq!(parse_size("5é"), None);
+    }
+
+    #[test]
+    fn parses_ages() {
+        assert_eq!(parse_age("12h"), Some(Duration::from_secs(12 * HOUR)));
+        assert_eq!(parse_age("90d"), Some(Duration::from_secs(90 * DAY)));
+        assert_eq!(parse_age("8W"), Some(Duration::from_secs(56 * DAY)));
+        assert_eq!(parse_age(" 6m "), Some(Duration::from_secs(180 * DAY)));
+        assert_eq!(parse_age("1y"), Some(Duration::from_secs(365 * DAY)));
+        assert_eq!(parse_age("1.5d"), Some(Duration::from_secs(36 * HOUR)));
+    }
+
+    #[test]
+    fn rejects_invalid_ages() {
+        assert_eq!(parse_age(""), None);
+        assert_eq!(parse_age("d"), None);
+        assert_eq!(parse_age("90"), None);
+        assert_eq!(parse_age("90x"), None);
+        assert_eq!(parse_age("-1d"), None);
+        assert_eq!(parse_age("infd"), None);
+    }
+
+    #[test]
+    fn rejects_ages_ending_in_non_ascii() {
+        assert_eq!(parse_age("9é"), None);
+        assert_eq!(parse_age("é"), None);
+        assert_eq!(parse_age("90d€"), None);
+        assert_eq!(parse_age("9日"), None);
+    }
+}
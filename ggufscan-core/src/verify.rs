@@ -0,0 +1,184 @@
+//! Checking model files against the SHA256 hashes Hugging Face publishes for
+//! the files of a repo.
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Write as _,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{scan::open_untouched, FileInfo, MetadataValue, ModelSource};
+
+const HF_API: &str = "https://huggingface.co/api/models";
+// Metadata keys converters fill with the page of the model
+const REPO_URL_KEYS: [&str; 4] = [
+    "general.repo_url",
+    "general.url",
+    "general.source.repo_url",
+    "general.source.url",
+];
+
+/// What Hugging Face knows about a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Same content as `path` in the repo
+    Verified { repo: String, path: String },
+    /// The repo has a file of that name, with different content
+    Modified { repo: String, path: String },
+    /// No repo to check against, or none of its files has the hash
+    Unknown,
+}
+
+impl Verification {
+    pub fn label(&self) -> String {
+        match self {
+            Verification::Verified { repo, path } => format!("verified, {}/{}", repo, path),
+            Verification::Modified { repo, path } => {
+                format!("modified, differs from {}/{}", repo, path)
+            }
+            Verification::Unknown => "unknown to Hugging Face".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TreeEntry {
+    path: String,
+    lfs: Option<LfsInfo>,
+}
+
+#[derive(Deserialize)]
+struct LfsInfo {
+    oid: String,
+}
+
+/// Hex SHA256 of the file, `progress` gets the bytes hashed so far.
+pub fn sha256_file(path: &Path, mut progress: impl FnMut(u64)) -> io::Result<String> {
+    let mut file = open_untouched(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    let mut hashed = 0;
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..read]);
+        hashed += read as u64;
+        progress(hashed);
+    }
+    let mut hex = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        write!(hex, "{:02x}", byte).expect("writing to a string");
+    }
+    Ok(hex)
+}
+
+/// Repos and revisions the file may have come from: the Hugging Face cache
+/// or LM Studio folder it is in and the model page in its metadata.
+pub fn hf_candidates(file: &FileInfo) -> Vec<(String, String)> {
+    let mut candidates = Vec::new();
+    match &file.source {
+        Some(ModelSource::HuggingFace {
+            repo, revisions, ..
+        }) => {
+            for revision in revisions {
+                candidates.push((repo.clone(), revision.id.clone()));
+            }
+        }
+        Some(ModelSource::LmStudio { publisher, model }) => {
+            candidates.push((format!("{}/{}", publisher, model), "main".to_string()));
+        }
+        Some(ModelSource::Ollama { .. }) | None => {}
+    }
+    if let Some(header) = &file.header {
+        for key in REPO_URL_KEYS {
+            let Some(repo) = header
+                .get(key)
+                .and_then(MetadataValue::as_str)
+                .and_then(hf_repo_from_url)
+            else {
+                continue;
+            };
+            let candidate = (repo, "main".to_string());
+            if !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates
+}
+
+// `https://huggingface.co/org/model/tree/main` is `org/model`
+fn hf_repo_from_url(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("https://huggingface.co/")
+        .or_else(|| url.strip_prefix("http://huggingface.co/"))?;
+    let mut parts = rest.split('/').filter(|part| !part.is_empty());
+    let (org, model) = (parts.next()?, parts.next()?);
+    Some(format!("{}/{}", org, model))
+}
+
+/// Hashes the file and looks the hash up in the repos of [`hf_candidates`],
+/// `progress` gets the bytes hashed so far. Without candidates the file
+/// isn't hashed at all.
+pub fn verify_file(
+    path: &Path,
+    candidates: &[(String, String)],
+    progress: impl FnMut(u64),
+) -> io::Result<Verification> {
+    if candidates.is_empty() {
+        return Ok(Verification::Unknown);
+    }
+    let hash = sha256_file(path, progress)?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut modified = None;
+    for (repo, revision) in candidates {
+        let entries = repo_files(repo, revision)?;
+        if let Some(entry) = entries
+            .iter()
+            .find(|entry| entry.lfs.as_ref().is_some_and(|lfs| lfs.oid == hash))
+        {
+            return Ok(Verification::Verified {
+                repo: repo.clone(),
+                path: entry.path.clone(),
+            });
+        }
+        if modified.is_none() {
+            modified = entries
+                .iter()
+                .find(|entry| Path::new(&entry.path).file_name() == Some(name.as_ref()))
+                .map(|entry| Verification::Modified {
+                    repo: repo.clone(),
+                    path: entry.path.clone(),
+                });
+        }
+    }
+    Ok(modified.unwrap_or(Verification::Unknown))
+}
+
+// All files of the repo at the revision, `HF_TOKEN` gets at gated repos
+fn repo_files(repo: &str, revision: &str) -> io::Result<Vec<TreeEntry>> {
+    let url = format!("{}/{}/tree/{}?recursive=true", HF_API, repo, revision);
+    let mut request = ureq::get(&url);
+    if let Ok(token) = std::env::var("HF_TOKEN") {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+    match request.call() {
+        Ok(mut response) => response
+            .body_mut()
+            .read_json()
+            .map_err(|e| io::Error::other(format!("{}: {}", repo, e))),
+        // Deleted, renamed or private repos know nothing about the file
+        Err(ureq::Error::StatusCode(401 | 403 | 404)) => Ok(Vec::new()),
+        Err(e) => Err(io::Error::other(format!("{}: {}", repo, e))),
+    }
+}
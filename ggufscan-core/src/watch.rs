@@ -0,0 +1,193 @@
+//! Following changes below the scan roots once the scan is done.
+
+use ignore::WalkBuilder;
+use notify::{
+    event::{AccessKind, AccessMode, ModifyKind},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    bundle::bundle_containing,
+    scan::{inspect_bundle, inspect_file},
+    source::SourceResolver,
+    ModelFormat, ScanMessage, ScanOptions,
+};
+
+// Changed paths are inspected at most this often, files being downloaded
+// change many times a second
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(crate) struct RootWatcher {
+    // Stops watching when dropped
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+}
+
+impl RootWatcher {
+    pub(crate) fn start(options: &ScanOptions) -> notify::Result<Self> {
+        let (events_tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(events_tx)?;
+        for root in &options.roots {
+            watcher.watch(root, RecursiveMode::Recursive)?;
+        }
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    // Reports changed files until the receiving end of `tx` goes away
+    pub(crate) fn run(self, options: &ScanOptions, tx: &SyncSender<ScanMessage>) {
+        // Changed paths, and whether they were created or moved in, only
+        // new directories are walked
+        let mut pending: HashMap<PathBuf, bool> = HashMap::new();
+        let mut last_update = Instant::now();
+
+        loop {
+            match self.events.recv_timeout(WATCH_INTERVAL) {
+                // Reading files, including our own inspecting, changes nothing
+                Ok(Ok(event))
+                    if matches!(event.kind, EventKind::Access(kind)
+                        if kind != AccessKind::Close(AccessMode::Write)) => {}
+                Ok(Ok(event)) => {
+                    let added = matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+                    );
+                    for path in event.paths {
+                        *pending.entry(path).or_default() |= added;
+                    }
+                }
+                Ok(Err(e)) => {
+                    if tx
+                        .send(ScanMessage::Error(format!("Error watching files: {}", e)))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            if !pending.is_empty() && last_update.elapsed() >= WATCH_INTERVAL {
+                let paths: Vec<(PathBuf, bool)> = pending.drain().collect();
+                if report_changes(paths, options, tx).is_err() {
+                    return;
+                }
+                last_update = Instant::now();
+            }
+        }
+    }
+}
+
+// Sends the current state of every changed path, the error means nobody is
+// listening anymore
+fn report_changes(
+    paths: Vec<(PathBuf, bool)>,
+    options: &ScanOptions,
+    tx: &SyncSender<ScanMessage>,
+) -> Result<(), mpsc::SendError<ScanMessage>> {
+    // Created fresh so new Ollama manifests and the like are picked up
+    let sources = SourceResolver::new();
+
+    for (path, added) in paths {
+        let Some(root) = options.roots.iter().find(|root| path.starts_with(root)) else {
+            continue;
+        };
+        if options.excludes_path(root, &path) {
+            continue;
+        }
+
+        // Any change inside a bundle changes its size
+        if let Some((bundle, format)) = bundle_containing(&path, root) {
+            report_bundle(&bundle, root, format, options, &sources, tx)?;
+        } else if path.is_dir() {
+            // Directories moved in don't get events for the files inside
+            if added {
+                let mut bundles = HashSet::new();
+                for file in files_below(&path, options) {
+                    match bundle_containing(&file, root) {
+                        Some((bundle, format)) => {
+                            if bundles.insert(bundle.clone()) {
+                                report_bundle(&bundle, root, format, options, &sources, tx)?;
+                            }
+                        }
+                        None => report_file(&file, root, options, &sources, tx)?,
+                    }
+                }
+            }
+        } else if path.is_file() {
+            report_file(&path, root, options, &sources, tx)?;
+        } else {
+            tx.send(ScanMessage::Removed(path))?;
+        }
+    }
+    Ok(())
+}
+
+fn report_file(
+    path: &Path,
+    root: &Path,
+    options: &ScanOptions,
+    sources: &SourceResolver,
+    tx: &SyncSender<ScanMessage>,
+) -> Result<(), mpsc::SendError<ScanMessage>> {
+    // Renamed to something not named like a model, as far as the scan goes
+    if !options.may_read(path) {
+        return tx.send(ScanMessage::Removed(path.to_owned()));
+    }
+    match inspect_file(path, root, options, sources, tx) {
+        Ok(Some(file_info)) => tx.send(ScanMessage::File(Box::new(file_info))),
+        // Overwritten with something that isn't a model anymore, or too small
+        Ok(None) => tx.send(ScanMessage::Removed(path.to_owned())),
+        Err(e) => tx.send(ScanMessage::Error(format!(
+            "Error reading file {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+fn report_bundle(
+    path: &Path,
+    root: &Path,
+    format: ModelFormat,
+    options: &ScanOptions,
+    sources: &SourceResolver,
+    tx: &SyncSender<ScanMessage>,
+) -> Result<(), mpsc::SendError<ScanMessage>> {
+    // A Core ML bundle is told by its name alone, also once it is gone
+    if !path.is_dir() {
+        return tx.send(ScanMessage::Removed(path.to_owned()));
+    }
+    match inspect_bundle(path, root, format, options, sources) {
+        Ok(Some(file_info)) => tx.send(ScanMessage::File(Box::new(file_info))),
+        Ok(None) => tx.send(ScanMessage::Removed(path.to_owned())),
+        Err(e) => tx.send(ScanMessage::Error(format!(
+            "Error reading directory {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+fn files_below(dir: &Path, options: &ScanOptions) -> Vec<PathBuf> {
+    let filter_options = options.clone();
+    WalkBuilder::new(dir)
+        .hidden(false)
+        .ignore(false)
+        .git_ignore(false)
+        .filter_entry(move |entry| !filter_options.is_excluded(entry.path()))
+        .build()
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.into_path())
+        .collect()
+}
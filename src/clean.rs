@@ -0,0 +1,218 @@
+//! `ggufscan clean`: scans with the filters given and deletes what they
+//! match in one go, without the TUI.
+
+use anyhow::Result;
+use ggufscan_core::{
+    file_users, format_size, is_s3_path, unique_size, FileInfo, FileUser, ScanMessage,
+};
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
+
+use crate::{
+    check_roots, config::Config, configured_roots, filter::arch_matches, quarantine_dir,
+    remove_path, spawn_scan, CleanArgs, DeleteMode,
+};
+
+// Scans with the filters of `clean` and deletes what they match, split
+// models only when all of their parts do
+pub(crate) fn run_clean(config: &Config, clean: &CleanArgs) -> Result<()> {
+    anyhow::ensure!(
+        clean.scan.older_than.is_some() || clean.scan.min_size.is_some() || !clean.arch.is_empty(),
+        "Give at least one of --older-than, --min-size or --arch, clean would delete every model otherwise"
+    );
+    let roots = if clean.paths.is_empty() {
+        configured_roots(config)?
+    } else {
+        clean.paths.clone()
+    };
+    check_roots(&roots)?;
+    // Objects have no trash to go to
+    let delete_mode = if roots.iter().any(|root| is_s3_path(root)) {
+        DeleteMode::Permanent
+    } else {
+        clean.delete.delete_mode(config)
+    };
+    let quarantine = quarantine_dir(config);
+    anyhow::ensure!(
+        delete_mode != DeleteMode::Quarantine || quarantine.is_some(),
+        "No quarantine directory found, set quarantine_dir in the config"
+    );
+    let options = clean.scan.scan_options(config, roots)?;
+
+    let mut files = Vec::new();
+    for message in spawn_scan(options).1 {
+        match message {
+            ScanMessage::File(file) if arch_matches(&clean.arch, &file) => files.push(*file),
+            ScanMessage::Error(error) => eprintln!("{}", error),
+            ScanMessage::Done | ScanMessage::Cancelled => break,
+            _ => {}
+        }
+    }
+
+    skip_incomplete_models(&mut files);
+    // A model being served is left alone, it's still wanted
+    let paths: Vec<PathBuf> = files.iter().map(|file| file.path.clone()).collect();
+    skip_open_files(&mut files, &file_users(&paths));
+    files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    if files.is_empty() {
+        eprintln!("No model files match");
+        return Ok(());
+    }
+
+    for file in &files {
+        println!("{:<10} | {}", format_size(file.size), file.path.display());
+    }
+    let total = unique_size(files.iter());
+    if clean.dry_run {
+        eprintln!("Would delete {} files, {}", files.len(), format_size(total));
+        return Ok(());
+    }
+    if !clean.yes {
+        anyhow::ensure!(
+            io::stdin().is_terminal(),
+            "Pass --yes to delete without asking"
+        );
+        eprint!(
+            "Delete {} files, {}? [y/N] ",
+            files.len(),
+            format_size(total)
+        );
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            return Ok(());
+        }
+    }
+
+    let quarantine = quarantine.unwrap_or_default();
+    let mut removed = Vec::new();
+    for file in &files {
+        match remove_path(
+            delete_mode,
+            &quarantine,
+            &file.path,
+            file.format.is_bundle(),
+        ) {
+            Ok(()) => removed.push(file),
+            Err(e) => eprintln!("Error with {}: {}", file.path.display(), e),
+        }
+    }
+    let verb = match delete_mode {
+        DeleteMode::Trash => "Trashed",
+        DeleteMode::Permanent => "Deleted",
+        DeleteMode::Quarantine => "Quarantined",
+    };
+    eprintln!(
+        "{} {} of {} files, freed {}",
+        verb,
+        removed.len(),
+        files.len(),
+        format_size(unique_size(removed.into_iter()))
+    );
+    Ok(())
+}
+
+// Leaves out the parts of split models that not every part of matched
+fn skip_incomplete_models(files: &mut Vec<FileInfo>) {
+    let mut parts: HashMap<PathBuf, u32> = HashMap::new();
+    for shard in files.iter().filter_map(|file| file.shard.as_ref()) {
+        *parts.entry(shard.group.clone()).or_default() += 1;
+    }
+    files.retain(|file| match &file.shard {
+        Some(shard) if parts[&shard.group] < shard.count => {
+            eprintln!(
+                "Skipping {}, only {} of its {} parts match",
+                file.path.display(),
+                parts[&shard.group],
+                shard.count
+            );
+            false
+        }
+        _ => true,
+    });
+}
+
+fn skip_open_files(files: &mut Vec<FileInfo>, in_use: &HashMap<PathBuf, Vec<FileUser>>) {
+    files.retain(|file| match in_use.get(&file.path) {
+        Some(users) => {
+            let users: Vec<&str> = users.iter().map(|user| user.name.as_str()).collect();
+            eprintln!(
+                "Skipping {}, {} has it open",
+                file.path.display(),
+                users.join(", ")
+            );
+            false
+        }
+        None => true,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ggufscan_core::{ModelFormat, Shard};
+    use std::path::Path;
+
+    fn file(path: &str) -> FileInfo {
+        let path = PathBuf::from(path);
+        FileInfo {
+            shard: Shard::parse(&path, None),
+            path,
+            size: 1 << 30,
+            root: PathBuf::from("/models"),
+            modified: None,
+            accessed: None,
+            format: ModelFormat::Gguf,
+            header: None,
+            duplicate_group: None,
+            file_id: None,
+            links: 1,
+            symlink: false,
+            readonly: false,
+            source: None,
+            corrupt: None,
+            packed: Vec::new(),
+        }
+    }
+
+    fn paths(files: &[FileInfo]) -> Vec<&Path> {
+        files.iter().map(|file| file.path.as_path()).collect()
+    }
+
+    #[test]
+    fn skips_models_missing_parts() {
+        let mut files = vec![
+            file("/models/llama-00001-of-00002.gguf"),
+            file("/models/llama-00002-of-00002.gguf"),
+            file("/models/qwen-00001-of-00003.gguf"),
+            file("/models/qwen-00003-of-00003.gguf"),
+            file("/models/phi.gguf"),
+        ];
+        skip_incomplete_models(&mut files);
+        assert_eq!(
+            paths(&files),
+            [
+                Path::new("/models/llama-00001-of-00002.gguf"),
+                Path::new("/models/llama-00002-of-00002.gguf"),
+                Path::new("/models/phi.gguf"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_open_files() {
+        let mut files = vec![file("/models/llama.gguf"), file("/models/phi.gguf")];
+        let in_use = HashMap::from([(
+            PathBuf::from("/models/llama.gguf"),
+            vec![FileUser {
+                pid: 42,
+                name: "llama-server".to_string(),
+            }],
+        )]);
+        skip_open_files(&mut files, &in_use);
+        assert_eq!(paths(&files), [Path::new("/models/phi.gguf")]);
+    }
+}
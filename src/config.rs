@@ -0,0 +1,251 @@
+//! Settings read from `config.toml` in the ggufscan config directory.
+
+use anyhow::{Context, Result};
+use ggufscan_core::{parse_age, parse_size};
+use serde::{de, Deserialize, Deserializer};
+use std::{collections::HashMap, fs, io, path::PathBuf, time::Duration};
+
+use crate::{
+    keymap::{Action, ConfigKeys, Keymap},
+    schedule::Schedule,
+    theme::{Theme, ThemeColors, ThemeName},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Scanned when no roots are given on the command line
+    pub roots: Vec<String>,
+    // Network shares like `\\nas\models` scanned along with the drives when
+    // neither roots nor `roots` are given
+    pub shares: Vec<String>,
+    // Directories with models below the roots, walked first along with the
+    // Ollama, Hugging Face and LM Studio folders
+    pub model_dirs: Vec<String>,
+    // Added to the `--exclude` globs
+    pub exclude: Vec<String>,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub min_size: Option<u64>,
+    #[serde(deserialize_with = "deserialize_age")]
+    pub older_than: Option<Duration>,
+    pub delete: DeleteMode,
+    // Where `delete = "quarantine"` moves files, defaults to the ggufscan
+    // data directory
+    pub quarantine_dir: Option<String>,
+    // Where archiving compresses models to, defaults to the ggufscan data
+    // directory
+    pub archive_dir: Option<String>,
+    // Columns of the file list in the order shown, the file name or path
+    // always comes last
+    pub columns: Vec<Column>,
+    pub theme: ThemeName,
+    // Single colors replacing those of the theme
+    pub colors: ThemeColors,
+    // Keys replacing the defaults of an action
+    pub keys: HashMap<Action, ConfigKeys>,
+    // Command the highlighted model is run with, like
+    // `llama-server -m {path}`
+    pub runner: Option<String>,
+    // What normalizing names renames the selected files to, like
+    // `{name}-{params}-{quant}.gguf`
+    pub name_template: Option<String>,
+    // Command merging split models, `llama-gguf-split` from llama.cpp
+    pub gguf_split: Option<String>,
+    // Keep every scan in the inventory database, as `--record` does
+    pub record_scans: bool,
+    // Where the inventory database is, defaults to the ggufscan data
+    // directory
+    pub inventory: Option<String>,
+    // Show a desktop notification when a scan is done, as `--notify` does
+    pub notify: bool,
+    pub daemon: DaemonConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            shares: Vec::new(),
+            model_dirs: Vec::new(),
+            exclude: Vec::new(),
+            min_size: None,
+            older_than: None,
+            delete: DeleteMode::default(),
+            quarantine_dir: None,
+            archive_dir: None,
+            columns: Column::DEFAULT.to_vec(),
+            theme: ThemeName::default(),
+            colors: ThemeColors::default(),
+            keys: HashMap::new(),
+            runner: None,
+            name_template: None,
+            gguf_split: None,
+            record_scans: false,
+            inventory: None,
+            notify: false,
+            daemon: DaemonConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    // A missing file is the same as an empty one
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_path() else {
+            return Ok(Self::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text)
+                .with_context(|| format!("Error reading config {}", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Error reading config {}", path.display())),
+        }
+    }
+
+    pub fn theme(&self) -> Theme {
+        Theme::new(self.theme, &self.colors)
+    }
+
+    pub fn keymap(&self) -> Result<Keymap> {
+        Keymap::new(&self.keys).context("Error in the [keys] table of the config")
+    }
+}
+
+// `$XDG_CONFIG_HOME/ggufscan/config.toml`, `%APPDATA%` on Windows and
+// `~/.config` otherwise
+pub fn config_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("ggufscan").join("config.toml"))
+}
+
+// The `[daemon]` table, for `ggufscan daemon`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    // When to scan, a cron expression like `0 3 * * *`
+    #[serde(deserialize_with = "deserialize_schedule")]
+    pub schedule: Option<Schedule>,
+    // Alert when the models found take up more than this
+    #[serde(deserialize_with = "deserialize_size")]
+    pub alert_size: Option<u64>,
+    // Command run on an alert, like `notify-send ggufscan {message}`, with
+    // `{total}`, `{limit}` and `{message}` replaced
+    pub alert_command: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeleteMode {
+    #[default]
+    Trash,
+    Permanent,
+    // Moved below a directory of our own, see `ggufscan quarantine`
+    Quarantine,
+}
+
+// Sizes and ages are written the same way as on the command line, `"100M"`
+// and `"90d"`
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let size = String::deserialize(deserializer)?;
+    parse_size(&size)
+        .map(Some)
+        .ok_or_else(|| de::Error::custom(format!("invalid size {:?}", size)))
+}
+
+fn deserialize_age<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    let age = String::deserialize(deserializer)?;
+    parse_age(&age)
+        .map(Some)
+        .ok_or_else(|| de::Error::custom(format!("invalid age {:?}", age)))
+}
+
+fn deserialize_schedule<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Schedule>, D::Error> {
+    let schedule = String::deserialize(deserializer)?;
+    schedule
+        .parse()
+        .map(Some)
+        .map_err(|e| de::Error::custom(format!("invalid schedule {:?}: {}", schedule, e)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Column {
+    Size,
+    Date,
+    Format,
+    Architecture,
+    #[serde(alias = "quant")]
+    Quantization,
+    Parameters,
+    ParentDir,
+    // The full path instead of just the file name
+    Path,
+}
+
+impl Column {
+    pub const ALL: [Column; 8] = [
+        Column::Size,
+        Column::Date,
+        Column::Format,
+        Column::Architecture,
+        Column::Quantization,
+        Column::Parameters,
+        Column::ParentDir,
+        Column::Path,
+    ];
+
+    const DEFAULT: [Column; 7] = [
+        Column::Size,
+        Column::Date,
+        Column::Format,
+        Column::Architecture,
+        Column::Quantization,
+        Column::Parameters,
+        Column::Path,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Column::Size => "Size",
+            Column::Date => "Date",
+            Column::Format => "Format",
+            Column::Architecture => "Architecture",
+            Column::Quantization => "Quantization",
+            Column::Parameters => "Parameters",
+            Column::ParentDir => "Parent directory",
+            Column::Path => "Full path",
+        }
+    }
+
+    // Title in the header row of the list
+    pub fn header(self) -> &'static str {
+        match self {
+            Column::Size => "Size",
+            Column::Date => "Date",
+            Column::Format => "Format",
+            Column::Architecture => "Arch",
+            Column::Quantization => "Quant",
+            Column::Parameters => "Params",
+            Column::ParentDir => "Parent",
+            Column::Path => "Path",
+        }
+    }
+
+    // Characters the column takes up in the list
+    pub fn width(self) -> usize {
+        match self {
+            Column::Size | Column::Date | Column::Architecture => 10,
+            Column::Format => 11,
+            Column::Quantization | Column::Parameters => 8,
+            Column::ParentDir => 24,
+            Column::Path => 0,
+        }
+    }
+}
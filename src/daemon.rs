@@ -0,0 +1,185 @@
+//! `ggufscan daemon`: scans the roots on the schedule of the `[daemon]`
+//! table, keeps each scan in the inventory database and alerts when the
+//! models take up more than `alert_size`.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use ggufscan_core::{format_size, unique_size, ScanMessage, ScanOptions};
+use std::{
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    check_roots,
+    config::Config,
+    configured_roots,
+    db::{inventory_path, record_scan},
+    notify::notify,
+    scan_summary, spawn_scan, DaemonArgs,
+};
+
+// Sleeps are cut short to notice the clock jumping, e.g. after a suspend
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+pub(crate) fn run_daemon(config: &Config, args: &DaemonArgs) -> Result<()> {
+    let schedule = args
+        .schedule
+        .clone()
+        .or_else(|| config.daemon.schedule.clone())
+        .context(
+            "No schedule set, add schedule = \"0 3 * * *\" to the [daemon] table of the config \
+             or pass --schedule",
+        )?;
+    anyhow::ensure!(
+        schedule.next_after(Local::now()).is_some(),
+        "Schedule {} never runs",
+        schedule
+    );
+    let roots = if args.paths.is_empty() {
+        configured_roots(config)?
+    } else {
+        args.paths.clone()
+    };
+    check_roots(&roots)?;
+    let inventory = inventory_path(config).context("No inventory database found")?;
+    let alert_size = args.alert_size.or(config.daemon.alert_size);
+    let notify = args.notify || config.notify;
+    let options = args.scan.scan_options(config, roots.clone())?;
+
+    eprintln!(
+        "Scanning {} on schedule {} into {}",
+        roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        schedule,
+        inventory.display()
+    );
+    let mut last_total = None;
+    loop {
+        let total = scan(&options, &roots, &inventory, last_total, notify);
+        if let (Some(total), Some(limit)) = (total, alert_size) {
+            // Once when the limit is crossed, not on every scan above it
+            if total > limit && last_total.is_none_or(|last| last <= limit) {
+                alert(config, total, limit, notify);
+            }
+        }
+        last_total = total.or(last_total);
+
+        let next = schedule
+            .next_after(Local::now())
+            .with_context(|| format!("Schedule {} never runs", schedule))?;
+        eprintln!("Next scan at {}", next.format("%Y-%m-%d %H:%M"));
+        while let Ok(left) = (next - Local::now()).to_std() {
+            thread::sleep(left.min(MAX_SLEEP));
+        }
+    }
+}
+
+// Scans once and records it, the total size of the models or `None` when the
+// scan couldn't be recorded
+fn scan(
+    options: &ScanOptions,
+    roots: &[PathBuf],
+    inventory: &Path,
+    last_total: Option<u64>,
+    notify: bool,
+) -> Option<u64> {
+    let (mut files, mut errors) = (Vec::new(), 0);
+    for message in spawn_scan(options.clone()).1 {
+        match message {
+            ScanMessage::File(file) => files.push(*file),
+            ScanMessage::Error(_) => errors += 1,
+            ScanMessage::Done | ScanMessage::Cancelled => break,
+            _ => {}
+        }
+    }
+    let scan = match record_scan(inventory, roots, &files) {
+        Ok(scan) => scan,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            return None;
+        }
+    };
+    let total = unique_size(files.iter());
+    let change = match last_total {
+        Some(last) if total >= last => {
+            format!(", +{} since the last scan", format_size(total - last))
+        }
+        Some(last) => format!(", -{} since the last scan", format_size(last - total)),
+        None => String::new(),
+    };
+    eprintln!(
+        "{} scan #{}: {} files, {}{}{}",
+        Local::now().format("%Y-%m-%d %H:%M"),
+        scan,
+        files.len(),
+        format_size(total),
+        change,
+        match errors {
+            0 => String::new(),
+            errors => format!(", {} unreadable", errors),
+        }
+    );
+    if notify {
+        show_notification(
+            "ggufscan scan done",
+            &(scan_summary(roots, &files) + &change),
+        );
+    }
+    Some(total)
+}
+
+fn alert(config: &Config, total: u64, limit: u64, notify: bool) {
+    let message = format!(
+        "Models take up {}, more than the {} alert size",
+        format_size(total),
+        format_size(limit)
+    );
+    eprintln!("{}", message);
+    if notify {
+        show_notification("ggufscan alert", &message);
+    }
+    let Some(command) = &config.daemon.alert_command else {
+        return;
+    };
+    let Some(mut command) = alert_command(command, total, limit, &message) else {
+        return;
+    };
+    match command.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("Alert command failed with {}", status),
+        Err(e) => eprintln!("Error running the alert command: {}", e),
+    }
+}
+
+fn show_notification(title: &str, body: &str) {
+    if let Err(e) = notify(title, body) {
+        eprintln!("Error showing the notification: {}", e);
+    }
+}
+
+// Split on whitespace like the runner command, with the placeholders
+// replaced in every word so the message stays one argument
+fn alert_command(
+    command: &str,
+    total: u64,
+    limit: u64,
+    message: &str,
+) -> Option<std::process::Command> {
+    let words: Vec<String> = command
+        .split_whitespace()
+        .map(|word| {
+            word.replace("{total}", &format_size(total))
+                .replace("{limit}", &format_size(limit))
+                .replace("{message}", message)
+        })
+        .collect();
+    let (program, args) = words.split_first()?;
+    let mut command = std::process::Command::new(program);
+    command.args(args);
+    Some(command)
+}
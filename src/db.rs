@@ -0,0 +1,215 @@
+//! `ggufscan db`: the scans recorded in the inventory database, queried or
+//! listed with how the total size changed between them.
+
+use anyhow::{Context, Result};
+use ggufscan_core::{default_inventory_path, format_date, format_size, FileInfo, Inventory};
+use std::{
+    io::{self, stdout, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{config::Config, expand_home, DbCommand};
+
+// `inventory` from the config or the default in the data directory
+pub(crate) fn inventory_path(config: &Config) -> Option<PathBuf> {
+    config
+        .inventory
+        .as_deref()
+        .map(expand_home)
+        .or_else(default_inventory_path)
+}
+
+pub(crate) fn record_scan(inventory: &Path, roots: &[PathBuf], files: &[FileInfo]) -> Result<i64> {
+    let mut inventory = Inventory::open(inventory)
+        .with_context(|| format!("Error opening {}", inventory.display()))?;
+    Ok(inventory.record_scan(roots, files)?)
+}
+
+pub(crate) fn run_db(inventory: &Path, command: &DbCommand) -> Result<()> {
+    anyhow::ensure!(
+        inventory.exists(),
+        "No scans recorded yet in {}, scan with --record first",
+        inventory.display()
+    );
+    let inventory = Inventory::open(inventory)
+        .with_context(|| format!("Error opening {}", inventory.display()))?;
+    let mut out = io::BufWriter::new(stdout().lock());
+    match command {
+        DbCommand::Query { query, scan } => {
+            let summary = write_query(&inventory, query, *scan, &mut out)?;
+            out.flush()?;
+            eprintln!("{}", summary);
+        }
+        DbCommand::History => {
+            write_history(&inventory, &mut out)?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+// Writes the files matching `query` and returns the line summing them up
+fn write_query(
+    inventory: &Inventory,
+    query: &str,
+    scan: Option<i64>,
+    out: &mut impl Write,
+) -> Result<String> {
+    let files = inventory.query(query, scan)?;
+    for file in &files {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            format_size(file.size),
+            file.format,
+            file.architecture.as_deref().unwrap_or("-"),
+            file.quantization.as_deref().unwrap_or("-"),
+            file.path.display()
+        )?;
+    }
+    let total: u64 = files.iter().map(|file| file.size).sum();
+    let scans = inventory.scans()?;
+    let scan = files
+        .first()
+        .map(|file| file.scan)
+        .or(scan)
+        .or(scans.last().map(|scan| scan.id));
+    Ok(match scans.iter().find(|record| Some(record.id) == scan) {
+        Some(record) => format!(
+            "{} files, {} in scan #{} of {}",
+            files.len(),
+            format_size(total),
+            record.id,
+            format_date(record.time)
+        ),
+        None => "No such scan".to_string(),
+    })
+}
+
+fn write_history(inventory: &Inventory, out: &mut impl Write) -> Result<()> {
+    let scans = inventory.scans()?;
+    for (i, record) in scans.iter().enumerate() {
+        // Compared with the last scan of the same roots
+        let change = scans[..i]
+            .iter()
+            .rev()
+            .find(|earlier| earlier.roots == record.roots)
+            .map(|earlier| {
+                if record.total_size >= earlier.total_size {
+                    format!("+{}", format_size(record.total_size - earlier.total_size))
+                } else {
+                    format!("-{}", format_size(earlier.total_size - record.total_size))
+                }
+            })
+            .unwrap_or_else(|| "-".to_string());
+        let roots: Vec<String> = record
+            .roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect();
+        writeln!(
+            out,
+            "#{}\t{}\t{} files\t{}\t{}\t{}",
+            record.id,
+            format_date(record.time),
+            record.files,
+            format_size(record.total_size),
+            change,
+            roots.join(", ")
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ggufscan_core::ModelFormat;
+    use std::fs;
+
+    fn file(path: &str, size: u64) -> FileInfo {
+        FileInfo {
+            path: PathBuf::from(path),
+            size,
+            root: PathBuf::from("/models"),
+            modified: None,
+            accessed: None,
+            format: ModelFormat::Gguf,
+            header: None,
+            duplicate_group: None,
+            file_id: None,
+            links: 1,
+            symlink: false,
+            readonly: false,
+            source: None,
+            shard: None,
+            corrupt: None,
+            packed: Vec::new(),
+        }
+    }
+
+    fn output(write: impl FnOnce(&mut Vec<u8>) -> Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_queries_and_history() {
+        let dir = std::env::temp_dir().join(format!("ggufscan-test-{}-db", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("inventory.db");
+        let roots = [PathBuf::from("/models")];
+        let llama = file("/models/llama.gguf", 4 << 30);
+        let phi = file("/models/phi.gguf", 2 << 30);
+        record_scan(&path, &roots, &[llama, phi]).unwrap();
+        record_scan(&path, &roots, &[file("/models/phi.gguf", 2 << 30)]).unwrap();
+        record_scan(&path, &[PathBuf::from("/other")], &[]).unwrap();
+        let inventory = Inventory::open(&path).unwrap();
+        let scans = inventory.scans().unwrap();
+
+        let mut summary = String::new();
+        let listed = output(|out| {
+            summary = write_query(&inventory, "size > 3G", Some(1), out)?;
+            Ok(())
+        });
+        let size = |gib: u64| format_size(gib << 30);
+        assert_eq!(
+            listed,
+            format!("{}\tGGUF\t-\t-\t/models/llama.gguf\n", size(4))
+        );
+        assert_eq!(
+            summary,
+            format!(
+                "1 files, {} in scan #1 of {}",
+                size(4),
+                format_date(scans[0].time)
+            )
+        );
+        let listed = output(|out| {
+            summary = write_query(&inventory, "", Some(7), out)?;
+            Ok(())
+        });
+        assert_eq!((listed.as_str(), summary.as_str()), ("", "No such scan"));
+
+        let history = output(|out| write_history(&inventory, out));
+        let times: Vec<String> = scans.iter().map(|scan| format_date(scan.time)).collect();
+        assert_eq!(
+            history,
+            format!(
+                "#1\t{}\t2 files\t{}\t-\t/models\n\
+                 #2\t{}\t1 files\t{}\t-{}\t/models\n\
+                 #3\t{}\t0 files\t{}\t-\t/other\n",
+                times[0],
+                size(6),
+                times[1],
+                size(2),
+                size(4),
+                times[2],
+                size(0)
+            )
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+//! The filter bar of the file list and the `--arch` filter of the scans.
+
+use ggufscan_core::{
+    parse_age, parse_size, FileInfo, GgufHeader, MetadataValue, ModelFormat, ModelSource,
+};
+use std::time::Duration;
+
+// What the filter bar asks for, whitespace separated terms that all have to
+// match: `size>100M` and `size<2G` compare the size, `age>90d` and `age<1w`
+// the time since the file was last modified or read, anything else is
+// matched case-insensitively against the path and the main metadata fields
+#[derive(Debug, Default)]
+pub(crate) struct FilterQuery {
+    terms: Vec<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    min_age: Option<Duration>,
+    max_age: Option<Duration>,
+}
+
+impl FilterQuery {
+    pub(crate) fn parse(filter: &str) -> Self {
+        let mut query = Self::default();
+        for term in filter.split_whitespace() {
+            let term = term.to_lowercase();
+            if let Some(size) = term.strip_prefix("size>").and_then(parse_size) {
+                query.min_size = Some(size);
+            } else if let Some(size) = term.strip_prefix("size<").and_then(parse_size) {
+                query.max_size = Some(size);
+            } else if let Some(age) = term.strip_prefix("age>").and_then(parse_age) {
+                query.min_age = Some(age);
+            } else if let Some(age) = term.strip_prefix("age<").and_then(parse_age) {
+                query.max_age = Some(age);
+            } else {
+                query.terms.push(term);
+            }
+        }
+        query
+    }
+
+    // Incomplete split models are told by the other parts, not the file
+    pub(crate) fn matches(&self, file: &FileInfo, incomplete: bool) -> bool {
+        if self.min_size.is_some_and(|size| file.size < size)
+            || self.max_size.is_some_and(|size| file.size > size)
+        {
+            return false;
+        }
+        if self.min_age.is_some() || self.max_age.is_some() {
+            // Files without timestamps can't be told apart by age
+            let Some(age) = file.last_used().and_then(|time| time.elapsed().ok()) else {
+                return false;
+            };
+            if self.min_age.is_some_and(|min| age < min)
+                || self.max_age.is_some_and(|max| age > max)
+            {
+                return false;
+            }
+        }
+        if self.terms.is_empty() {
+            return true;
+        }
+
+        let mut fields = vec![
+            file.path.to_string_lossy().into_owned(),
+            file.format.label().to_string(),
+        ];
+        fields.extend(file.source.as_ref().map(ModelSource::label));
+        if file.corrupt.is_some() {
+            fields.push("corrupt".to_string());
+        }
+        if is_legacy(file) {
+            fields.push("legacy".to_string());
+        }
+        if incomplete {
+            fields.push("incomplete".to_string());
+        }
+        if let Some(header) = &file.header {
+            fields.extend(header.architecture().map(str::to_string));
+            fields.extend(header.quantization().map(str::to_string));
+            fields.extend(header.size_class());
+            fields.extend(
+                header
+                    .get("general.name")
+                    .and_then(MetadataValue::as_str)
+                    .map(str::to_string),
+            );
+        }
+        let fields: Vec<String> = fields.iter().map(|field| field.to_lowercase()).collect();
+        self.terms
+            .iter()
+            .all(|term| fields.iter().any(|field| field.contains(term.as_str())))
+    }
+}
+
+// GGUF v1 and the GGML formats before it, which llama.cpp stopped loading
+pub(crate) fn is_legacy(file: &FileInfo) -> bool {
+    file.format == ModelFormat::Ggml || file.header.as_ref().is_some_and(GgufHeader::is_legacy)
+}
+
+// Whether the file is of one of the architectures, all files are when none
+// are given and files without a GGUF header never are otherwise
+pub(crate) fn arch_matches(architectures: &[String], file: &FileInfo) -> bool {
+    architectures.is_empty()
+        || file
+            .header
+            .as_ref()
+            .and_then(GgufHeader::architecture)
+            .is_some_and(|arch| architectures.iter().any(|a| a.eq_ignore_ascii_case(arch)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{path::PathBuf, time::SystemTime};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn file(path: &str, size: u64, age: Option<Duration>) -> FileInfo {
+        FileInfo {
+            path: PathBuf::from(path),
+            size,
+            root: PathBuf::from("/models"),
+            modified: age.map(|age| SystemTime::now() - age),
+            accessed: None,
+            format: ModelFormat::Gguf,
+            header: None,
+            duplicate_group: None,
+            file_id: None,
+            links: 1,
+            symlink: false,
+            readonly: false,
+            source: None,
+            shard: None,
+            corrupt: None,
+            packed: Vec::new(),
+        }
+    }
+
+    fn llama(version: u32) -> FileInfo {
+        let mut file = file("/models/model.gguf", 4 << 30, Some(DAY));
+        file.header = Some(GgufHeader {
+            version,
+            tensor_count: 0,
+            metadata: [
+                ("general.architecture", "llama"),
+                ("general.name", "Meta Llama 3"),
+                ("general.size_label", "8B"),
+            ]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), MetadataValue::String(value.to_string())))
+            .chain([("general.file_type".to_string(), MetadataValue::U32(15))])
+            .collect(),
+            parameter_count: 8_000_000_000,
+            tensor_type: None,
+            data_end: None,
+        });
+        file
+    }
+
+    #[test]
+    fn parses_filters() {
+        let query = FilterQuery::parse("  Llama SIZE>1G size<2gib age>90d age<1y size>big ");
+        assert_eq!(query.terms, ["llama", "size>big"]);
+        assert_eq!(query.min_size, Some(1 << 30));
+        assert_eq!(query.max_size, Some(2 << 30));
+        assert_eq!(query.min_age, Some(90 * DAY));
+        assert_eq!(query.max_age, Some(365 * DAY));
+        assert!(FilterQuery::parse("").terms.is_empty());
+    }
+
+    #[test]
+    fn empty_filters_match_everything() {
+        let query = FilterQuery::parse("");
+        assert!(query.matches(&file("/a.gguf", 0, None), false));
+        assert!(query.matches(&llama(3), true));
+    }
+
+    #[test]
+    fn filters_by_size() {
+        let file = file("/a.gguf", 1 << 30, None);
+        assert!(FilterQuery::parse("size>1G").matches(&file, false));
+        assert!(FilterQuery::parse("size<1G").matches(&file, false));
+        assert!(!FilterQuery::parse("size>1025M").matches(&file, false));
+        assert!(!FilterQuery::parse("size<1023M").matches(&file, false));
+    }
+
+    #[test]
+    fn filters_by_age() {
+        let old = file("/old.gguf", 0, Some(100 * DAY));
+        let new = file("/new.gguf", 0, Some(DAY));
+        let query = FilterQuery::parse("age>90d");
+        assert!(query.matches(&old, false));
+        assert!(!query.matches(&new, false));
+        assert!(FilterQuery::parse("age<1w").matches(&new, false));
+        // Read again recently counts as used
+        let mut read = file("/read.gguf", 0, Some(100 * DAY));
+        read.accessed = Some(SystemTime::now());
+        assert!(!query.matches(&read, false));
+        // Files without timestamps never match an age
+        let unknown = file("/unknown.gguf", 0, None);
+        assert!(!query.matches(&unknown, false));
+        assert!(!FilterQuery::parse("age<1w").matches(&unknown, false));
+    }
+
+    #[test]
+    fn matches_terms_against_the_metadata() {
+        let file = llama(3);
+        for filter in [
+            "LLAMA",
+            "q4_k",
+            "8b",
+            "meta",
+            "gguf",
+            "models/",
+            "llama q4_k_m",
+        ] {
+            assert!(
+                FilterQuery::parse(filter).matches(&file, false),
+                "{}",
+                filter
+            );
+        }
+        for filter in ["qwen", "llama qwen", "legacy", "corrupt", "incomplete"] {
+            assert!(
+                !FilterQuery::parse(filter).matches(&file, false),
+                "{}",
+                filter
+            );
+        }
+        assert!(FilterQuery::parse("incomplete").matches(&file, true));
+    }
+
+    #[test]
+    fn flags_legacy_and_corrupt_files() {
+        assert!(is_legacy(&llama(1)));
+        assert!(!is_legacy(&llama(2)));
+        let mut ggml = file("/old.bin", 0, None);
+        ggml.format = ModelFormat::Ggml;
+        assert!(is_legacy(&ggml));
+        assert!(FilterQuery::parse("legacy").matches(&llama(1), false));
+
+        let mut corrupt = file("/cut.gguf", 0, None);
+        corrupt.corrupt = Some("truncated".to_string());
+        assert!(FilterQuery::parse("corrupt").matches(&corrupt, false));
+    }
+
+    #[test]
+    fn matches_architectures() {
+        let model = llama(3);
+        assert!(arch_matches(&[], &model));
+        assert!(arch_matches(
+            &["Qwen2".to_string(), "LLaMA".to_string()],
+            &model
+        ));
+        assert!(!arch_matches(&["qwen2".to_string()], &model));
+        // Files without a header match only when no architecture is asked for
+        let headerless = file("/a.safetensors", 0, None);
+        assert!(arch_matches(&[], &headerless));
+        assert!(!arch_matches(&["llama".to_string()], &headerless));
+    }
+}
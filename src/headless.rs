@@ -0,0 +1,270 @@
+//! The scan written to stdout instead of the TUI, as the files of
+//! `--output` and `--report` or the messages of `--progress ndjson`.
+
+use anyhow::Result;
+use ggufscan_core::{
+    export_files, format_size, format_text_line, unique_size, write_event, ExportFormat, FileInfo,
+    ScanMessage,
+};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+};
+
+use crate::{db::record_scan, filter::arch_matches, notify};
+
+pub(crate) fn run_headless(
+    out: &mut impl Write,
+    rx: Receiver<ScanMessage>,
+    format: ExportFormat,
+    roots: &[PathBuf],
+    architectures: &[String],
+    inventory: Option<&Path>,
+    notify: bool,
+) -> Result<()> {
+    let mut files = Vec::new();
+
+    for message in rx {
+        match message {
+            ScanMessage::File(file_info) if !arch_matches(architectures, &file_info) => {}
+            ScanMessage::File(file_info) => {
+                // Plain text is streamed as files are found, other formats
+                // need the complete result set
+                if format == ExportFormat::Text {
+                    let written = writeln!(out, "{}", format_text_line(&file_info));
+                    match written {
+                        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                        written => written?,
+                    }
+                }
+                files.push(*file_info);
+            }
+            ScanMessage::Error(error) => eprintln!("{}", error),
+            ScanMessage::CacheLoaded(_)
+            | ScanMessage::Directory(_)
+            | ScanMessage::Progress(_)
+            | ScanMessage::RootProgress(..)
+            | ScanMessage::RootDone(_)
+            | ScanMessage::CheckingDuplicates
+            | ScanMessage::DuplicateGroup(_)
+            | ScanMessage::DuplicatesDone
+            | ScanMessage::Watching
+            | ScanMessage::Removed(_) => {}
+            ScanMessage::Done => {
+                if let Some(inventory) = inventory {
+                    record_scan(inventory, roots, &files)?;
+                }
+                break;
+            }
+            ScanMessage::Cancelled => break,
+        }
+    }
+
+    if format != ExportFormat::Text {
+        export_files(out, format, roots, &files)?;
+    }
+    out.flush()?;
+
+    let total = unique_size(files.iter());
+    eprintln!(
+        "Found {} model files, {} total",
+        files.len(),
+        format_size(total)
+    );
+    if notify {
+        if let Err(e) = notify::notify("ggufscan scan done", &scan_summary(roots, &files)) {
+            eprintln!("Error showing the notification: {}", e);
+        }
+    }
+    Ok(())
+}
+
+// What a notification says about a finished scan
+pub(crate) fn scan_summary(roots: &[PathBuf], files: &[FileInfo]) -> String {
+    format!(
+        "Found {} model files, {} total in {}",
+        files.len(),
+        format_size(unique_size(files.iter())),
+        roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+// Every message of the scan as a line of JSON, until the scan and the
+// duplicate check are done or, with `--watch`, for good
+pub(crate) fn run_events(
+    out: &mut impl Write,
+    rx: Receiver<ScanMessage>,
+    roots: &[PathBuf],
+    architectures: &[String],
+    inventory: Option<&Path>,
+) -> Result<()> {
+    let mut files = Vec::new();
+    for message in rx {
+        if let ScanMessage::File(file_info) = &message {
+            if !arch_matches(architectures, file_info) {
+                continue;
+            }
+        }
+        match write_event(out, &message) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            written => written?,
+        }
+        match message {
+            ScanMessage::File(file_info) => files.push(*file_info),
+            ScanMessage::Done => {
+                if let Some(inventory) = inventory {
+                    record_scan(inventory, roots, &files)?;
+                }
+            }
+            ScanMessage::Cancelled => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ggufscan_core::{GgufHeader, Inventory, MetadataValue, ModelFormat};
+    use std::{fs, sync::mpsc};
+
+    fn file(path: &str, arch: &str) -> FileInfo {
+        FileInfo {
+            path: PathBuf::from(path),
+            size: 4 << 30,
+            root: PathBuf::from("/models"),
+            modified: None,
+            accessed: None,
+            format: ModelFormat::Gguf,
+            header: Some(GgufHeader {
+                version: 3,
+                tensor_count: 0,
+                metadata: vec![(
+                    "general.architecture".to_string(),
+                    MetadataValue::String(arch.to_string()),
+                )],
+                parameter_count: 0,
+                tensor_type: None,
+                data_end: None,
+            }),
+            duplicate_group: None,
+            file_id: None,
+            links: 1,
+            symlink: false,
+            readonly: false,
+            source: None,
+            shard: None,
+            corrupt: None,
+            packed: Vec::new(),
+        }
+    }
+
+    // A scan finding a llama and a phi3 model, ended by `end`
+    fn scan(end: ScanMessage) -> Receiver<ScanMessage> {
+        let (tx, rx) = mpsc::channel();
+        for message in [
+            ScanMessage::File(Box::new(file("/models/llama.gguf", "llama"))),
+            ScanMessage::Error("Error reading /models/broken.gguf".to_string()),
+            ScanMessage::File(Box::new(file("/models/phi.gguf", "phi3"))),
+            end,
+            ScanMessage::File(Box::new(file("/models/late.gguf", "llama"))),
+        ] {
+            tx.send(message).unwrap();
+        }
+        rx
+    }
+
+    #[test]
+    fn writes_the_files_found() {
+        let roots = [PathBuf::from("/models")];
+        let llama = ["llama".to_string()];
+
+        let mut out = Vec::new();
+        let rx = scan(ScanMessage::Done);
+        run_headless(
+            &mut out,
+            rx,
+            ExportFormat::Text,
+            &roots,
+            &llama,
+            None,
+            false,
+        )
+        .unwrap();
+        let line = format_text_line(&file("/models/llama.gguf", "llama"));
+        assert_eq!(String::from_utf8(out).unwrap(), line + "\n");
+
+        let mut out = Vec::new();
+        let rx = scan(ScanMessage::Done);
+        run_headless(&mut out, rx, ExportFormat::Json, &roots, &[], None, false).unwrap();
+        let mut expected = Vec::new();
+        let files = [
+            file("/models/llama.gguf", "llama"),
+            file("/models/phi.gguf", "phi3"),
+        ];
+        export_files(&mut expected, ExportFormat::Json, &roots, &files).unwrap();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn records_finished_scans() {
+        let dir =
+            std::env::temp_dir().join(format!("ggufscan-test-{}-headless", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let inventory = dir.join("inventory.db");
+        let roots = [PathBuf::from("/models")];
+
+        let rx = scan(ScanMessage::Cancelled);
+        run_headless(
+            &mut io::sink(),
+            rx,
+            ExportFormat::Text,
+            &roots,
+            &[],
+            Some(&inventory),
+            false,
+        )
+        .unwrap();
+        assert!(!inventory.exists());
+
+        let rx = scan(ScanMessage::Done);
+        run_headless(
+            &mut io::sink(),
+            rx,
+            ExportFormat::Text,
+            &roots,
+            &[],
+            Some(&inventory),
+            false,
+        )
+        .unwrap();
+        let scans = Inventory::open(&inventory).unwrap().scans().unwrap();
+        assert_eq!(scans.len(), 1);
+        assert_eq!(scans[0].files, 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_events_until_cancelled() {
+        let mut out = Vec::new();
+        let roots = [PathBuf::from("/models")];
+        let rx = scan(ScanMessage::Cancelled);
+        run_events(&mut out, rx, &roots, &["phi3".to_string()], None).unwrap();
+        let mut expected = Vec::new();
+        for message in [
+            ScanMessage::Error("Error reading /models/broken.gguf".to_string()),
+            ScanMessage::File(Box::new(file("/models/phi.gguf", "phi3"))),
+            ScanMessage::Cancelled,
+        ] {
+            write_event(&mut expected, &message).unwrap();
+        }
+        assert_eq!(out, expected);
+    }
+}
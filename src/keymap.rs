@@ -0,0 +1,587 @@
+//! What the keys do in the file list, the defaults with the `[keys]` table of
+//! the config on top.
+
+use anyhow::{bail, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    Top,
+    Bottom,
+    Expand,
+    Collapse,
+    Toggle,
+    Visual,
+    SelectUp,
+    SelectDown,
+    SelectAll,
+    DeselectAll,
+    InvertSelection,
+    SelectDuplicates,
+    SelectOlder,
+    SelectCorrupt,
+    Selection,
+    Delete,
+    Undo,
+    Move,
+    Archive,
+    Merge,
+    RestoreArchive,
+    Rename,
+    NormalizeNames,
+    CopyPath,
+    OpenFolder,
+    Run,
+    RemoveRevision,
+    Verify,
+    Checksum,
+    Details,
+    ChatTemplate,
+    Tensors,
+    Diff,
+    Sort,
+    SortDirection,
+    Filter,
+    Architectures,
+    Quantizations,
+    Families,
+    Command,
+    Columns,
+    DirView,
+    TreeView,
+    ExportJson,
+    ExportCsv,
+    Pause,
+    CancelScan,
+    Rescan,
+    Errors,
+    Help,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 57] = [
+        Action::Up,
+        Action::Down,
+        Action::PageUp,
+        Action::PageDown,
+        Action::HalfPageUp,
+        Action::HalfPageDown,
+        Action::Top,
+        Action::Bottom,
+        Action::Expand,
+        Action::Collapse,
+        Action::Toggle,
+        Action::Visual,
+        Action::SelectUp,
+        Action::SelectDown,
+        Action::SelectAll,
+        Action::DeselectAll,
+        Action::InvertSelection,
+        Action::SelectDuplicates,
+        Action::SelectOlder,
+        Action::SelectCorrupt,
+        Action::Selection,
+        Action::Delete,
+        Action::Undo,
+        Action::Move,
+        Action::Archive,
+        Action::Merge,
+        Action::RestoreArchive,
+        Action::Rename,
+        Action::NormalizeNames,
+        Action::CopyPath,
+        Action::OpenFolder,
+        Action::Run,
+        Action::RemoveRevision,
+        Action::Verify,
+        Action::Checksum,
+        Action::Details,
+        Action::ChatTemplate,
+        Action::Tensors,
+        Action::Diff,
+        Action::Sort,
+        Action::SortDirection,
+        Action::Filter,
+        Action::Architectures,
+        Action::Quantizations,
+        Action::Families,
+        Action::Command,
+        Action::Columns,
+        Action::DirView,
+        Action::TreeView,
+        Action::ExportJson,
+        Action::ExportCsv,
+        Action::Pause,
+        Action::CancelScan,
+        Action::Rescan,
+        Action::Errors,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Up => &["up", "k"],
+            Action::Down => &["down", "j"],
+            Action::PageUp => &["pageup"],
+            Action::PageDown => &["pagedown"],
+            Action::HalfPageUp => &["ctrl-u"],
+            Action::HalfPageDown => &["ctrl-d"],
+            Action::Top => &["home", "g g"],
+            Action::Bottom => &["end", "G"],
+            Action::Expand => &["right"],
+            Action::Collapse => &["left"],
+            Action::Toggle => &["space"],
+            Action::Visual => &["v"],
+            Action::SelectUp => &["shift-up"],
+            Action::SelectDown => &["shift-down"],
+            Action::SelectAll => &["a"],
+            Action::DeselectAll => &["u"],
+            Action::InvertSelection => &["i"],
+            Action::SelectDuplicates => &["K"],
+            Action::SelectOlder => &["N"],
+            Action::SelectCorrupt => &["B"],
+            Action::Selection => &["w"],
+            Action::Delete => &["d"],
+            Action::Undo => &["z"],
+            Action::Move => &["m"],
+            Action::Archive => &["Z"],
+            Action::Merge => &["M"],
+            Action::RestoreArchive => &["U"],
+            Action::Rename => &["f2", "R"],
+            Action::NormalizeNames => &["n"],
+            Action::CopyPath => &["y"],
+            Action::OpenFolder => &["o"],
+            Action::Run => &["L"],
+            Action::RemoveRevision => &["H"],
+            Action::Verify => &["V"],
+            Action::Checksum => &["h"],
+            Action::Details => &["tab", "enter"],
+            Action::ChatTemplate => &["t"],
+            Action::Tensors => &["l"],
+            Action::Diff => &["="],
+            Action::Sort => &["s"],
+            Action::SortDirection => &["S"],
+            Action::Filter => &["/"],
+            Action::Architectures => &["A"],
+            Action::Quantizations => &["Q"],
+            Action::Families => &["F"],
+            Action::Command => &[":"],
+            Action::Columns => &["C"],
+            // Not `g`, which starts `g g`
+            Action::DirView => &["D"],
+            Action::TreeView => &["T"],
+            Action::ExportJson => &["x"],
+            Action::ExportCsv => &["e"],
+            Action::Pause => &["p"],
+            Action::CancelScan => &["c", "esc"],
+            Action::Rescan => &["r"],
+            Action::Errors => &["E"],
+            Action::Help => &["?"],
+            Action::Quit => &["q"],
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Up => "Move up",
+            Action::Down => "Move down",
+            Action::PageUp => "Page up",
+            Action::PageDown => "Page down",
+            Action::HalfPageUp => "Half a page up",
+            Action::HalfPageDown => "Half a page down",
+            Action::Top => "Go to the top",
+            Action::Bottom => "Go to the bottom",
+            Action::Expand => "Expand split model/open directory",
+            Action::Collapse => "Collapse split model/back to directories",
+            Action::Toggle => "Select/deselect file",
+            Action::Visual => "Select a range",
+            Action::SelectUp => "Extend the range up",
+            Action::SelectDown => "Extend the range down",
+            Action::SelectAll => "Select all",
+            Action::DeselectAll => "Deselect all",
+            Action::InvertSelection => "Invert selection",
+            Action::SelectDuplicates => "Select duplicate copies",
+            Action::SelectOlder => "Keep newest copy, select older",
+            Action::SelectCorrupt => "Select corrupt, legacy and incomplete files",
+            Action::Selection => "List selected files",
+            Action::Delete => "Delete selected",
+            Action::Undo => "Undo delete",
+            Action::Move => "Move selected",
+            Action::Archive => "Archive selected with zstd",
+            Action::Merge => "Merge split model",
+            Action::RestoreArchive => "Restore selected archives",
+            Action::Rename => "Rename file",
+            Action::NormalizeNames => "Normalize names of selected",
+            Action::CopyPath => "Copy path",
+            Action::OpenFolder => "Open folder",
+            Action::Run => "Run the model",
+            Action::RemoveRevision => "Remove stale HF revision",
+            Action::Verify => "Verify against Hugging Face",
+            Action::Checksum => "Compute checksums",
+            Action::Details => "Show/hide details",
+            Action::ChatTemplate => "Show chat template",
+            Action::Tensors => "List tensors",
+            Action::Diff => "Compare two selected files",
+            Action::Sort => "Sort by next column",
+            Action::SortDirection => "Flip sort direction",
+            Action::Filter => "Filter",
+            Action::Architectures => "Filter by architecture",
+            Action::Quantizations => "Storage by quantization",
+            Action::Families => "Storage by architecture and family",
+            Action::Command => "Run a command like :select",
+            Action::Columns => "Show/hide columns",
+            Action::DirView => "Group by directory",
+            Action::TreeView => "Disk usage tree",
+            Action::ExportJson => "Export JSON",
+            Action::ExportCsv => "Export CSV",
+            Action::Pause => "Pause/resume scan",
+            Action::CancelScan => "Cancel scan/delete",
+            Action::Rescan => "Rescan",
+            Action::Errors => "Show errors",
+            Action::Help => "Show all keys",
+            Action::Quit => "Quit",
+        }
+    }
+
+    // Moving the highlight extends the visual range instead of ending it
+    pub fn is_movement(self) -> bool {
+        matches!(
+            self,
+            Action::Up
+                | Action::Down
+                | Action::PageUp
+                | Action::PageDown
+                | Action::HalfPageUp
+                | Action::HalfPageDown
+                | Action::Top
+                | Action::Bottom
+                | Action::SelectUp
+                | Action::SelectDown
+        )
+    }
+}
+
+// One key or several for an action in the config, `[]` unbinds it
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigKeys {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    code: KeyCode,
+    ctrl: bool,
+    shift: bool,
+}
+
+impl KeyPress {
+    // Shift is part of the character already, `G` is shift-g, it only
+    // counts for the other keys
+    pub fn from_event(event: &KeyEvent) -> Self {
+        Self {
+            code: event.code,
+            ctrl: event.modifiers.contains(KeyModifiers::CONTROL),
+            shift: !matches!(event.code, KeyCode::Char(_))
+                && event.modifiers.contains(KeyModifiers::SHIFT),
+        }
+    }
+
+    // `q`, `G`, `ctrl-d`, `shift-up`, `space`, `pageup` and the like
+    fn parse(key: &str) -> Option<Self> {
+        let (mut ctrl, mut shift, mut name) = (false, false, key);
+        loop {
+            let prefix = |prefix: &str| {
+                name.get(..prefix.len() + 1)
+                    .filter(|start| {
+                        start
+                            .get(..prefix.len())
+                            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+                            && (start.ends_with('-') || start.ends_with('+'))
+                    })
+                    .map(|start| &name[start.len()..])
+            };
+            if let Some(rest) = prefix("ctrl") {
+                (ctrl, name) = (true, rest);
+            } else if let Some(rest) = prefix("shift") {
+                (shift, name) = (true, rest);
+            } else {
+                break;
+            }
+        }
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match name.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+            },
+        };
+        // `shift-g` is `G`
+        let code = match code {
+            KeyCode::Char(c) if shift => {
+                shift = false;
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            code => code,
+        };
+        Some(Self { code, ctrl, shift })
+    }
+
+    fn label(self) -> String {
+        let name = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => "Enter".to_string(),
+            KeyCode::Esc => "Esc".to_string(),
+            KeyCode::Tab => "Tab".to_string(),
+            KeyCode::Backspace => "Backspace".to_string(),
+            KeyCode::Delete => "Del".to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::PageUp => "PgUp".to_string(),
+            KeyCode::PageDown => "PgDn".to_string(),
+            KeyCode::Home => "Home".to_string(),
+            KeyCode::End => "End".to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            code => format!("{:?}", code),
+        };
+        let name = if self.shift {
+            format!("Shift-{}", name)
+        } else {
+            name
+        };
+        if self.ctrl {
+            format!("Ctrl-{}", name)
+        } else {
+            name
+        }
+    }
+}
+
+// A key or a sequence of keys pressed one after another, like `g g`
+type Binding = Vec<KeyPress>;
+
+fn binding_label(binding: &Binding) -> String {
+    binding
+        .iter()
+        .map(|key| key.label())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug)]
+pub struct Keymap {
+    bindings: Vec<(Binding, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(&HashMap::new()).expect("default keys parse")
+    }
+}
+
+impl Keymap {
+    // The keys from the config replace the defaults of their action, a key
+    // bound to two actions is an error rather than a surprise
+    pub fn new(overrides: &HashMap<Action, ConfigKeys>) -> Result<Self> {
+        let mut bindings: Vec<(Binding, Action)> = Vec::new();
+        for action in Action::ALL {
+            let keys: Vec<&str> = match overrides.get(&action) {
+                Some(ConfigKeys::One(key)) => vec![key.as_str()],
+                Some(ConfigKeys::Many(keys)) => keys.iter().map(String::as_str).collect(),
+                None => action.default_keys().to_vec(),
+            };
+            for key in keys {
+                let binding: Option<Binding> =
+                    key.split_whitespace().map(KeyPress::parse).collect();
+                let binding = binding
+                    .filter(|binding| (1..=2).contains(&binding.len()))
+                    .with_context(|| {
+                        format!("Invalid key {:?} for {:?}", key, action.description())
+                    })?;
+                if let Some((_, other)) = bindings.iter().find(|(other, _)| *other == binding) {
+                    bail!(
+                        "Key {:?} is bound to both {:?} and {:?}",
+                        key,
+                        other.description(),
+                        action.description()
+                    );
+                }
+                // A single key taken as soon as it's pressed, a sequence
+                // starting with it could never be finished
+                if let Some((other, other_action)) = bindings
+                    .iter()
+                    .find(|(other, _)| other.starts_with(&binding) || binding.starts_with(other))
+                {
+                    let ((short, short_action), (long, long_action)) =
+                        if other.len() < binding.len() {
+                            ((other, other_action), (&binding, &action))
+                        } else {
+                            ((&binding, &action), (other, other_action))
+                        };
+                    bail!(
+                        "Key {:?} for {:?} starts {:?} for {:?}, which could never be pressed",
+                        binding_label(short),
+                        short_action.description(),
+                        binding_label(long),
+                        long_action.description()
+                    );
+                }
+                bindings.push((binding, action));
+            }
+        }
+        Ok(Self { bindings })
+    }
+
+    // The action of the key, `pending` holds the first key of a sequence
+    // until the next one comes in
+    pub fn action(&self, pending: &mut Option<KeyPress>, key: KeyPress) -> Option<Action> {
+        if let Some(first) = pending.take() {
+            if let Some(action) = self.find(&[first, key]) {
+                return Some(action);
+            }
+        }
+        if let Some(action) = self.find(&[key]) {
+            return Some(action);
+        }
+        if self
+            .bindings
+            .iter()
+            .any(|(binding, _)| binding.len() > 1 && binding[0] == key)
+        {
+            *pending = Some(key);
+        }
+        None
+    }
+
+    fn find(&self, keys: &[KeyPress]) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding == keys)
+            .map(|&(_, action)| action)
+    }
+
+    // All keys of the action, `↑/k`, empty when it is unbound
+    pub fn keys_label(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .filter(|&&(_, bound)| bound == action)
+            .map(|(binding, _)| {
+                binding
+                    .iter()
+                    .map(|key| key.label())
+                    .collect::<Vec<_>>()
+                    .join("")
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(keys: &[(Action, &str)]) -> Result<Keymap> {
+        let overrides = keys
+            .iter()
+            .map(|&(action, key)| (action, ConfigKeys::One(key.to_string())))
+            .collect();
+        Keymap::new(&overrides)
+    }
+
+    fn press(key: &str) -> KeyPress {
+        KeyPress::parse(key).unwrap()
+    }
+
+    #[test]
+    fn default_keys_parse() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.keys_label(Action::Top), "Home/gg");
+        assert_eq!(keymap.keys_label(Action::HalfPageDown), "Ctrl-d");
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(press("G"), press("shift-g"));
+        assert_eq!(press("Ctrl+x").label(), "Ctrl-x");
+        assert_eq!(press("shift-up").label(), "Shift-↑");
+        assert_eq!(press("f12").code, KeyCode::F(12));
+        assert_eq!(press("space").code, KeyCode::Char(' '));
+        for key in ["", "ctrl-", "fx", "f99999", "pageupp"] {
+            assert!(KeyPress::parse(key).is_none(), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn finishes_sequences() {
+        let keymap = Keymap::default();
+        let mut pending = None;
+        assert_eq!(keymap.action(&mut pending, press("g")), None);
+        assert_eq!(pending, Some(press("g")));
+        assert_eq!(keymap.action(&mut pending, press("g")), Some(Action::Top));
+        assert_eq!(pending, None);
+        // A key that doesn't finish the sequence counts on its own
+        keymap.action(&mut pending, press("g"));
+        assert_eq!(keymap.action(&mut pending, press("j")), Some(Action::Down));
+    }
+
+    #[test]
+    fn rejects_keys_bound_twice() {
+        let error = keymap(&[(Action::Quit, "d")]).unwrap_err().to_string();
+        assert!(error.contains("bound to both"), "{}", error);
+        assert!(keymap(&[(Action::Quit, "d"), (Action::Delete, "ctrl-x")]).is_ok());
+    }
+
+    #[test]
+    fn rejects_keys_starting_sequences() {
+        let error = keymap(&[(Action::DirView, "g")]).unwrap_err().to_string();
+        assert!(
+            error.contains("\"g\" for \"Group by directory\" starts \"g g\""),
+            "{}",
+            error
+        );
+        let error = keymap(&[(Action::Help, "q x")]).unwrap_err().to_string();
+        assert!(
+            error.contains("\"q\" for \"Quit\" starts \"q x\""),
+            "{}",
+            error
+        );
+        assert!(keymap(&[(Action::DirView, "g"), (Action::Top, "g t")]).is_err());
+        assert!(keymap(&[(Action::DirView, "g"), (Action::Top, "home")]).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert!(keymap(&[(Action::Quit, "hyper-q")]).is_err());
+        assert!(keymap(&[(Action::Quit, "a b c")]).is_err());
+    }
+}
@@ -1,342 +1,5646 @@
+mod clean;
+mod config;
+mod daemon;
+mod db;
+mod filter;
+mod headless;
+mod keymap;
+mod merge;
+mod notify;
+mod quarantine;
+mod rename;
+mod schedule;
+mod serve;
+mod theme;
+
 use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use config::{Column, Config, DeleteMode};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use ignore::WalkBuilder;
+use db::{inventory_path, record_scan};
+use filter::{arch_matches, is_legacy, FilterQuery};
+use ggufscan_core::{
+    archive_file, checksum_file, default_archive_dir, default_cache_path,
+    default_checksum_cache_path, default_roots, delete_object, export_files, file_users,
+    format_date, format_day, format_param_count, format_size, format_size_class,
+    globset::{Glob, GlobSet, GlobSetBuilder},
+    hf_candidates, is_s3_path, lmstudio_model_dirs, move_file, parse_age, parse_size,
+    quarantine_file, read_tensors, reclaimable_size, remote_path, remove_dir, remove_file,
+    restore_archive, restore_from_trash, restore_quarantined, unique_size, unix_seconds,
+    verify_file, write_remote_message, ChecksumCache, Checksums, ExportFormat, FileInfo, FileUser,
+    GgufHeader, MetadataValue, ModelFormat, ModelSource, RemoteScanner, ReportGroup,
+    RevisionRemoval, S3Scanner, ScanControl, ScanMessage, ScanOptions, ScanProgress, Scanner,
+    TensorInfo, Verification,
+};
+use headless::{run_events, run_headless, scan_summary};
+use keymap::{Action, KeyPress, Keymap};
+use merge::{merged_path, Merge, MergeResult, DEFAULT_GGUF_SPLIT};
+use quarantine::quarantine_dir;
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{
+        Bar, BarChart, BarGroup, Block, Borders, Clear, Gauge, List, ListItem, ListState,
+        Paragraph, Wrap,
+    },
 };
+use regex::Regex;
+use rename::{planned_renames, DEFAULT_NAME_TEMPLATE};
+use schedule::Schedule;
+use theme::Theme;
 
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
-    io::{self, stdout, Read},
-    path::PathBuf,
-    sync::mpsc::{self, Receiver, Sender},
+    io::{self, stdout, Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 
-const GGUF_MAGIC: &[u8] = b"GGUF";
+/// Find and delete GGUF model files.
+#[derive(Parser, Debug)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Directories or `s3://bucket/prefix` locations to scan, defaults to
+    /// the filesystem root (every drive on Windows)
+    #[arg(value_name = "ROOT")]
+    paths: Vec<PathBuf>,
+
+    /// Directory to scan (same as the positional argument, can be repeated)
+    #[arg(long = "root", value_name = "ROOT")]
+    roots: Vec<PathBuf>,
+
+    /// Scan the model folders of a known tool, in addition to any given roots
+    #[arg(long, value_enum, value_name = "PRESET")]
+    preset: Option<Preset>,
+
+    /// Look at the files listed on stdin, one per line or separated by NUL
+    /// as `find -print0` writes them, instead of scanning
+    #[arg(long, conflicts_with_all = ["paths", "roots", "preset", "watch"])]
+    stdin: bool,
+
+    #[command(flatten)]
+    delete: DeleteArgs,
+
+    /// Only tell what deleting, moving or removing a revision would do,
+    /// nothing on disk is touched
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Never delete, move or remove anything, for audits where the files
+    /// must stay as they are
+    #[arg(long)]
+    read_only: bool,
+
+    /// Skip hashing same-sized files to find duplicates after the scan
+    #[arg(long)]
+    no_dedup: bool,
+
+    /// Scan everything again instead of starting from the last results
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Keep the list up to date with files added or removed after the scan
+    #[arg(long)]
+    watch: bool,
+
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    /// Only list models of these architectures, e.g. `llama,qwen2`
+    #[arg(long, value_name = "ARCH", value_delimiter = ',')]
+    arch: Vec<String>,
+
+    /// Keep the results of this scan in the inventory database, see
+    /// `ggufscan db`
+    #[arg(long)]
+    record: bool,
+
+    /// Show a desktop notification when the scan is done, for scans left
+    /// running in the background
+    #[arg(long)]
+    notify: bool,
+
+    /// Print the found files to stdout instead of starting the TUI
+    #[arg(long)]
+    no_tui: bool,
+
+    /// Print the found files in the given format instead of starting the TUI
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    output: Option<OutputFormat>,
+
+    /// Print an inventory report of the found files instead of starting the
+    /// TUI
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["output", "no_tui"])]
+    report: Option<ReportFormat>,
+
+    /// What the sections of the report are
+    #[arg(
+        long,
+        value_enum,
+        value_name = "GROUP",
+        default_value = "directory",
+        requires = "report"
+    )]
+    report_by: ReportBy,
+
+    /// Stream what the scan does to stdout instead of starting the TUI, one
+    /// JSON object per event
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["output", "no_tui", "report"])]
+    progress: Option<ProgressFormat>,
+
+    // Scan for `ggufscan ssh` on the other machine, writing the messages to
+    // stdout
+    #[arg(long, hide = true, conflicts_with_all = ["output", "no_tui", "report", "progress"])]
+    remote_helper: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the files deleted with `--quarantine`, or restore or purge them
+    Quarantine {
+        /// Only files that were below these paths
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+
+        /// Move the files back where they were
+        #[arg(long, conflicts_with = "purge")]
+        restore: bool,
+
+        /// Delete the files for good
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Delete the models matching the filters without the TUI, e.g. from
+    /// cron
+    Clean(CleanArgs),
+    /// Look up the scans kept with `--record`
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// Scan a directory of another machine over SSH, e.g.
+    /// `ggufscan ssh gpu-box:/data/models`. `ggufscan` has to be installed
+    /// there and ssh has to log in without a password prompt.
+    Ssh(SshArgs),
+    /// Scan in the background and answer a JSON API over HTTP, for
+    /// dashboards and scripts
+    Serve(ServeArgs),
+    /// Scan on the schedule of the `[daemon]` table of the config, record
+    /// every scan and alert when the models take up too much
+    Daemon(DaemonArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct DeleteArgs {
+    /// Delete files permanently instead of moving them to the trash
+    #[arg(long)]
+    permanent: bool,
+
+    /// Move deleted files to the trash even if the config says otherwise
+    #[arg(long, conflicts_with = "permanent")]
+    trash: bool,
+
+    /// Move deleted files to the quarantine directory, keeping their paths,
+    /// instead of the trash
+    #[arg(long, conflicts_with_all = ["permanent", "trash"])]
+    quarantine: bool,
+}
+
+impl DeleteArgs {
+    fn delete_mode(&self, config: &Config) -> DeleteMode {
+        if self.permanent {
+            DeleteMode::Permanent
+        } else if self.trash {
+            DeleteMode::Trash
+        } else if self.quarantine {
+            DeleteMode::Quarantine
+        } else {
+            config.delete
+        }
+    }
+}
+
+// The filters and filesystem flags of every command that scans
+#[derive(clap::Args, Debug)]
+struct ScanArgs {
+    /// Skip files and directories matching this glob (can be repeated)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Stay on the filesystems of the roots, skipping anything mounted below
+    /// (like `du -x`)
+    #[arg(short = 'x', long, visible_alias = "same-filesystem")]
+    one_file_system: bool,
+
+    /// Descend into symlinked directories, like model folders linked in from
+    /// an external drive
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Skip network filesystems (NFS, SMB, ...) and removable drives mounted
+    /// below the roots
+    #[arg(long)]
+    skip_network_mounts: bool,
+
+    /// Also scan `/proc`, `/sys`, `/dev`, snap mounts and the other system
+    /// pseudo-filesystems skipped by default
+    #[arg(long)]
+    no_default_excludes: bool,
+
+    /// Only descend this many directory levels below the roots
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Skip files smaller than this, e.g. `100M` or `2G`
+    #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+    min_size: Option<u64>,
+
+    /// Only list files not modified or read for this long, e.g. `90d` or `1y`
+    #[arg(long, value_name = "AGE", value_parser = parse_age_arg)]
+    older_than: Option<Duration>,
+
+    /// Read every file to tell whether it is a model, not only those named
+    /// like one (`.gguf`, `.safetensors`, `.bin`, ...) or without an extension
+    #[arg(long)]
+    deep: bool,
+
+    /// Also look for GGUF files inside `.zip`, `.tar` and `.tar.gz` archives,
+    /// reading only their headers
+    #[arg(long)]
+    scan_archives: bool,
+}
+
+impl ScanArgs {
+    // A scan of the roots with the filters of the config where none are
+    // given, without watching or looking for duplicates
+    fn scan_options(&self, config: &Config, roots: Vec<PathBuf>) -> Result<ScanOptions> {
+        Ok(ScanOptions {
+            roots,
+            excludes: exclude_set(self.exclude.iter().chain(&config.exclude))?,
+            find_duplicates: false,
+            cache: default_cache_path(),
+            watch: false,
+            one_file_system: self.one_file_system,
+            follow_symlinks: self.follow_symlinks,
+            skip_network_mounts: self.skip_network_mounts,
+            default_excludes: !self.no_default_excludes,
+            max_depth: self.max_depth,
+            min_size: self.min_size.or(config.min_size).unwrap_or(0),
+            older_than: self.older_than.or(config.older_than),
+            deep: self.deep,
+            scan_archives: self.scan_archives,
+            files: Vec::new(),
+            priority_dirs: config
+                .model_dirs
+                .iter()
+                .map(|dir| expand_home(dir))
+                .collect(),
+        })
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct SshArgs {
+    /// What to scan, `[USER@]HOST:PATH` like scp takes it
+    #[arg(value_name = "[USER@]HOST:PATH")]
+    target: String,
+
+    /// The ggufscan to run on the other machine, when it isn't on the PATH
+    /// there
+    #[arg(long, value_name = "PATH", default_value = "ggufscan")]
+    helper: String,
+
+    /// Print the found files in the given format instead of starting the TUI
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    output: Option<OutputFormat>,
+
+    /// Scan options passed on to the other side, after `--`, e.g.
+    /// `-- --min-size 1G --deep`
+    #[arg(last = true, value_name = "SCAN_ARGS")]
+    scan_args: Vec<String>,
+}
 
-#[derive(Debug)]
-struct FileInfo {
+// A scan of another machine, started again the same way on a rescan
+#[derive(Debug, Clone)]
+struct RemoteTarget {
+    host: String,
     path: PathBuf,
-    size: u64,
+    helper: String,
+    args: Vec<String>,
+}
+
+impl RemoteTarget {
+    fn parse(ssh: &SshArgs) -> Result<Self> {
+        let (host, path) = ssh
+            .target
+            .split_once(':')
+            .filter(|(host, path)| !host.is_empty() && !path.is_empty())
+            .with_context(|| format!("Expected [USER@]HOST:PATH, got {}", ssh.target))?;
+        Ok(Self {
+            host: host.to_string(),
+            path: PathBuf::from(path),
+            helper: ssh.helper.clone(),
+            args: ssh.scan_args.clone(),
+        })
+    }
+
+    // The root as the files are shown, `host:/path`
+    fn root(&self) -> PathBuf {
+        remote_path(&self.host, &self.path)
+    }
+
+    fn scanner(&self) -> RemoteScanner {
+        RemoteScanner::new(
+            self.host.clone(),
+            vec![self.path.clone()],
+            self.helper.clone(),
+            self.args.clone(),
+        )
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct CleanArgs {
+    /// Directories or `s3://bucket/prefix` locations to clean, defaults to
+    /// the roots of the config or the filesystem root
+    #[arg(value_name = "ROOT")]
+    paths: Vec<PathBuf>,
+
+    /// Only models of these architectures, e.g. `llama,qwen2`
+    #[arg(long, value_name = "ARCH", value_delimiter = ',')]
+    arch: Vec<String>,
+
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    /// Delete without asking
+    #[arg(long, short)]
+    yes: bool,
+
+    /// Only list what would be deleted
+    #[arg(long)]
+    dry_run: bool,
+
+    #[command(flatten)]
+    delete: DeleteArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    /// Directories or `s3://bucket/prefix` locations to scan, defaults to
+    /// the roots of the config or the filesystem root
+    #[arg(value_name = "ROOT")]
+    paths: Vec<PathBuf>,
+
+    /// Port to listen on
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+
+    /// Address to listen on, `0.0.0.0` for other machines to reach it
+    #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1")]
+    bind: String,
+
+    /// Token the rescan and delete requests need as `Authorization: Bearer
+    /// TOKEN`, also taken from `GGUFSCAN_TOKEN`. Without one nothing can be
+    /// deleted.
+    #[arg(long, value_name = "TOKEN")]
+    token: Option<String>,
+
+    /// Origin of a web dashboard allowed to call the API from the browser,
+    /// e.g. `http://localhost:3000`
+    #[arg(long, value_name = "ORIGIN")]
+    allow_origin: Option<String>,
+
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    #[command(flatten)]
+    delete: DeleteArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct DaemonArgs {
+    /// Directories or `s3://bucket/prefix` locations to scan, defaults to
+    /// the roots of the config or the filesystem root
+    #[arg(value_name = "ROOT")]
+    paths: Vec<PathBuf>,
+
+    /// When to scan, a cron expression in local time like `"0 3 * * *"`,
+    /// instead of the schedule of the config
+    #[arg(long, value_name = "CRON")]
+    schedule: Option<Schedule>,
+
+    /// Alert when the models take up more than this, e.g. `500G`
+    #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+    alert_size: Option<u64>,
+
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    /// Show a desktop notification when a scan is done and on alerts
+    #[arg(long)]
+    notify: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum DbCommand {
+    /// List the files of a recorded scan, e.g. `"size > 10GB and arch = llama"`
+    Query {
+        /// Conditions joined by `and` on size, params, age, arch, quant,
+        /// format or path; all files when left out
+        #[arg(default_value = "")]
+        query: String,
+
+        /// The scan to look at, the latest one by default
+        #[arg(long, value_name = "ID")]
+        scan: Option<i64>,
+    },
+    /// List the recorded scans with how the total size changed
+    History,
+}
+
+fn parse_size_arg(size: &str) -> Result<u64, String> {
+    parse_size(size).ok_or_else(|| format!("invalid size {:?}, expected e.g. 500M or 2G", size))
+}
+
+fn parse_age_arg(age: &str) -> Result<Duration, String> {
+    parse_age(age).ok_or_else(|| format!("invalid age {:?}, expected e.g. 12h, 90d or 1y", age))
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Preset {
+    Lmstudio,
+}
+
+impl Preset {
+    fn label(self) -> &'static str {
+        match self {
+            Preset::Lmstudio => "LM Studio",
+        }
+    }
+
+    fn roots(self) -> Vec<PathBuf> {
+        match self {
+            Preset::Lmstudio => lmstudio_model_dirs(),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportFormat {
+    Md,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ProgressFormat {
+    Ndjson,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportBy {
+    Directory,
+    Architecture,
+}
+
+impl From<ReportBy> for ReportGroup {
+    fn from(group: ReportBy) -> Self {
+        match group {
+            ReportBy::Directory => ReportGroup::Directory,
+            ReportBy::Architecture => ReportGroup::Architecture,
+        }
+    }
+}
+
+impl From<OutputFormat> for ExportFormat {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Text => ExportFormat::Text,
+            OutputFormat::Json => ExportFormat::Json,
+            OutputFormat::Csv => ExportFormat::Csv,
+        }
+    }
+}
+
+impl Args {
+    fn scan_options(&self, config: &Config) -> Result<ScanOptions> {
+        let files = if self.stdin {
+            listed_files(io::stdin().lock())?
+        } else {
+            Vec::new()
+        };
+        // Listed files are shown below the directory they all are in
+        let roots = match common_dir(&files) {
+            Some(dir) => vec![dir],
+            None => self.scan_roots(config)?,
+        };
+
+        Ok(ScanOptions {
+            find_duplicates: !self.no_dedup && self.headless_output().is_none(),
+            cache: if self.no_cache {
+                None
+            } else {
+                default_cache_path()
+            },
+            watch: self.watch && self.headless_output().is_none(),
+            files,
+            ..self.scan.scan_options(config, roots)?
+        })
+    }
+
+    // `--no-tui` is shorthand for `--output text`
+    fn headless_output(&self) -> Option<ExportFormat> {
+        match self.report {
+            Some(ReportFormat::Md) => Some(ExportFormat::Markdown(self.report_by.into())),
+            None => self
+                .output
+                .or(self.no_tui.then_some(OutputFormat::Text))
+                .map(ExportFormat::from),
+        }
+    }
+
+    fn scan_roots(&self, config: &Config) -> Result<Vec<PathBuf>> {
+        let preset_roots = match self.preset {
+            Some(preset) => {
+                let roots = preset.roots();
+                anyhow::ensure!(
+                    !roots.is_empty(),
+                    "No {} models folder found",
+                    preset.label()
+                );
+                roots
+            }
+            None => Vec::new(),
+        };
+
+        let mut roots: Vec<PathBuf> = Vec::new();
+        for root in self.paths.iter().chain(&self.roots).chain(&preset_roots) {
+            if !roots.contains(root) {
+                roots.push(root.clone());
+            }
+        }
+        if roots.is_empty() {
+            roots = configured_roots(config)?;
+        }
+        Ok(roots)
+    }
+}
+
+// Paths one per line, or separated by NUL as `find -print0` writes them
+fn listed_files(mut input: impl Read) -> Result<Vec<PathBuf>> {
+    let mut bytes = Vec::new();
+    input
+        .read_to_end(&mut bytes)
+        .context("Error reading paths from stdin")?;
+    let separator = if bytes.contains(&0) { b'\0' } else { b'\n' };
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for path in bytes.split(|&byte| byte == separator) {
+        let path = String::from_utf8_lossy(path);
+        let path = path.strip_suffix('\r').unwrap_or(&path);
+        if path.is_empty() {
+            continue;
+        }
+        let path = std::path::absolute(path)?;
+        if seen.insert(path.clone()) {
+            files.push(path);
+        }
+    }
+    anyhow::ensure!(!files.is_empty(), "No paths on stdin");
+    Ok(files)
+}
+
+// The deepest directory all the files are in
+fn common_dir(files: &[PathBuf]) -> Option<PathBuf> {
+    let mut dir = files.first()?.parent()?.to_owned();
+    for file in files {
+        while !file.starts_with(&dir) && dir.pop() {}
+    }
+    Some(dir)
+}
+
+fn exclude_set<'a>(patterns: impl IntoIterator<Item = &'a String>) -> Result<GlobSet> {
+    let mut excludes = GlobSetBuilder::new();
+    for pattern in patterns {
+        excludes
+            .add(Glob::new(pattern).with_context(|| format!("Invalid exclude glob {}", pattern))?);
+    }
+    Ok(excludes.build()?)
+}
+
+// The roots of the config, or every drive and the shares when there are none
+fn configured_roots(config: &Config) -> Result<Vec<PathBuf>> {
+    let mut roots: Vec<PathBuf> = config.roots.iter().map(|root| expand_home(root)).collect();
+    if roots.is_empty() {
+        roots = default_roots();
+        roots.extend(config.shares.iter().map(PathBuf::from));
+        anyhow::ensure!(!roots.is_empty(), "No drives found to scan");
+    }
+    Ok(roots)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Size,
+    Name,
+    Path,
+    Modified,
+    Quantization,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Size => SortKey::Name,
+            SortKey::Name => SortKey::Path,
+            SortKey::Path => SortKey::Modified,
+            SortKey::Modified => SortKey::Quantization,
+            SortKey::Quantization => SortKey::Size,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Size => "size",
+            SortKey::Name => "name",
+            SortKey::Path => "path",
+            SortKey::Modified => "date",
+            SortKey::Quantization => "quant",
+        }
+    }
+
+    // Largest and newest files first, names alphabetically
+    fn default_descending(self) -> bool {
+        matches!(self, SortKey::Size | SortKey::Modified)
+    }
+
+    fn compare(self, a: &FileInfo, b: &FileInfo) -> std::cmp::Ordering {
+        match self {
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Name => a.path.file_name().cmp(&b.path.file_name()),
+            SortKey::Path => a.path.cmp(&b.path),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Quantization => bits_per_weight(a)
+                .total_cmp(&bits_per_weight(b))
+                .then_with(|| quantization(a).cmp(&quantization(b))),
+        }
+    }
 }
 
-// Function to check if a file is a GGUF file by reading its magic number
-fn is_gguf_file(path: &std::path::Path) -> io::Result<bool> {
-    let mut file = fs::File::open(path)?;
-    let mut buffer = [0u8; 4];
+fn quantization(file: &FileInfo) -> Option<&'static str> {
+    file.header.as_ref().and_then(GgufHeader::quantization)
+}
 
-    match file.read_exact(&mut buffer) {
-        Ok(_) => Ok(buffer == GGUF_MAGIC),
-        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
-        Err(e) => Err(e),
+// What the quant costs per weight, which orders Q2_K below Q4_K_M below F16
+// without a table of all the quant names; files without a header come first
+fn bits_per_weight(file: &FileInfo) -> f64 {
+    match &file.header {
+        Some(header) if header.parameter_count > 0 => {
+            file.size as f64 * 8.0 / header.parameter_count as f64
+        }
+        _ => 0.0,
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Popup {
+    ConfirmDelete,
+    ConfirmRevision,
+    ConfirmRenames,
+    Filter,
+    MoveDestination,
+    Rename,
+    Command,
+    Columns,
+    Architectures,
+    Help,
+    Errors,
+    Selection,
+    ChatTemplate,
+    Tensors,
+    Diff,
+    Quantizations,
+    Families,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListView {
+    Files,
+    // Files grouped by their parent directory
+    Directories,
+    // Directories nested below the roots, sized by all the files below them
+    Tree,
+}
+
+// A row of the directory or tree view
+struct DirGroup {
+    dir: PathBuf,
+    // The full path in the directory view, relative to the parent row in the
+    // tree with directories holding nothing but one other joined in
+    label: String,
+    depth: usize,
+    // Directories below it in the tree, whether or not they are shown
+    has_children: bool,
+    // The visible files in it, below it in the tree, every part of
+    // collapsed split models included
+    files: Vec<usize>,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+// A delete that went to the trash or quarantine, so it can be taken back
+struct UndoEntry {
+    paths: Vec<PathBuf>,
+    // Taken back out of the quarantine directory rather than the trash
+    quarantined: bool,
+    // Counted in the space reclaimed this session
+    size: u64,
+    // List entries removed along with it
+    files: Vec<FileInfo>,
+}
+
+// Selected files being moved on a background thread
+struct MoveJob {
+    destination: PathBuf,
+    updates: Receiver<MoveUpdate>,
+    files: usize,
+    total_size: u64,
+    // Size of the files done so far and bytes copied of the current one
+    done_size: u64,
+    copied: u64,
+    moved: Vec<PathBuf>,
+    errors: Vec<String>,
+}
+
+// Files being hashed and looked up on Hugging Face in the background
+struct VerifyJob {
+    updates: Receiver<VerifyUpdate>,
+    files: usize,
+    done: usize,
+    // Files Hugging Face gave an answer for
+    checked: Vec<PathBuf>,
+    // Bytes hashed of the current file and its size
+    hashed: u64,
+    size: u64,
+}
+
+// A split model being merged into one file by gguf-split
+struct MergeJob {
+    output: PathBuf,
+    parts: Vec<PathBuf>,
+    total_size: u64,
+    updates: Receiver<Result<MergeResult, String>>,
+}
+
+// The highlighted file being hashed in the background
+struct ChecksumJob {
+    path: PathBuf,
+    size: u64,
+    hashed: u64,
+    updates: Receiver<ChecksumUpdate>,
+}
+
+enum ChecksumUpdate {
+    Hashed(u64),
+    Done(io::Result<Checksums>),
+}
+
+enum VerifyUpdate {
+    Started(u64),
+    Hashed(u64),
+    Done(PathBuf, io::Result<Verification>),
+}
+
+// Selected files being deleted on a background thread
+struct DeleteJob {
+    updates: Receiver<DeleteUpdate>,
+    cancel: Arc<AtomicBool>,
+    files: usize,
+    removed: HashSet<PathBuf>,
+    failed: usize,
+}
+
+enum DeleteUpdate {
+    Removed(PathBuf),
+    Failed(PathBuf, io::Error),
+}
+
+enum MoveUpdate {
+    Copied(u64),
+    Moved(PathBuf, u64),
+    Failed(PathBuf, u64, io::Error),
+}
+
+// Selected files being compressed into the archive directory, or archives
+// being restored, on a background thread
+struct ArchiveJob {
+    restoring: bool,
+    updates: Receiver<ArchiveUpdate>,
+    files: usize,
+    // Sizes of the files before and after, to tell what archiving freed
+    size_before: u64,
+    size_after: u64,
+    done: Vec<PathBuf>,
+    errors: Vec<String>,
+}
+
+enum ArchiveUpdate {
+    Done(PathBuf, PathBuf),
+    Failed(PathBuf, io::Error),
+}
+
 struct App {
+    roots: Vec<PathBuf>,
     files: Vec<FileInfo>,
     selected: Vec<bool>,
+    // Indices into `files` of the rows shown in the list
+    visible: Vec<usize>,
+    filter: String,
     list_state: ListState,
     scanning: bool,
     current_path: String,
-    dirs_scanned: usize,
+    progress: ScanProgress,
+    // How far the walk of each root got when there are several, and the
+    // roots walked completely
+    root_progress: HashMap<PathBuf, ScanProgress>,
+    roots_done: HashSet<PathBuf>,
+    scan_started: Instant,
+    scan_options: Option<ScanOptions>,
+    scan_control: ScanControl,
+    scan_cancelled: bool,
+    // Paths selected before a rescan, selected again when found
+    reselect: HashSet<PathBuf>,
+    // When the current pause started and how long earlier ones took
+    paused_at: Option<Instant>,
+    paused_for: Duration,
     files_found: usize,
+    status: Option<String>,
+    delete_mode: DeleteMode,
+    quarantine_dir: PathBuf,
+    popup: Option<Popup>,
+    show_details: bool,
+    view: ListView,
+    dir_groups: Vec<DirGroup>,
+    // Directories of the tree view showing what is in them
+    expanded_dirs: HashSet<PathBuf>,
+    // Directory opened from the directory view, only the files directly in
+    // it are listed
+    within_dir: Option<PathBuf>,
+    // None keeps the files in discovery order
+    sort_key: Option<SortKey>,
+    sort_descending: bool,
+    checking_duplicates: bool,
+    // Files shown from the scan cache while the refresh is running
+    cached_files: usize,
+    watching: bool,
+    duplicate_groups: usize,
+    pending_revision: Option<RevisionRemoval>,
+    move_destination: String,
+    // New name of the highlighted file, starts out as its current name
+    new_name: String,
+    // Fields of the GGUF metadata normalized names are made of
+    name_template: String,
+    // Files normalizing names would rename and where to, or why not
+    pending_renames: Vec<(PathBuf, Result<PathBuf, String>)>,
+    // What is typed after `:`
+    command: String,
+    move_job: Option<MoveJob>,
+    // Where archived models go, below the path they had
+    archive_dir: PathBuf,
+    archive_job: Option<ArchiveJob>,
+    // Command split models are merged with
+    gguf_split: String,
+    merge_job: Option<MergeJob>,
+    delete_job: Option<DeleteJob>,
+    // Processes having the files about to be deleted open
+    in_use: Vec<(PathBuf, Vec<FileUser>)>,
+    verify_job: Option<VerifyJob>,
+    // What Hugging Face said about the files checked so far
+    verifications: HashMap<PathBuf, Verification>,
+    checksums: ChecksumCache,
+    checksum_job: Option<ChecksumJob>,
+    undo_stack: Vec<UndoEntry>,
+    // Space freed and files removed by deletes this session, less what was
+    // undone
+    reclaimed: u64,
+    removed_files: usize,
+    // With `--dry-run` what would have been done, printed on exit, and the
+    // space it would have freed
+    dry_run: bool,
+    read_only: bool,
+    // Scanning another machine, where nothing can be changed from here
+    remote: Option<RemoteTarget>,
+    // Scanning `s3://` roots, whose objects can only be deleted
+    object_storage: bool,
+    dry_run_log: Vec<String>,
+    dry_run_size: u64,
+    // Depth limit of the scan, shown in the title
+    max_depth: Option<usize>,
+    // Split models listed part by part instead of as a single row
+    expanded_shards: HashSet<PathBuf>,
+    // Split models with parts missing and the number of parts found
+    incomplete_shards: HashMap<PathBuf, u32>,
+    // Rows of the list that fit on screen, as of the last draw
+    list_height: usize,
+    // Where the list was drawn last, for telling what a click hit
+    rows_area: Rect,
+    header_row: u16,
+    header_cells: Vec<(Range<u16>, SortKey)>,
+    // File the visual range started on and the files selected before, the
+    // range is selected on top of them. Kept by path, the list is sorted and
+    // files go away while the range is open.
+    visual: Option<(PathBuf, HashSet<PathBuf>)>,
+    // The range was started with Shift and ends with the next move without it
+    shift_range: bool,
+    keymap: Keymap,
+    // First key of a sequence like `g g`, waiting for the second one
+    pending_key: Option<KeyPress>,
+    theme: Theme,
+    columns: Vec<Column>,
+    // Highlighted entry of the column menu, an index into `Column::ALL`
+    column_cursor: usize,
+    // Architectures the list is restricted to, empty shows all files
+    architectures: Vec<String>,
+    // Highlighted entry of the architecture menu, an index into
+    // `found_architectures`
+    arch_cursor: usize,
+    // Lines the help or error popup is scrolled down by
+    popup_scroll: u16,
+    // Tensors of the file the tensor popup was opened for, read when it
+    // opens
+    tensors: Vec<TensorInfo>,
+    // The two files the diff popup compares
+    diff: Vec<PathBuf>,
+    // Failures while scanning, deleting or moving, the status bar only
+    // shows the count
+    errors: Vec<String>,
+    // Opened on the first copy and kept, on X11 the copied text goes away
+    // with it
+    clipboard: Option<arboard::Clipboard>,
+    // Command the highlighted model is run with, `{path}` is replaced by
+    // its path
+    runner: Option<String>,
+    // Database every finished scan is added to, with `--record`
+    inventory: Option<PathBuf>,
+    // Desktop notification when the scan is done, with `--notify`
+    notify: bool,
 }
 
 impl App {
-    fn new() -> Self {
+    fn new(roots: Vec<PathBuf>, delete_mode: DeleteMode, quarantine_dir: PathBuf) -> Self {
         Self {
+            roots,
             files: Vec::new(),
             selected: Vec::new(),
+            visible: Vec::new(),
+            filter: String::new(),
             list_state: ListState::default(),
             scanning: true,
             current_path: String::new(),
-            dirs_scanned: 0,
+            progress: ScanProgress::default(),
+            root_progress: HashMap::new(),
+            roots_done: HashSet::new(),
+            scan_started: Instant::now(),
+            scan_options: None,
+            scan_control: ScanControl::default(),
+            scan_cancelled: false,
+            reselect: HashSet::new(),
+            paused_at: None,
+            paused_for: Duration::ZERO,
             files_found: 0,
+            status: None,
+            delete_mode,
+            quarantine_dir,
+            popup: None,
+            show_details: false,
+            view: ListView::Files,
+            dir_groups: Vec::new(),
+            expanded_dirs: HashSet::new(),
+            within_dir: None,
+            sort_key: None,
+            sort_descending: false,
+            checking_duplicates: false,
+            cached_files: 0,
+            watching: false,
+            duplicate_groups: 0,
+            pending_revision: None,
+            move_destination: String::new(),
+            new_name: String::new(),
+            name_template: DEFAULT_NAME_TEMPLATE.to_string(),
+            pending_renames: Vec::new(),
+            command: String::new(),
+            move_job: None,
+            archive_dir: PathBuf::new(),
+            archive_job: None,
+            gguf_split: DEFAULT_GGUF_SPLIT.to_string(),
+            merge_job: None,
+            delete_job: None,
+            verify_job: None,
+            verifications: HashMap::new(),
+            checksums: ChecksumCache::default(),
+            checksum_job: None,
+            in_use: Vec::new(),
+            undo_stack: Vec::new(),
+            reclaimed: 0,
+            dry_run: false,
+            read_only: false,
+            remote: None,
+            object_storage: false,
+            dry_run_log: Vec::new(),
+            dry_run_size: 0,
+            removed_files: 0,
+            max_depth: None,
+            expanded_shards: HashSet::new(),
+            incomplete_shards: HashMap::new(),
+            list_height: 0,
+            rows_area: Rect::default(),
+            header_row: 0,
+            header_cells: Vec::new(),
+            visual: None,
+            shift_range: false,
+            keymap: Keymap::default(),
+            pending_key: None,
+            theme: Theme::default(),
+            columns: Config::default().columns,
+            column_cursor: 0,
+            architectures: Vec::new(),
+            arch_cursor: 0,
+            popup_scroll: 0,
+            tensors: Vec::new(),
+            diff: Vec::new(),
+            errors: Vec::new(),
+            clipboard: None,
+            runner: None,
+            inventory: None,
+            notify: false,
         }
     }
 
-    fn toggle_selected(&mut self) {
-        if let Some(i) = self.list_state.selected() {
-            self.selected[i] = !self.selected[i];
+    fn add_file(&mut self, file_info: FileInfo) {
+        // Files changing while watched are reported again
+        if self.watching {
+            if let Some(i) = self
+                .files
+                .iter()
+                .position(|file| file.path == file_info.path)
+            {
+                let highlighted = self.highlighted_file().map(|file| file.path.clone());
+                self.files[i] = file_info;
+                self.prune_duplicate_groups();
+                self.update_visible(highlighted);
+                return;
+            }
+        }
+
+        let is_shard = file_info.shard.is_some();
+        // Only split models can be incomplete, they are shown below
+        let shown = FilterQuery::parse(&self.filter).matches(&file_info, false)
+            && arch_matches(&self.architectures, &file_info);
+        let selected = self.reselect.contains(&file_info.path);
+        self.files.push(file_info);
+        self.selected.push(selected);
+        self.files_found += 1;
+
+        // A new part can replace the row shown for its model
+        if is_shard || self.view != ListView::Files || self.within_dir.is_some() {
+            let highlighted = self.highlighted_file().map(|file| file.path.clone());
+            self.update_visible(highlighted);
+        } else if shown {
+            self.visible.push(self.files.len() - 1);
+            if self.visible.len() == 1 {
+                self.list_state.select(Some(0));
+            }
         }
     }
 
-    fn select_all(&mut self) {
-        for selected in self.selected.iter_mut() {
-            *selected = true;
+    // Indices of all parts of the split model the file belongs to, or just
+    // the file itself
+    fn shard_members(&self, i: usize) -> Vec<usize> {
+        match &self.files[i].shard {
+            Some(shard) => (0..self.files.len())
+                .filter(|&j| {
+                    self.files[j]
+                        .shard
+                        .as_ref()
+                        .is_some_and(|other| other.group == shard.group)
+                })
+                .collect(),
+            None => vec![i],
         }
     }
 
-    fn deselect_all(&mut self) {
-        for selected in self.selected.iter_mut() {
-            *selected = false;
+    fn is_collapsed_shard(&self, i: usize) -> bool {
+        self.files[i]
+            .shard
+            .as_ref()
+            .is_some_and(|shard| !self.expanded_shards.contains(&shard.group))
+    }
+
+    fn expand_shards(&mut self, expand: bool) {
+        let Some(i) = self.highlighted_index() else {
+            return;
+        };
+        let Some(shard) = self.files[i].shard.clone() else {
+            return;
+        };
+        if expand {
+            self.expanded_shards.insert(shard.group);
+        } else {
+            self.expanded_shards.remove(&shard.group);
         }
+        let highlighted = self.highlighted_file().map(|file| file.path.clone());
+        self.update_visible(highlighted);
     }
 
-    fn next(&mut self) {
-        let i = match self.list_state.selected() {
-            Some(i) => {
-                if i >= self.files.len().saturating_sub(1) {
-                    0
+    // Parts of a split model are always selected together
+    fn set_selected(&mut self, i: usize, selected: bool) {
+        for j in self.shard_members(i) {
+            self.selected[j] = selected;
+        }
+    }
+
+    // Recomputes the visible rows, keeping the highlight on `highlighted` if
+    // it is still shown and at the same row otherwise
+    fn update_visible(&mut self, highlighted: Option<PathBuf>) {
+        let highlighted_dir = self.highlighted_dir().map(|group| group.dir.clone());
+        let query = FilterQuery::parse(&self.filter);
+
+        // Collapsed split models are represented by their lowest numbered part
+        let mut representatives: HashMap<&Path, (u32, usize)> = HashMap::new();
+        let mut parts: HashMap<PathBuf, (u32, u32)> = HashMap::new();
+        for (i, file) in self.files.iter().enumerate() {
+            if let Some(shard) = &file.shard {
+                let entry = representatives
+                    .entry(&shard.group)
+                    .or_insert((shard.number, i));
+                *entry = (*entry).min((shard.number, i));
+                parts
+                    .entry(shard.group.clone())
+                    .or_insert((0, shard.count))
+                    .0 += 1;
+            }
+        }
+        self.incomplete_shards = parts
+            .into_iter()
+            .filter(|(_, (found, count))| found < count)
+            .map(|(group, (found, _))| (group, found))
+            .collect();
+
+        self.visible = (0..self.files.len())
+            .filter(|&i| query.matches(&self.files[i], self.is_incomplete(&self.files[i])))
+            .filter(|&i| arch_matches(&self.architectures, &self.files[i]))
+            .filter(|&i| match &self.files[i].shard {
+                Some(shard) => {
+                    self.expanded_shards.contains(&shard.group)
+                        || representatives[shard.group.as_path()].1 == i
+                }
+                None => true,
+            })
+            .filter(|&i| {
+                self.within_dir
+                    .as_deref()
+                    .is_none_or(|dir| self.files[i].path.parent() == Some(dir))
+            })
+            .collect();
+        match self.view {
+            ListView::Files => {}
+            ListView::Directories => self.update_dir_groups(),
+            ListView::Tree => self.update_tree(),
+        }
+
+        let row = highlighted
+            .and_then(|path| {
+                if self.view != ListView::Files {
+                    // The deepest row in the tree the file is below
+                    let dir = highlighted_dir.as_ref();
+                    self.dir_groups
+                        .iter()
+                        .position(|group| Some(&group.dir) == dir)
+                        .or_else(|| {
+                            self.dir_groups.iter().rposition(|group| {
+                                group.files.iter().any(|&j| self.files[j].path == path)
+                            })
+                        })
                 } else {
-                    i + 1
+                    self.visible.iter().position(|&i| {
+                        self.shard_members(i)
+                            .into_iter()
+                            .any(|j| self.files[j].path == path)
+                    })
                 }
+            })
+            .or(self.list_state.selected())
+            .map(|row| row.min(self.row_count().saturating_sub(1)));
+        if self.row_count() == 0 {
+            self.list_state.select(None);
+        } else {
+            self.list_state.select(row.or(Some(0)));
+        }
+    }
+
+    // Groups the visible files by parent directory, biggest first unless
+    // sorted otherwise
+    fn update_dir_groups(&mut self) {
+        let mut by_dir: HashMap<&Path, Vec<usize>> = HashMap::new();
+        for &i in &self.visible {
+            let dir = self.files[i].path.parent().unwrap_or(Path::new(""));
+            let files = by_dir.entry(dir).or_default();
+            if self.is_collapsed_shard(i) {
+                files.extend(self.shard_members(i));
+            } else {
+                files.push(i);
             }
-            None => 0,
+        }
+
+        let mut groups: Vec<DirGroup> = by_dir
+            .into_iter()
+            .map(|(dir, files)| self.dir_group(dir, dir.display().to_string(), 0, files))
+            .collect();
+        groups.sort_by(|a, b| self.compare_groups(a, b));
+        self.dir_groups = groups;
+    }
+
+    fn dir_group(&self, dir: &Path, label: String, depth: usize, files: Vec<usize>) -> DirGroup {
+        DirGroup {
+            dir: dir.to_path_buf(),
+            label,
+            depth,
+            has_children: false,
+            size: unique_size(files.iter().map(|&i| &self.files[i])),
+            modified: files.iter().filter_map(|&i| self.files[i].modified).max(),
+            files,
+        }
+    }
+
+    fn compare_groups(&self, a: &DirGroup, b: &DirGroup) -> std::cmp::Ordering {
+        let key = self.sort_key.unwrap_or(SortKey::Size);
+        let ordering = match key {
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Name | SortKey::Path | SortKey::Quantization => a.dir.cmp(&b.dir),
         };
-        self.list_state.select(Some(i));
+        let ordering = if self.sort_key.is_none() || self.sort_descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering.then_with(|| a.dir.cmp(&b.dir))
     }
 
-    fn previous(&mut self) {
-        let i = match self.list_state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.files.len().saturating_sub(1)
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
+    // Flattens the directories below the roots into the rows of the tree
+    // view, down to the expanded ones
+    fn update_tree(&mut self) {
+        // Every directory between the files and their root, with all of the
+        // files below it
+        let mut below: HashMap<&Path, Vec<usize>> = HashMap::new();
+        for &i in &self.visible {
+            let members = if self.is_collapsed_shard(i) {
+                self.shard_members(i)
+            } else {
+                vec![i]
+            };
+            for j in members {
+                let file = &self.files[j];
+                let mut dir = file.path.parent();
+                while let Some(parent) = dir {
+                    below.entry(parent).or_default().push(j);
+                    if parent == file.root {
+                        break;
+                    }
+                    dir = parent.parent();
+                }
+            }
+        }
+        let mut children: HashMap<&Path, Vec<&Path>> = HashMap::new();
+        let mut tops = Vec::new();
+        for &dir in below.keys() {
+            match dir.parent().filter(|parent| below.contains_key(parent)) {
+                Some(parent) => children.entry(parent).or_default().push(dir),
+                None => tops.push(dir),
+            }
+        }
+
+        let mut groups: HashMap<&Path, DirGroup> = below
+            .iter()
+            .map(|(&dir, files)| (dir, self.dir_group(dir, String::new(), 0, files.clone())))
+            .collect();
+
+        let mut rows = Vec::new();
+        let mut stack: Vec<(&Path, Option<&Path>, usize)> = self
+            .stack_order(&tops, &groups)
+            .into_iter()
+            .map(|dir| (dir, None, 0))
+            .collect();
+        while let Some((start, parent, depth)) = stack.pop() {
+            // A directory with nothing but another one in it is shown as one
+            let mut dir = start;
+            while let [only] = children.get(dir).map(Vec::as_slice).unwrap_or_default() {
+                if below[dir]
+                    .iter()
+                    .any(|&i| self.files[i].path.parent() == Some(dir))
+                {
+                    break;
+                }
+                dir = only;
+            }
+            let dir_children = children.get(dir).map(Vec::as_slice).unwrap_or_default();
+            if self.expanded_dirs.contains(dir) || parent.is_none() && tops.len() == 1 {
+                stack.extend(
+                    self.stack_order(dir_children, &groups)
+                        .into_iter()
+                        .map(|child| (child, Some(dir), depth + 1)),
+                );
+            }
+            let Some(mut group) = groups.remove(dir) else {
+                continue;
+            };
+            let label = match parent {
+                Some(parent) => dir.strip_prefix(parent).unwrap_or(dir),
+                None => dir,
+            };
+            group.label = label.display().to_string();
+            group.depth = depth;
+            group.has_children = !dir_children.is_empty();
+            rows.push(group);
+        }
+        self.dir_groups = rows;
+    }
+
+    // Directories in the order to push them on the stack of the tree walk,
+    // the first row to show last
+    fn stack_order<'a>(
+        &self,
+        dirs: &[&'a Path],
+        groups: &HashMap<&Path, DirGroup>,
+    ) -> Vec<&'a Path> {
+        let mut dirs = dirs.to_vec();
+        dirs.sort_by(|a, b| self.compare_groups(&groups[b], &groups[a]));
+        dirs
+    }
+
+    // Rows of the list, directories in the directory and tree views and
+    // files otherwise
+    fn row_count(&self) -> usize {
+        match self.view {
+            ListView::Files => self.visible.len(),
+            ListView::Directories | ListView::Tree => self.dir_groups.len(),
+        }
+    }
+
+    fn highlighted_dir(&self) -> Option<&DirGroup> {
+        if self.view == ListView::Files {
+            return None;
+        }
+        self.list_state
+            .selected()
+            .and_then(|row| self.dir_groups.get(row))
+    }
+
+    // Switches to the view, or back to the file list when it is shown
+    // already, staying on the directory of the highlighted file
+    fn toggle_view(&mut self, view: ListView) {
+        let highlighted = match (&self.within_dir, self.highlighted_file()) {
+            (Some(dir), _) if self.view == ListView::Files => Some(dir.clone()),
+            (_, file) => file.map(|file| file.path.clone()),
+        };
+        self.visual = None;
+        self.view = if self.view == view {
+            ListView::Files
+        } else {
+            view
+        };
+        self.within_dir = None;
+        self.update_visible(highlighted.clone());
+        // Coming back from an opened directory, highlight it again
+        if let Some(row) =
+            highlighted.and_then(|dir| self.dir_groups.iter().position(|group| group.dir == dir))
+        {
+            self.list_state.select(Some(row));
+        }
+    }
+
+    // Lists the files of the highlighted directory
+    fn open_dir(&mut self) {
+        let Some(dir) = self.highlighted_dir().map(|group| group.dir.clone()) else {
+            return;
+        };
+        self.view = ListView::Files;
+        self.within_dir = Some(dir);
+        self.list_state.select(Some(0));
+        self.update_visible(None);
+    }
+
+    // Whether the directories in the one at the row are shown below it
+    fn dir_is_open(&self, row: usize) -> bool {
+        match (self.dir_groups.get(row), self.dir_groups.get(row + 1)) {
+            (Some(group), Some(next)) => next.depth > group.depth,
+            _ => false,
+        }
+    }
+
+    // Shows the directories in the highlighted one, or moves into them when
+    // they are shown already
+    fn expand_dir(&mut self) {
+        let (Some(row), Some(group)) = (self.list_state.selected(), self.highlighted_dir()) else {
+            return;
+        };
+        if !group.has_children {
+            return;
+        }
+        if self.dir_is_open(row) {
+            self.next();
+        } else {
+            self.expanded_dirs.insert(group.dir.clone());
+            self.update_visible(None);
+        }
+    }
+
+    // Hides what is in the highlighted directory, or moves up to the one it
+    // is in
+    fn collapse_dir(&mut self) {
+        let (Some(row), Some(group)) = (self.list_state.selected(), self.highlighted_dir()) else {
+            return;
+        };
+        let (dir, depth) = (group.dir.clone(), group.depth);
+        // The only root stays open, there would be nothing else to see
+        if self.dir_is_open(row) && self.expanded_dirs.remove(&dir) {
+            self.update_visible(None);
+        } else if let Some(parent) = self.dir_groups[..row]
+            .iter()
+            .rposition(|group| group.depth < depth)
+        {
+            self.list_state.select(Some(parent));
+        }
+    }
+
+    fn push_filter_char(&mut self, c: char) {
+        let highlighted = self.highlighted_file().map(|file| file.path.clone());
+        self.filter.push(c);
+        self.update_visible(highlighted);
+    }
+
+    fn pop_filter_char(&mut self) {
+        let highlighted = self.highlighted_file().map(|file| file.path.clone());
+        self.filter.pop();
+        self.update_visible(highlighted);
+    }
+
+    fn clear_filter(&mut self) {
+        let highlighted = self.highlighted_file().map(|file| file.path.clone());
+        self.filter.clear();
+        self.update_visible(highlighted);
+    }
+
+    fn cycle_sort_key(&mut self) {
+        let key = self.sort_key.map_or(SortKey::Size, SortKey::next);
+        self.sort_key = Some(key);
+        self.sort_descending = key.default_descending();
+        self.sort_files();
+    }
+
+    fn flip_sort_direction(&mut self) {
+        if self.sort_key.is_some() {
+            self.sort_descending = !self.sort_descending;
+            self.sort_files();
+        }
+    }
+
+    // Keeps the highlight on the same file while the list is reordered
+    fn sort_files(&mut self) {
+        let Some(key) = self.sort_key else {
+            return;
+        };
+        let highlighted = self.highlighted_file().map(|file| file.path.clone());
+
+        let mut entries: Vec<(FileInfo, bool)> =
+            self.files.drain(..).zip(self.selected.drain(..)).collect();
+        entries.sort_by(|(a, _), (b, _)| {
+            let ordering = key.compare(a, b);
+            if self.sort_descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        (self.files, self.selected) = entries.into_iter().unzip();
+
+        self.update_visible(highlighted);
+    }
+
+    // Popups that scroll open at their top
+    fn open_scrolling_popup(&mut self, popup: Popup) {
+        self.popup_scroll = 0;
+        self.popup = Some(popup);
+    }
+
+    fn chat_template(&self) -> Option<&str> {
+        self.highlighted_file()?
+            .header
+            .as_ref()?
+            .get("tokenizer.chat_template")?
+            .as_str()
+    }
+
+    fn show_chat_template(&mut self) {
+        if self.chat_template().is_some() {
+            self.open_scrolling_popup(Popup::ChatTemplate);
+        } else if self.highlighted_file().is_some() {
+            self.status = Some("The file has no chat template".to_string());
+        }
+    }
+
+    fn show_tensors(&mut self) {
+        let Some(file) = self.highlighted_file() else {
+            return;
+        };
+        if file.header.is_none() {
+            self.status = Some("Only GGUF files list their tensors".to_string());
+            return;
+        }
+        match read_tensors(&file.path) {
+            Ok(tensors) => {
+                self.tensors = tensors;
+                self.open_scrolling_popup(Popup::Tensors);
+            }
+            Err(e) => self.status = Some(format!("Cannot read the tensors: {}", e)),
+        }
+    }
+
+    fn show_diff(&mut self) {
+        let files: Vec<&FileInfo> = self.selected_files().collect();
+        if files.len() != 2 {
+            self.status = Some("Select two files to compare".to_string());
+        } else if files.iter().any(|file| file.header.is_none()) {
+            self.status = Some("Only GGUF files can be compared".to_string());
+        } else {
+            self.diff = files.iter().map(|file| file.path.clone()).collect();
+            self.open_scrolling_popup(Popup::Diff);
+        }
+    }
+
+    fn diff_files(&self) -> Option<(&FileInfo, &FileInfo)> {
+        let [a, b] = &self.diff[..] else {
+            return None;
+        };
+        let file = |path: &PathBuf| self.files.iter().find(|file| file.path == *path);
+        Some((file(a)?, file(b)?))
+    }
+
+    // Scrolls no further than the last line at the top
+    fn scroll_popup(&mut self, lines: u16) {
+        let last = match self.popup {
+            Some(Popup::Errors) => self.errors.len(),
+            Some(Popup::Selection) => self.selected_files().count(),
+            Some(Popup::ChatTemplate) => self
+                .chat_template()
+                .map_or(0, |template| template.lines().count()),
+            // Below the column names
+            Some(Popup::Tensors) => self.tensors.len() + 1,
+            Some(Popup::Diff) => self
+                .diff_files()
+                .map_or(0, |(a, b)| diff_rows(a, b).len() + 1),
+            Some(Popup::Quantizations) => self.quantization_sizes().len() + 1,
+            _ => Action::ALL.len(),
+        };
+        self.popup_scroll = (self.popup_scroll + lines).min(last.saturating_sub(1) as u16);
+    }
+
+    // Deleting trashes the files unless `--permanent` is given
+    fn action_description(&self, action: Action) -> &'static str {
+        match (action, self.delete_mode) {
+            (Action::Delete, DeleteMode::Trash) => "Trash Selected",
+            (Action::Delete, DeleteMode::Permanent) => "Delete Selected",
+            (Action::Delete, DeleteMode::Quarantine) => "Quarantine Selected",
+            (action, _) => action.description(),
+        }
+    }
+
+    // Shows or hides the column highlighted in the column menu, a column
+    // shown again goes back to its usual place
+    fn toggle_column(&mut self) {
+        let column = Column::ALL[self.column_cursor];
+        if let Some(i) = self.columns.iter().position(|&shown| shown == column) {
+            self.columns.remove(i);
+            return;
+        }
+        let rank = |column: &Column| Column::ALL.iter().position(|other| other == column);
+        let i = self
+            .columns
+            .iter()
+            .position(|shown| rank(shown) > rank(&column))
+            .unwrap_or(self.columns.len());
+        self.columns.insert(i, column);
+    }
+
+    // Architectures of the files found and how many files each has, along
+    // with those asked for that none has
+    fn found_architectures(&self) -> Vec<(String, usize)> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for arch in &self.architectures {
+            counts.insert(arch.to_lowercase(), 0);
+        }
+        for file in &self.files {
+            if let Some(arch) = file.header.as_ref().and_then(GgufHeader::architecture) {
+                *counts.entry(arch.to_lowercase()).or_default() += 1;
+            }
+        }
+        counts.into_iter().collect()
+    }
+
+    fn toggle_architecture(&mut self) {
+        let Some((arch, _)) = self.found_architectures().into_iter().nth(self.arch_cursor) else {
+            return;
+        };
+        let before = self.architectures.len();
+        self.architectures
+            .retain(|shown| !shown.eq_ignore_ascii_case(&arch));
+        if self.architectures.len() == before {
+            self.architectures.push(arch);
+        }
+        let highlighted = self.highlighted_file().map(|file| file.path.clone());
+        self.update_visible(highlighted);
+    }
+
+    // The header row of the list starting at column `x` of the screen, and
+    // where the titles of sortable columns ended up
+    fn list_header(&self, x: u16) -> (String, Vec<(Range<u16>, SortKey)>) {
+        let mut header = " ".repeat(4);
+        let mut cells = Vec::new();
+        let mut add = |header: &mut String, text: String, key: Option<SortKey>| {
+            let start = x + header.chars().count() as u16;
+            let end = start + text.chars().count() as u16;
+            if let Some(key) = key {
+                cells.push((start..end, key));
+            }
+            header.push_str(&text);
+        };
+        let arrow = |key: SortKey| match self.sort_key {
+            Some(sorted) if sorted == key => {
+                if self.sort_descending {
+                    "↓"
+                } else {
+                    "↑"
+                }
+            }
+            _ => "",
+        };
+
+        if self.view != ListView::Files {
+            let usage = if self.view == ListView::Tree {
+                format!("{:<width$} | ", "Usage", width = USAGE_BAR_LEN + 5)
+            } else {
+                String::new()
+            };
+            for (column, key) in [
+                (Column::Size, SortKey::Size),
+                (Column::Date, SortKey::Modified),
+            ] {
+                let title = format!("{}{}", column.header(), arrow(key));
+                add(&mut header, pad_cell(column, &title), Some(key));
+                header.push_str(" | ");
+            }
+            header.push_str(&format!("{:>8} | {}", "Files", usage));
+            add(
+                &mut header,
+                format!("Directory{}", arrow(SortKey::Path)),
+                Some(SortKey::Path),
+            );
+            return (header, cells);
+        }
+
+        for &column in &self.columns {
+            let key = match column {
+                Column::Size => Some(SortKey::Size),
+                Column::Date => Some(SortKey::Modified),
+                Column::Quantization => Some(SortKey::Quantization),
+                Column::Path => continue,
+                _ => None,
+            };
+            let title = format!("{}{}", column.header(), key.map_or("", arrow));
+            add(&mut header, pad_cell(column, &title), key);
+            header.push_str(" | ");
+        }
+        let (title, key) = if self.columns.contains(&Column::Path) {
+            ("Path", SortKey::Path)
+        } else {
+            ("Name", SortKey::Name)
+        };
+        add(&mut header, format!("{}{}", title, arrow(key)), Some(key));
+        (header, cells)
+    }
+
+    // Sorts by the column clicked, a second click flips the direction
+    fn sort_by(&mut self, key: SortKey) {
+        if self.sort_key == Some(key) {
+            self.flip_sort_direction();
+        } else {
+            self.sort_key = Some(key);
+            self.sort_descending = key.default_descending();
+            self.sort_files();
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.move_highlight(MOUSE_SCROLL_ROWS),
+            MouseEventKind::ScrollUp => self.move_highlight(-MOUSE_SCROLL_ROWS),
+            MouseEventKind::Down(MouseButton::Left) if mouse.row == self.header_row => {
+                if let Some((_, key)) = self
+                    .header_cells
+                    .iter()
+                    .find(|(cells, _)| cells.contains(&mouse.column))
+                {
+                    self.sort_by(*key);
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left)
+                if self
+                    .rows_area
+                    .contains(Position::new(mouse.column, mouse.row)) =>
+            {
+                let row = self.list_state.offset() + (mouse.row - self.rows_area.y) as usize;
+                if row < self.row_count() {
+                    self.list_state.select(Some(row));
+                    // The `[ ]` in front of the row
+                    if mouse.column < self.rows_area.x + 3 {
+                        self.toggle_selected();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn list_title(&self) -> String {
+        let mut title = match &self.within_dir {
+            _ if self.view == ListView::Directories => {
+                format!("Directories ({})", self.dir_groups.len())
+            }
+            _ if self.view == ListView::Tree => "Disk usage".to_string(),
+            Some(dir) => format!("Files in {}", dir.display()),
+            None => "Files".to_string(),
+        };
+        if let Some(key) = self.sort_key {
+            title.push_str(&format!(
+                " (sorted by {} {})",
+                key.label(),
+                if self.sort_descending { "↓" } else { "↑" }
+            ));
+        }
+        if !self.architectures.is_empty() {
+            title.push_str(&format!(" | Arch: {}", self.architectures.join(", ")));
+        }
+        if !self.filter.is_empty() {
+            title.push_str(&format!(" | Filter: {}", self.filter));
+        }
+        if !self.filter.is_empty() || !self.architectures.is_empty() {
+            title.push_str(&format!(
+                " ({} of {})",
+                self.visible.len(),
+                self.files.len()
+            ));
+        }
+        title
+    }
+
+    fn toggle_selected(&mut self) {
+        if let Some(group) = self.highlighted_dir() {
+            // Everything in the directory, unless it all is already
+            let select = !group.files.iter().all(|&i| self.selected[i]);
+            for i in group.files.clone() {
+                self.set_selected(i, select);
+            }
+            return;
+        }
+        if let Some(i) = self.highlighted_index() {
+            self.set_selected(i, !self.selected[i]);
+        }
+    }
+
+    // Bulk selection only touches the rows matching the current filter
+    fn select_all(&mut self) {
+        for i in self.visible.clone() {
+            self.set_selected(i, true);
+        }
+    }
+
+    fn deselect_all(&mut self) {
+        for i in self.visible.clone() {
+            self.set_selected(i, false);
+        }
+    }
+
+    // Every file flips, also those hidden by the filter: filtering to the
+    // models to keep, selecting them and inverting leaves the rest selected
+    fn invert_selection(&mut self) {
+        for selected in &mut self.selected {
+            *selected = !*selected;
+        }
+        let visible: HashSet<usize> = self
+            .visible
+            .iter()
+            .flat_map(|&i| self.shard_members(i))
+            .collect();
+        let hidden = (0..self.files.len())
+            .filter(|i| self.selected[*i] && !visible.contains(i))
+            .count();
+        let count = self.selected_files().count();
+        self.status = Some(if hidden > 0 {
+            format!("Selected {} files, {} hidden by the filter", count, hidden)
+        } else {
+            format!("Selected {} files", count)
+        });
+    }
+
+    // `select <regex>` and `deselect <regex>` mark every file whose path,
+    // model name or quantization matches, also those hidden by the filter
+    fn run_command(&mut self) {
+        let command = std::mem::take(&mut self.command);
+        let (name, argument) = command
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((command.trim(), ""));
+        let select = match name {
+            "select" | "s" => true,
+            "deselect" | "d" => false,
+            "" => return,
+            _ => {
+                self.status = Some(format!(
+                    "Unknown command :{}, expected :select or :deselect",
+                    name
+                ));
+                return;
+            }
+        };
+        let argument = argument.trim();
+        if argument.is_empty() {
+            self.status = Some(format!("Usage: :{} <regex>", name));
+            return;
+        }
+        let regex = match Regex::new(argument) {
+            Ok(regex) => regex,
+            // The parse error points at the spot over several lines
+            Err(e) => {
+                let e = e.to_string();
+                let reason = e.lines().last().unwrap_or_default().trim();
+                self.status = Some(format!("Invalid regex {}, {}", argument, reason));
+                return;
+            }
+        };
+
+        let matching: Vec<usize> = (0..self.files.len())
+            .filter(|&i| {
+                let file = &self.files[i];
+                let header = file.header.as_ref();
+                regex.is_match(&file.path.to_string_lossy())
+                    || header
+                        .and_then(|header| header.get("general.name"))
+                        .and_then(MetadataValue::as_str)
+                        .is_some_and(|name| regex.is_match(name))
+                    || header
+                        .and_then(GgufHeader::quantization)
+                        .is_some_and(|quant| regex.is_match(quant))
+            })
+            .collect();
+        for &i in &matching {
+            self.set_selected(i, select);
+        }
+        self.status = Some(format!(
+            "{} {} files matching {}",
+            if select { "Selected" } else { "Deselected" },
+            matching.len(),
+            argument
+        ));
+    }
+
+    fn next(&mut self) {
+        let i = match self.list_state.selected() {
+            Some(i) => {
+                if i >= self.row_count().saturating_sub(1) {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.list_state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        let i = match self.list_state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    self.row_count().saturating_sub(1)
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.list_state.select(Some(i));
+    }
+
+    // Moves the highlight by `delta` rows without wrapping around
+    fn move_highlight(&mut self, delta: isize) {
+        let Some(last) = self.row_count().checked_sub(1) else {
+            return;
+        };
+        let row = self.list_state.selected().unwrap_or(0);
+        self.list_state
+            .select(Some(row.saturating_add_signed(delta).min(last)));
+    }
+
+    // Moving by a page keeps one row of the previous page in view
+    fn page(&self) -> isize {
+        self.list_height.saturating_sub(1).max(1) as isize
+    }
+
+    fn half_page(&self) -> isize {
+        (self.list_height / 2).max(1) as isize
+    }
+
+    fn toggle_visual(&mut self) {
+        if self.visual.take().is_none() && self.view == ListView::Files {
+            if let Some(i) = self.highlighted_index() {
+                let selected = self
+                    .selected_files()
+                    .map(|file| file.path.clone())
+                    .collect();
+                self.visual = Some((self.files[i].path.clone(), selected));
+                self.apply_visual();
+            }
+        }
+    }
+
+    // Shift with a move starts a range on the highlighted file unless one is
+    // going already
+    fn extend_range(&mut self, up: bool) {
+        if self.visual.is_none() {
+            self.toggle_visual();
+            self.shift_range = self.visual.is_some();
+        }
+        if up {
+            self.previous();
+        } else {
+            self.next();
+        }
+    }
+
+    // Selects the rows between the start of the visual range and the
+    // highlight, rows the range no longer covers go back to how they were
+    fn apply_visual(&mut self) {
+        let Some((anchor, base)) = &self.visual else {
+            return;
+        };
+        let (Some(start), Some(end)) = (
+            self.visible
+                .iter()
+                .position(|&i| &self.files[i].path == anchor),
+            self.list_state.selected(),
+        ) else {
+            return;
+        };
+        for (selected, file) in self.selected.iter_mut().zip(&self.files) {
+            *selected = base.contains(&file.path);
+        }
+        let range = start.min(end)..=start.max(end).min(self.visible.len() - 1);
+        for row in range {
+            self.set_selected(self.visible[row], true);
+        }
+    }
+
+    fn add_duplicate_group(&mut self, paths: Vec<PathBuf>) {
+        let group = self.duplicate_groups;
+        self.duplicate_groups += 1;
+        for file in self.files.iter_mut() {
+            if paths.contains(&file.path) {
+                file.duplicate_group = Some(group);
+            }
+        }
+        self.prune_duplicate_groups();
+    }
+
+    // Unmarks files whose copies are gone from the list
+    fn prune_duplicate_groups(&mut self) {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for group in self.files.iter().filter_map(|file| file.duplicate_group) {
+            *counts.entry(group).or_default() += 1;
+        }
+        for file in self.files.iter_mut() {
+            if file.duplicate_group.is_some_and(|group| counts[&group] < 2) {
+                file.duplicate_group = None;
+            }
+        }
+    }
+
+    fn duplicate_count(&self) -> usize {
+        self.files
+            .iter()
+            .filter_map(|file| file.duplicate_group)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    // Keeps the first file of each duplicate group in list order and
+    // selects all other copies
+    fn select_duplicate_copies(&mut self) {
+        let mut kept = HashSet::new();
+        let mut count = 0;
+        for i in 0..self.files.len() {
+            if let Some(group) = self.files[i].duplicate_group {
+                if !kept.insert(group) {
+                    self.set_selected(i, true);
+                    count += 1;
+                }
+            }
+        }
+        self.status = Some(format!("Selected {} duplicate copies", count));
+    }
+
+    // Copies are identical files and GGUFs of the same model and quant, the
+    // most recently modified one of each stays unselected. Split models
+    // count as one file, symlinks and extra hard links aren't copies.
+    fn select_older_copies(&mut self) {
+        let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut seen_shards = HashSet::new();
+        let mut seen_ids = HashSet::new();
+        for (i, file) in self.files.iter().enumerate() {
+            if file.symlink || file.file_id.is_some_and(|id| !seen_ids.insert(id)) {
+                continue;
+            }
+            if let Some(shard) = &file.shard {
+                if !seen_shards.insert(&shard.group) {
+                    continue;
+                }
+            }
+            let model = file.header.as_ref().and_then(|header| {
+                let name = header.get("general.name").and_then(MetadataValue::as_str)?;
+                Some((name.to_lowercase(), header.quantization()?))
+            });
+            let key = match (file.duplicate_group, model) {
+                (Some(group), _) => format!("copy {}", group),
+                (None, Some((name, quant))) => format!("model {} {}", name, quant),
+                (None, None) => continue,
+            };
+            groups.entry(key).or_default().push(i);
+        }
+
+        let mut group_count = 0;
+        let mut count = 0;
+        for files in groups.into_values().filter(|files| files.len() > 1) {
+            let newest = files
+                .iter()
+                .copied()
+                .max_by_key(|&i| self.files[i].modified)
+                .expect("groups have files");
+            for i in files.into_iter().filter(|&i| i != newest) {
+                self.set_selected(i, true);
+                count += 1;
+            }
+            group_count += 1;
+        }
+        self.status = Some(format!(
+            "Kept the newest of {} groups, selected {} older copies",
+            group_count, count
+        ));
+    }
+
+    // Files that can't be loaded anyway, the safest ones to delete
+    fn select_corrupt(&mut self) {
+        let mut count = 0;
+        for i in 0..self.files.len() {
+            let file = &self.files[i];
+            if file.corrupt.is_some() || is_legacy(file) || self.is_incomplete(file) {
+                self.set_selected(i, true);
+                count += 1;
+            }
+        }
+        self.status = Some(format!(
+            "Selected {} corrupt, legacy or incomplete files",
+            count
+        ));
+    }
+
+    // Part of a split model some parts of which weren't found. Parts still
+    // coming in while scanning don't count as missing.
+    fn is_incomplete(&self, file: &FileInfo) -> bool {
+        !self.scanning
+            && file
+                .shard
+                .as_ref()
+                .is_some_and(|shard| self.incomplete_shards.contains_key(&shard.group))
+    }
+
+    // Tells why nothing can be done in read-only mode
+    fn refuse_read_only(&mut self, what: &str) -> bool {
+        if self.read_only {
+            self.status = Some(format!("Read-only, {} is disabled", what));
+        }
+        self.read_only
+    }
+
+    // Objects can be deleted, everything else works on local files
+    fn refuse_object_storage(&mut self, what: &str) -> bool {
+        if self.object_storage {
+            self.status = Some(format!("Object storage, {} is disabled", what));
+        }
+        self.object_storage
+    }
+
+    fn request_delete(&mut self) {
+        if self.refuse_read_only("deleting") {
+            return;
+        }
+        if self.move_job.is_some() {
+            self.status = Some("Wait for the move to finish".to_string());
+        } else if self.delete_job.is_some() {
+            self.status = Some("A delete is already running".to_string());
+        } else if self.selected.contains(&true) {
+            let paths: Vec<PathBuf> = self
+                .selected_files()
+                .map(|file| file.path.clone())
+                .collect();
+            self.in_use = file_users(&paths).into_iter().collect();
+            self.in_use.sort_by(|a, b| a.0.cmp(&b.0));
+            self.popup = Some(Popup::ConfirmDelete);
+        } else {
+            self.status = Some("No files selected".to_string());
+        }
+    }
+
+    // What a delete, move or revision removal would have done, the files
+    // stay where they are and are deselected
+    fn rehearse(&mut self, action: &str, paths: Vec<(PathBuf, u64)>, size: u64, target: &str) {
+        for (path, file_size) in &paths {
+            self.dry_run_log.push(format!(
+                "Would {} {} ({}){}",
+                action,
+                path.display(),
+                format_size(*file_size),
+                target
+            ));
+        }
+        for i in 0..self.files.len() {
+            if paths.iter().any(|(path, _)| *path == self.files[i].path) {
+                self.selected[i] = false;
+            }
+        }
+        self.dry_run_size += size;
+        self.status = Some(format!(
+            "Dry run: would {} {} files{}, {} ({} this session)",
+            action,
+            paths.len(),
+            target,
+            format_size(size),
+            format_size(self.dry_run_size)
+        ));
+    }
+
+    // Files are removed one after another on a background thread, those
+    // that can't be stay in the list and selected
+    fn delete_selected(&mut self) {
+        if self.dry_run {
+            let paths = self
+                .selected_files()
+                .map(|file| (file.path.clone(), file.size))
+                .collect();
+            let action = match self.delete_mode {
+                DeleteMode::Trash => "trash",
+                DeleteMode::Permanent => "delete",
+                DeleteMode::Quarantine => "quarantine",
+            };
+            self.rehearse(action, paths, self.get_selected_size(), "");
+            return;
+        }
+        let paths: Vec<PathBuf> = self
+            .selected_files()
+            .map(|file| file.path.clone())
+            .collect();
+        let (tx, updates) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancelled = cancel.clone();
+        let (mode, quarantine_dir) = (self.delete_mode, self.quarantine_dir.clone());
+        let to_remove = paths.clone();
+        thread::spawn(move || {
+            for path in to_remove {
+                if cancelled.load(Ordering::Relaxed) {
+                    break;
+                }
+                let update = match remove_path(mode, &quarantine_dir, &path, path.is_dir()) {
+                    Ok(()) => DeleteUpdate::Removed(path),
+                    Err(e) => DeleteUpdate::Failed(path, e),
+                };
+                if tx.send(update).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.delete_job = Some(DeleteJob {
+            updates,
+            cancel,
+            files: paths.len(),
+            removed: HashSet::new(),
+            failed: 0,
+        });
+        self.update_delete_status();
+    }
+
+    // Stops after the file being removed, what is gone stays gone
+    fn cancel_delete(&mut self) {
+        if let Some(job) = &self.delete_job {
+            job.cancel.store(true, Ordering::Relaxed);
+            self.status = Some("Cancelling the delete…".to_string());
+        }
+    }
+
+    // Applies the progress of the running delete, once it is done the
+    // removed files leave the list
+    fn poll_delete(&mut self) {
+        let Some(job) = &mut self.delete_job else {
+            return;
+        };
+        loop {
+            match job.updates.try_recv() {
+                Ok(DeleteUpdate::Removed(path)) => {
+                    job.removed.insert(path);
+                }
+                Ok(DeleteUpdate::Failed(path, e)) => {
+                    job.failed += 1;
+                    self.errors
+                        .push(format!("Error deleting {}: {}", path.display(), e));
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    if !job.cancel.load(Ordering::Relaxed) {
+                        self.update_delete_status();
+                    }
+                    return;
+                }
+                Err(mpsc::TryRecvError::Disconnected) => break,
+            }
+        }
+
+        let Some(job) = self.delete_job.take() else {
+            return;
+        };
+        let removed = self.retain_files(|file| !job.removed.contains(&file.path));
+        let size = reclaimable_size(removed.iter());
+        self.reclaimed += size;
+        self.removed_files += removed.len();
+
+        let mut done = match self.delete_mode {
+            DeleteMode::Trash => format!("Moved {} files to the trash", removed.len()),
+            DeleteMode::Permanent => format!("Deleted {} files", removed.len()),
+            DeleteMode::Quarantine => format!(
+                "Moved {} files to {}",
+                removed.len(),
+                self.quarantine_dir.display()
+            ),
+        };
+        done.push_str(&format!(
+            " ({}, {} this session)",
+            format_size(size),
+            format_size(self.reclaimed)
+        ));
+        if self.delete_mode != DeleteMode::Permanent && !removed.is_empty() {
+            done.push_str(&format!(
+                ", {} to undo",
+                self.keymap.keys_label(Action::Undo)
+            ));
+            self.undo_stack.push(UndoEntry {
+                paths: removed.iter().map(|file| file.path.clone()).collect(),
+                quarantined: self.delete_mode == DeleteMode::Quarantine,
+                size,
+                files: removed,
+            });
+        }
+        let skipped = job.files - job.removed.len() - job.failed;
+        if skipped > 0 {
+            done.push_str(&format!(", cancelled before {} files", skipped));
+        }
+        if job.failed > 0 {
+            done.push_str(&format!(", {} failed, E for details", job.failed));
+        }
+        self.status = Some(done);
+    }
+
+    fn update_delete_status(&mut self) {
+        let Some(job) = &self.delete_job else {
+            return;
+        };
+        let verb = match self.delete_mode {
+            DeleteMode::Trash => "Moving to the trash",
+            DeleteMode::Permanent => "Deleting",
+            DeleteMode::Quarantine => "Quarantining",
+        };
+        self.status = Some(format!(
+            "{} {} of {} files… {}",
+            verb,
+            (job.removed.len() + job.failed + 1).min(job.files),
+            job.files,
+            self.keymap
+                .keys_label(Action::CancelScan)
+                .split('/')
+                .next()
+                .map(|key| format!("{} to cancel", key))
+                .unwrap_or_default()
+        ));
+    }
+
+    fn remove(&self, path: &Path, is_dir: bool) -> io::Result<()> {
+        remove_path(self.delete_mode, &self.quarantine_dir, path, is_dir)
+    }
+
+    // Removes the files not to keep from the list and returns them
+    fn retain_files(&mut self, keep: impl Fn(&FileInfo) -> bool) -> Vec<FileInfo> {
+        let highlighted = self.highlighted_file().map(|file| file.path.clone());
+        let mut removed = Vec::new();
+        let mut i = 0;
+        while i < self.files.len() {
+            if keep(&self.files[i]) {
+                i += 1;
+            } else {
+                removed.push(self.files.remove(i));
+                self.selected.remove(i);
+            }
+        }
+        // A range can't go on from a file that is gone
+        if let Some((anchor, _)) = &self.visual {
+            if removed.iter().any(|file| &file.path == anchor) {
+                self.visual = None;
+                self.shift_range = false;
+            }
+        }
+        self.prune_duplicate_groups();
+        self.update_visible(highlighted);
+        removed
+    }
+
+    // Restores the files of the last delete from the trash or quarantine
+    fn undo_delete(&mut self) {
+        if self.refuse_read_only("restoring") {
+            return;
+        }
+        let Some(entry) = self.undo_stack.pop() else {
+            self.status = Some("Nothing to undo".to_string());
+            return;
+        };
+        let result = if entry.quarantined {
+            entry
+                .paths
+                .iter()
+                .try_for_each(|path| restore_quarantined(path, &self.quarantine_dir))
+        } else {
+            restore_from_trash(&entry.paths)
+        };
+        if let Err(e) = result {
+            self.status = Some(format!("Undo failed: {}", e));
+            return;
+        }
+
+        let count = entry.files.len();
+        self.reclaimed = self.reclaimed.saturating_sub(entry.size);
+        self.removed_files = self.removed_files.saturating_sub(count);
+        for file in entry.files {
+            self.add_file(file);
+        }
+        self.prune_duplicate_groups();
+        self.sort_files();
+        self.status = Some(format!(
+            "Restored {} files from the {}",
+            count,
+            if entry.quarantined {
+                "quarantine"
+            } else {
+                "trash"
+            }
+        ));
+    }
+
+    // Offers removing the stale hub cache revision the highlighted file
+    // belongs to
+    fn request_revision_removal(&mut self) {
+        if self.refuse_read_only("removing revisions") {
+            return;
+        }
+        let Some(ModelSource::HuggingFace {
+            repo,
+            repo_dir,
+            revisions,
+        }) = self
+            .highlighted_file()
+            .and_then(|file| file.source.as_ref())
+        else {
+            self.status = Some("Not a Hugging Face cache file".to_string());
+            return;
+        };
+        let Some(revision) = revisions.iter().find(|revision| revision.is_stale()) else {
+            self.status = Some(format!("No stale revision of {} to remove", repo));
+            return;
+        };
+
+        self.pending_revision = Some(RevisionRemoval::plan(repo, repo_dir, &revision.id));
+        self.popup = Some(Popup::ConfirmRevision);
+    }
+
+    fn remove_revision(&mut self) {
+        let Some(removal) = self.pending_revision.take() else {
+            return;
+        };
+        if self.dry_run {
+            let mut paths: Vec<(PathBuf, u64)> = removal
+                .blobs
+                .iter()
+                .map(|blob| {
+                    (
+                        blob.clone(),
+                        fs::metadata(blob).map_or(0, |metadata| metadata.len()),
+                    )
+                })
+                .collect();
+            paths.push((removal.snapshot_dir.clone(), 0));
+            self.rehearse("remove", paths, removal.size, "");
+            return;
+        }
+
+        let result = removal
+            .blobs
+            .iter()
+            .try_for_each(|blob| self.remove(blob, false))
+            .and_then(|()| self.remove(&removal.snapshot_dir, true));
+        let removed = self.retain_files(|file| {
+            file.path.exists()
+                && !removal.blobs.contains(&file.path)
+                && !file.path.starts_with(&removal.snapshot_dir)
+        });
+        let removed_count = removed.len();
+        if result.is_ok() && self.delete_mode != DeleteMode::Permanent {
+            let mut paths = removal.blobs.clone();
+            paths.push(removal.snapshot_dir.clone());
+            self.undo_stack.push(UndoEntry {
+                paths,
+                quarantined: self.delete_mode == DeleteMode::Quarantine,
+                size: removal.size,
+                files: removed,
+            });
+        }
+
+        self.status = Some(match result {
+            Ok(()) => {
+                self.reclaimed += removal.size;
+                self.removed_files += removed_count;
+                format!(
+                    "Removed revision {} of {}, freed {} ({} this session)",
+                    removal.revision,
+                    removal.repo,
+                    format_size(removal.size),
+                    format_size(self.reclaimed)
+                )
+            }
+            Err(e) => {
+                let error = format!("Failed to remove revision {}: {}", removal.revision, e);
+                self.errors.push(error.clone());
+                error
+            }
+        });
+    }
+
+    // Copies the absolute paths of the selected files, or of the highlighted
+    // one when nothing is selected, one per line
+    fn copy_paths(&mut self) {
+        let paths: Vec<String> = if self.selected.contains(&true) {
+            self.selected_files()
+                .map(|file| absolute_path(&file.path))
+                .collect()
+        } else {
+            self.highlighted_file()
+                .map(|file| absolute_path(&file.path))
+                .into_iter()
+                .collect()
+        };
+        if paths.is_empty() {
+            self.status = Some("Nothing to copy".to_string());
+            return;
+        }
+
+        let clipboard = match self.clipboard.take() {
+            Some(clipboard) => Ok(clipboard),
+            None => arboard::Clipboard::new(),
+        };
+        let result = clipboard
+            .and_then(|clipboard| self.clipboard.insert(clipboard).set_text(paths.join("\n")));
+        self.status = Some(match (result, paths.as_slice()) {
+            (Ok(()), [path]) => format!("Copied {} to the clipboard", path),
+            (Ok(()), paths) => format!("Copied {} paths to the clipboard", paths.len()),
+            (Err(e), _) => format!("Cannot copy to the clipboard: {}", e),
+        });
+    }
+
+    // Shows the folder of the highlighted file, or the highlighted directory,
+    // in the file manager
+    fn open_folder(&mut self) {
+        let path = match (self.highlighted_dir(), self.highlighted_file()) {
+            (Some(group), _) => group.dir.clone(),
+            (None, Some(file)) => file.path.clone(),
+            (None, None) => return,
+        };
+        self.status = Some(match reveal_in_file_manager(&path) {
+            Ok(()) => format!("Opened {}", path.display()),
+            Err(e) => format!("Cannot open {}: {}", path.display(), e),
+        });
+    }
+
+    fn request_move(&mut self) {
+        if self.refuse_read_only("moving") || self.refuse_object_storage("moving") {
+            return;
+        }
+        if self.move_job.is_some() {
+            self.status = Some("A move is already running".to_string());
+        } else if self.delete_job.is_some() {
+            self.status = Some("Wait for the delete to finish".to_string());
+        } else if self.selected.contains(&true) {
+            self.popup = Some(Popup::MoveDestination);
+        } else {
+            self.status = Some("No files selected".to_string());
+        }
+    }
+
+    fn request_rename(&mut self) {
+        if self.refuse_read_only("renaming") || self.refuse_object_storage("renaming") {
+            return;
+        }
+        let Some(file) = self.highlighted_file() else {
+            return;
+        };
+        if file.shard.is_some() {
+            self.status = Some("Renaming one part would break the split model".to_string());
+            return;
+        }
+        let Some(name) = file.path.file_name() else {
+            return;
+        };
+        self.new_name = name.to_string_lossy().into_owned();
+        self.popup = Some(Popup::Rename);
+    }
+
+    // Renames within the directory the file is in
+    fn rename_highlighted(&mut self) {
+        let Some(file) = self.highlighted_file() else {
+            return;
+        };
+        let path = file.path.clone();
+        let size = file.size;
+        let name = self.new_name.trim();
+        if name.is_empty() || name.contains(std::path::is_separator) {
+            self.status = Some(format!("Invalid file name {}", name));
+            return;
+        }
+        let target = path.with_file_name(name);
+        if target == path {
+            return;
+        }
+        if target.exists() {
+            self.status = Some(format!("{} already exists", target.display()));
+            return;
+        }
+        if self.dry_run {
+            let target = format!(" to {}", name);
+            self.rehearse("rename", vec![(path, size)], 0, &target);
+            return;
+        }
+        if let Err(e) = fs::rename(&path, &target) {
+            self.status = Some(format!("Cannot rename {}: {}", path.display(), e));
+            return;
+        }
+        if let Some(file) = self.files.iter_mut().find(|file| file.path == path) {
+            file.path = target.clone();
+        }
+        self.status = Some(format!("Renamed to {}", target.display()));
+        self.update_visible(Some(target));
+    }
+
+    // Previews the names the selected files get from their metadata,
+    // nothing is renamed until confirmed
+    fn request_normalize_names(&mut self) {
+        if self.refuse_read_only("renaming") || self.refuse_object_storage("renaming") {
+            return;
+        }
+        if !self.selected.contains(&true) {
+            self.status = Some("No files selected".to_string());
+            return;
+        }
+        let renames = planned_renames(&self.name_template, self.selected_files());
+        if renames.is_empty() {
+            self.status = Some("The selected files are named that way already".to_string());
+            return;
+        }
+        self.pending_renames = renames;
+        self.popup = Some(Popup::ConfirmRenames);
+    }
+
+    fn normalize_names(&mut self) {
+        let renames: Vec<(PathBuf, PathBuf)> = std::mem::take(&mut self.pending_renames)
+            .into_iter()
+            .filter_map(|(path, target)| Some((path, target.ok()?)))
+            .collect();
+        if self.dry_run {
+            for (path, target) in &renames {
+                let size = self
+                    .files
+                    .iter()
+                    .find(|file| file.path == *path)
+                    .map_or(0, |file| file.size);
+                let target = format!(" to {}", target.display());
+                self.rehearse("rename", vec![(path.clone(), size)], 0, &target);
+            }
+            self.status = Some(format!("Dry run: would rename {} files", renames.len()));
+            return;
+        }
+        let highlighted = self.highlighted_file().map(|file| file.path.clone());
+        let mut renamed = 0;
+        for (path, target) in &renames {
+            if let Err(e) = fs::rename(path, target) {
+                self.errors
+                    .push(format!("Error renaming {}: {}", path.display(), e));
+                continue;
+            }
+            renamed += 1;
+            if let Some(file) = self.files.iter_mut().find(|file| file.path == *path) {
+                file.path = target.clone();
+            }
+        }
+        self.status = Some(format!("Renamed {} of {} files", renamed, renames.len()));
+        let highlighted = highlighted.map(|highlighted| {
+            renames
+                .iter()
+                .find(|(path, target)| *path == highlighted && target.exists())
+                .map_or(highlighted, |(_, target)| target.clone())
+        });
+        self.update_visible(highlighted);
+    }
+
+    fn start_move(&mut self) {
+        let destination = expand_home(self.move_destination.trim());
+        if self.dry_run {
+            let paths = self
+                .selected_files()
+                .map(|file| (file.path.clone(), file.size))
+                .collect();
+            let target = format!(" to {}", destination.display());
+            self.rehearse("move", paths, 0, &target);
+            return;
+        }
+        if let Err(e) = fs::create_dir_all(&destination) {
+            self.status = Some(format!("Cannot move to {}: {}", destination.display(), e));
+            return;
+        }
+
+        let files: Vec<(PathBuf, u64)> = self
+            .selected_files()
+            .map(|file| (file.path.clone(), file.size))
+            .collect();
+        let (tx, updates) = mpsc::channel();
+        let dest_dir = destination.clone();
+        let to_move = files.clone();
+        thread::spawn(move || {
+            for (path, size) in to_move {
+                let result = move_file(&path, &dest_dir, |copied| {
+                    tx.send(MoveUpdate::Copied(copied)).ok();
+                });
+                let update = match result {
+                    Ok(_) => MoveUpdate::Moved(path, size),
+                    Err(e) => MoveUpdate::Failed(path, size, e),
+                };
+                if tx.send(update).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.move_job = Some(MoveJob {
+            destination,
+            updates,
+            files: files.len(),
+            total_size: files.iter().map(|(_, size)| size).sum(),
+            done_size: 0,
+            copied: 0,
+            moved: Vec::new(),
+            errors: Vec::new(),
+        });
+        self.update_move_status();
+    }
+
+    // Applies the progress of the running move, moved files leave the list
+    fn poll_move(&mut self) {
+        let Some(job) = &mut self.move_job else {
+            return;
+        };
+        loop {
+            match job.updates.try_recv() {
+                Ok(MoveUpdate::Copied(copied)) => job.copied = copied,
+                Ok(MoveUpdate::Moved(path, size)) => {
+                    job.done_size += size;
+                    job.copied = 0;
+                    job.moved.push(path);
+                }
+                Ok(MoveUpdate::Failed(path, size, e)) => {
+                    job.done_size += size;
+                    job.copied = 0;
+                    job.errors.push(format!("{}: {}", path.display(), e));
+                    self.errors
+                        .push(format!("Error moving {}: {}", path.display(), e));
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    self.update_move_status();
+                    return;
+                }
+                Err(mpsc::TryRecvError::Disconnected) => break,
+            }
+        }
+
+        let Some(job) = self.move_job.take() else {
+            return;
+        };
+        self.retain_files(|file| !job.moved.contains(&file.path));
+        self.status = Some(match job.errors.first() {
+            None => format!(
+                "Moved {} files to {}",
+                job.moved.len(),
+                job.destination.display()
+            ),
+            Some(error) => format!(
+                "Moved {} files, {} failed ({})",
+                job.moved.len(),
+                job.errors.len(),
+                error
+            ),
+        });
+    }
+
+    fn update_move_status(&mut self) {
+        let Some(job) = &self.move_job else {
+            return;
+        };
+        let done = job.done_size + job.copied;
+        let percent = (done * 100).checked_div(job.total_size).unwrap_or(100);
+        self.status = Some(format!(
+            "Moving {} of {} files to {}… {}% ({} of {})",
+            (job.moved.len() + job.errors.len() + 1).min(job.files),
+            job.files,
+            job.destination.display(),
+            percent,
+            format_size(done),
+            format_size(job.total_size)
+        ));
+    }
+
+    // Compresses the selected models, or restores the selected archives,
+    // one after another on a background thread
+    fn request_archive(&mut self, restore: bool) {
+        if self.refuse_read_only(if restore { "restoring" } else { "archiving" })
+            || self.refuse_object_storage(if restore { "restoring" } else { "archiving" })
+        {
+            return;
+        }
+        if self.archive_job.is_some() {
+            self.status = Some("Already archiving files".to_string());
+            return;
+        }
+        if self.move_job.is_some() || self.delete_job.is_some() {
+            self.status = Some("Wait for the move or delete to finish".to_string());
+            return;
+        }
+        let files: Vec<(PathBuf, u64)> = self
+            .selected_files()
+            .filter(|file| {
+                (file.format == ModelFormat::Zstd) == restore && !file.format.is_bundle()
+            })
+            .map(|file| (file.path.clone(), file.size))
+            .collect();
+        if self.archive_dir.as_os_str().is_empty() {
+            self.status =
+                Some("No archive directory found, set archive_dir in the config".to_string());
+            return;
+        }
+        if files.is_empty() {
+            self.status = Some(if restore {
+                "No archives selected".to_string()
+            } else {
+                "No files selected".to_string()
+            });
+            return;
+        }
+        if self.dry_run {
+            let size = files.iter().map(|(_, size)| size).sum();
+            if restore {
+                self.rehearse("restore", files, 0, "");
+            } else {
+                let target = format!(" to {}", self.archive_dir.display());
+                self.rehearse("archive", files, size, &target);
+            }
+            return;
+        }
+
+        let (tx, updates) = mpsc::channel();
+        let archive_dir = self.archive_dir.clone();
+        let paths: Vec<PathBuf> = files.iter().map(|(path, _)| path.clone()).collect();
+        thread::spawn(move || {
+            for path in paths {
+                let result = if restore {
+                    restore_archive(&path, &archive_dir)
+                } else {
+                    archive_file(&path, &archive_dir)
+                };
+                let update = match result {
+                    Ok(dest) => ArchiveUpdate::Done(path, dest),
+                    Err(e) => ArchiveUpdate::Failed(path, e),
+                };
+                if tx.send(update).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.archive_job = Some(ArchiveJob {
+            restoring: restore,
+            updates,
+            files: files.len(),
+            size_before: 0,
+            size_after: 0,
+            done: Vec::new(),
+            errors: Vec::new(),
+        });
+        self.update_archive_status();
+    }
+
+    // Archived and restored files leave the list, the next scan finds them
+    // under their new name
+    fn poll_archive(&mut self) {
+        let Some(job) = &mut self.archive_job else {
+            return;
+        };
+        loop {
+            match job.updates.try_recv() {
+                Ok(ArchiveUpdate::Done(path, dest)) => {
+                    job.size_before += self
+                        .files
+                        .iter()
+                        .find(|file| file.path == path)
+                        .map_or(0, |file| file.size);
+                    job.size_after += fs::metadata(&dest).map_or(0, |metadata| metadata.len());
+                    job.done.push(path);
+                }
+                Ok(ArchiveUpdate::Failed(path, e)) => {
+                    job.errors.push(format!("{}: {}", path.display(), e));
+                    let action = if job.restoring {
+                        "restoring"
+                    } else {
+                        "archiving"
+                    };
+                    self.errors
+                        .push(format!("Error {} {}: {}", action, path.display(), e));
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    self.update_archive_status();
+                    return;
+                }
+                Err(mpsc::TryRecvError::Disconnected) => break,
+            }
+        }
+
+        let Some(job) = self.archive_job.take() else {
+            return;
+        };
+        self.retain_files(|file| !job.done.contains(&file.path));
+        let done = if job.restoring {
+            format!("Restored {} files", job.done.len())
+        } else {
+            self.reclaimed += job.size_before.saturating_sub(job.size_after);
+            format!(
+                "Archived {} files to {}, freed {}",
+                job.done.len(),
+                self.archive_dir.display(),
+                format_size(job.size_before.saturating_sub(job.size_after))
+            )
+        };
+        self.status = Some(match job.errors.first() {
+            None => done,
+            Some(error) => format!("{}, {} failed ({})", done, job.errors.len(), error),
+        });
+    }
+
+    fn update_archive_status(&mut self) {
+        let Some(job) = &self.archive_job else {
+            return;
+        };
+        self.status = Some(format!(
+            "{} {} of {} files…",
+            if job.restoring {
+                "Restoring"
+            } else {
+                "Archiving"
+            },
+            (job.done.len() + job.errors.len() + 1).min(job.files),
+            job.files
+        ));
+    }
+
+    // Merges the highlighted split model next to its parts with
+    // `gguf-split --merge`, the parts are removed the way deleting does once
+    // the merged file checks out
+    fn request_merge(&mut self) {
+        if self.refuse_read_only("merging") || self.refuse_object_storage("merging") {
+            return;
+        }
+        if self.merge_job.is_some() {
+            self.status = Some("A merge is already running".to_string());
+            return;
+        }
+        let Some(i) = self.highlighted_index() else {
+            return;
+        };
+        let Some(shard) = self.files[i].shard.clone() else {
+            self.status = Some("Not a split model".to_string());
+            return;
+        };
+        if self.is_incomplete(&self.files[i]) {
+            self.status = Some("Parts of the model are missing".to_string());
+            return;
+        }
+        let mut parts: Vec<&FileInfo> = self
+            .shard_members(i)
+            .into_iter()
+            .map(|j| &self.files[j])
+            .collect();
+        parts.sort_by_key(|file| file.shard.as_ref().map(|shard| shard.number));
+        let Some(output) = merged_path(&shard.group) else {
+            return;
+        };
+        if output.symlink_metadata().is_ok() {
+            self.status = Some(format!("{} already exists", output.display()));
+            return;
+        }
+        let tensors: u64 = parts
+            .iter()
+            .filter_map(|file| file.header.as_ref())
+            .map(|header| header.tensor_count)
+            .sum();
+        let paths: Vec<(PathBuf, u64)> = parts
+            .iter()
+            .map(|file| (file.path.clone(), file.size))
+            .collect();
+        if self.dry_run {
+            let target = format!(" into {}", output.display());
+            self.rehearse("merge", paths, 0, &target);
+            return;
+        }
+
+        let (tx, updates) = mpsc::channel();
+        let parts: Vec<PathBuf> = paths.iter().map(|(path, _)| path.clone()).collect();
+        let merge = Merge {
+            program: self.gguf_split.clone(),
+            parts: parts.clone(),
+            output: output.clone(),
+            tensors,
+            delete_mode: self.delete_mode,
+            quarantine_dir: self.quarantine_dir.clone(),
+            scan_options: self.scan_options.clone(),
+        };
+        thread::spawn(move || {
+            tx.send(merge.run()).ok();
+        });
+        self.merge_job = Some(MergeJob {
+            output,
+            parts,
+            total_size: paths.iter().map(|(_, size)| size).sum(),
+            updates,
+        });
+        self.update_merge_status();
+    }
+
+    // The merged file replaces its parts in the list
+    fn poll_merge(&mut self) {
+        let Some(job) = &self.merge_job else {
+            return;
+        };
+        let result = match job.updates.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => {
+                self.update_merge_status();
+                return;
+            }
+            Err(mpsc::TryRecvError::Disconnected) => Err("merging stopped".to_string()),
+        };
+        let Some(job) = self.merge_job.take() else {
+            return;
+        };
+        match result {
+            Ok(result) => {
+                self.retain_files(|file| !job.parts.contains(&file.path));
+                let failed = result.errors.len();
+                self.errors.extend(result.errors);
+                if let Some(file) = result.file {
+                    self.add_file(file);
+                }
+                self.status = Some(if failed > 0 {
+                    format!(
+                        "Merged into {}, {} parts not removed, E for details",
+                        job.output.display(),
+                        failed
+                    )
+                } else {
+                    format!(
+                        "Merged {} parts into {}",
+                        job.parts.len(),
+                        job.output.display()
+                    )
+                });
+            }
+            Err(e) => {
+                self.errors.push(format!(
+                    "Error merging into {}: {}",
+                    job.output.display(),
+                    e
+                ));
+                self.status = Some(format!("Merge failed: {}", e));
+            }
+        }
+    }
+
+    fn update_merge_status(&mut self) {
+        let Some(job) = &self.merge_job else {
+            return;
+        };
+        let written = fs::metadata(&job.output).map_or(0, |metadata| metadata.len());
+        let percent = (written.min(job.total_size) * 100)
+            .checked_div(job.total_size)
+            .unwrap_or(100);
+        self.status = Some(format!(
+            "Merging {} parts into {}… {}% ({} of {})",
+            job.parts.len(),
+            job.output.display(),
+            percent,
+            format_size(written),
+            format_size(job.total_size)
+        ));
+    }
+
+    // Hashes the selected files, or the highlighted one, and looks them up
+    // in the repos they may have come from
+    fn start_verify(&mut self) {
+        if self.verify_job.is_some() {
+            self.status = Some("Already verifying files".to_string());
+            return;
+        }
+        let files: Vec<&FileInfo> = if self.selected.contains(&true) {
+            self.selected_files().collect()
+        } else {
+            self.highlighted_file().into_iter().collect()
+        };
+        if files.is_empty() {
+            self.status = Some("Nothing to verify".to_string());
+            return;
+        }
+        let files: Vec<_> = files
+            .into_iter()
+            .map(|file| (file.path.clone(), file.size, hf_candidates(file)))
+            .collect();
+
+        let (tx, updates) = mpsc::channel();
+        let count = files.len();
+        thread::spawn(move || {
+            for (path, size, candidates) in files {
+                tx.send(VerifyUpdate::Started(size)).ok();
+                let result = verify_file(&path, &candidates, |hashed| {
+                    tx.send(VerifyUpdate::Hashed(hashed)).ok();
+                });
+                if tx.send(VerifyUpdate::Done(path, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        self.verify_job = Some(VerifyJob {
+            updates,
+            files: count,
+            done: 0,
+            checked: Vec::new(),
+            hashed: 0,
+            size: 0,
+        });
+        self.update_verify_status();
+    }
+
+    // The details pane shows the progress and then the checksums
+    fn start_checksum(&mut self) {
+        if let Some(job) = &self.checksum_job {
+            self.status = Some(format!("Already hashing {}", job.path.display()));
+            return;
+        }
+        let Some(file) = self.highlighted_file() else {
+            return;
+        };
+        if file.format.is_bundle() {
+            self.status = Some(format!("{} models are directories", file.format.label()));
+            return;
+        }
+        let known = self.checksums.get(file).is_some();
+        let (path, size) = (file.path.clone(), file.size);
+        self.show_details = true;
+        if known {
+            self.status = Some("Checksums are in the details pane".to_string());
+            return;
+        }
+        let (tx, updates) = mpsc::channel();
+        let to_hash = path.clone();
+        thread::spawn(move || {
+            let result = checksum_file(&to_hash, |hashed| {
+                tx.send(ChecksumUpdate::Hashed(hashed)).ok();
+            });
+            tx.send(ChecksumUpdate::Done(result)).ok();
+        });
+        self.checksum_job = Some(ChecksumJob {
+            path,
+            size,
+            hashed: 0,
+            updates,
+        });
+    }
+
+    fn poll_checksum(&mut self) {
+        let Some(job) = &mut self.checksum_job else {
+            return;
+        };
+        let result = loop {
+            match job.updates.try_recv() {
+                Ok(ChecksumUpdate::Hashed(hashed)) => job.hashed = hashed,
+                Ok(ChecksumUpdate::Done(result)) => break result,
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    break Err(io::Error::other("hashing stopped"))
+                }
+            }
+        };
+        let Some(job) = self.checksum_job.take() else {
+            return;
+        };
+        let checksums = match result {
+            Ok(checksums) => checksums,
+            Err(e) => {
+                self.status = Some(format!("Cannot hash {}: {}", job.path.display(), e));
+                return;
+            }
+        };
+        // The file may have left the list in the meantime
+        let Some(file) = self.files.iter().find(|file| file.path == job.path) else {
+            return;
+        };
+        if let Err(e) = self.checksums.insert(file, checksums) {
+            self.errors
+                .push(format!("Error caching the checksums: {}", e));
+        }
+        self.status = Some(format!("Hashed {}", job.path.display()));
+    }
+
+    fn poll_verify(&mut self) {
+        let Some(job) = &mut self.verify_job else {
+            return;
+        };
+        loop {
+            match job.updates.try_recv() {
+                Ok(VerifyUpdate::Started(size)) => {
+                    job.hashed = 0;
+                    job.size = size;
+                }
+                Ok(VerifyUpdate::Hashed(hashed)) => job.hashed = hashed,
+                Ok(VerifyUpdate::Done(path, result)) => {
+                    job.done += 1;
+                    match result {
+                        Ok(verification) => {
+                            job.checked.push(path.clone());
+                            self.verifications.insert(path, verification);
+                        }
+                        Err(e) => {
+                            self.errors
+                                .push(format!("Error verifying {}: {}", path.display(), e))
+                        }
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    self.update_verify_status();
+                    return;
+                }
+                Err(mpsc::TryRecvError::Disconnected) => break,
+            }
+        }
+
+        let Some(job) = self.verify_job.take() else {
+            return;
+        };
+        let results: Vec<&Verification> = job
+            .checked
+            .iter()
+            .filter_map(|path| self.verifications.get(path))
+            .collect();
+        let failed = job.files - results.len();
+        self.status = Some(match (job.checked.as_slice(), results.as_slice()) {
+            ([path], [verification]) if job.files == 1 => {
+                format!("{}: {}", path.display(), verification.label())
+            }
+            _ => {
+                let count = |matches: fn(&Verification) -> bool| {
+                    results.iter().filter(|v| matches(v)).count()
+                };
+                let mut status = format!(
+                    "Verified {} files: {} match Hugging Face, {} modified, {} unknown",
+                    results.len(),
+                    count(|v| matches!(v, Verification::Verified { .. })),
+                    count(|v| matches!(v, Verification::Modified { .. })),
+                    count(|v| *v == Verification::Unknown)
+                );
+                if failed > 0 {
+                    status.push_str(&format!(", {} failed, E for details", failed));
+                }
+                status
+            }
+        });
+    }
+
+    fn update_verify_status(&mut self) {
+        let Some(job) = &self.verify_job else {
+            return;
+        };
+        let percent = (job.hashed * 100).checked_div(job.size).unwrap_or(100);
+        self.status = Some(format!(
+            "Verifying {} of {} files against Hugging Face… {}%",
+            (job.done + 1).min(job.files),
+            job.files,
+            percent
+        ));
+    }
+
+    fn record_scan(&mut self) {
+        let Some(inventory) = &self.inventory else {
+            return;
+        };
+        match record_scan(inventory, &self.roots, &self.files) {
+            Ok(scan) => self.status = Some(format!("Recorded as scan #{}", scan)),
+            Err(e) => self
+                .errors
+                .push(format!("Error recording the scan: {:#}", e)),
+        }
+    }
+
+    fn notify_done(&mut self) {
+        if !self.notify {
+            return;
+        }
+        let summary = scan_summary(&self.roots, &self.files);
+        if let Err(e) = notify::notify("ggufscan scan done", &summary) {
+            self.errors
+                .push(format!("Error showing the notification: {}", e));
+        }
+    }
+
+    // Writes the current file list into the working directory
+    fn export(&mut self, format: ExportFormat) {
+        let path = PathBuf::from(format!(
+            "ggufscan-export-{}.{}",
+            unix_seconds(SystemTime::now()),
+            format.extension()
+        ));
+        let result = fs::File::create(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                let mut writer = io::BufWriter::new(file);
+                export_files(&mut writer, format, &self.roots, &self.files)?;
+                writer.flush()?;
+                Ok(())
+            });
+        self.status = Some(match result {
+            Ok(()) => format!("Exported {} files to {}", self.files.len(), path.display()),
+            Err(e) => format!("Export failed: {}", e),
+        });
+    }
+
+    // Pausing stops the walk and the duplicate check from touching the disk,
+    // the files found so far can still be worked with
+    fn toggle_pause(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused_for += paused_at.elapsed();
+            self.scan_control.resume();
+            self.status = Some("Scan resumed".to_string());
+        } else if self.scanning || self.checking_duplicates {
+            self.paused_at = Some(Instant::now());
+            self.scan_control.pause();
+            self.status = Some("Scan paused, P to resume".to_string());
+        }
+    }
+
+    fn start_scan(&mut self, options: ScanOptions) -> Receiver<ScanMessage> {
+        self.scan_options = Some(options.clone());
+        self.scanning = true;
+        self.scan_started = Instant::now();
+        if let Some(remote) = &self.remote {
+            let scanner = remote.scanner();
+            self.scan_control = scanner.control();
+            return scanner.spawn();
+        }
+        let (control, rx) = spawn_scan(options);
+        self.scan_control = control;
+        rx
+    }
+
+    // Starts over with an empty list, files selected before that are still
+    // there get selected again
+    fn rescan(&mut self) -> Option<Receiver<ScanMessage>> {
+        if self.move_job.is_some() {
+            self.status = Some("Wait for the move to finish before rescanning".to_string());
+            return None;
+        }
+        if self.delete_job.is_some() {
+            self.status = Some("Wait for the delete to finish before rescanning".to_string());
+            return None;
+        }
+        if self.archive_job.is_some() {
+            self.status = Some("Wait for archiving to finish before rescanning".to_string());
+            return None;
+        }
+        let options = self.scan_options.clone()?;
+        self.scan_control.cancel();
+
+        self.reselect = self
+            .files
+            .iter()
+            .zip(&self.selected)
+            .filter(|(_, &selected)| selected)
+            .map(|(file, _)| file.path.clone())
+            .collect();
+        self.files.clear();
+        self.selected.clear();
+        self.visible.clear();
+        self.dir_groups.clear();
+        self.list_state.select(None);
+        self.current_path.clear();
+        self.progress = ScanProgress::default();
+        self.root_progress.clear();
+        self.roots_done.clear();
+        self.scan_cancelled = false;
+        self.paused_at = None;
+        self.paused_for = Duration::ZERO;
+        self.files_found = 0;
+        self.checking_duplicates = false;
+        self.cached_files = 0;
+        self.watching = false;
+        self.duplicate_groups = 0;
+        self.status = Some("Rescanning".to_string());
+        Some(self.start_scan(options))
+    }
+
+    fn cancel_scan(&mut self) {
+        if self.scanning || self.checking_duplicates {
+            self.scan_control.cancel();
+        }
+    }
+
+    // Time spent scanning, pauses left out
+    fn scan_elapsed(&self) -> Duration {
+        let paused = self.paused_for + self.paused_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.scan_started.elapsed().saturating_sub(paused)
+    }
+
+    fn roots_label(&self) -> String {
+        let mut label = self
+            .roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Some(max_depth) = self.max_depth {
+            label.push_str(&format!(" (max depth {})", max_depth));
+        }
+        label
+    }
+
+    fn selected_files(&self) -> impl Iterator<Item = &FileInfo> {
+        self.files
+            .iter()
+            .zip(self.selected.iter())
+            .filter(|(_, &selected)| selected)
+            .map(|(file, _)| file)
+    }
+
+    // The first file of the highlighted directory in the directory views
+    fn highlighted_index(&self) -> Option<usize> {
+        if self.view != ListView::Files {
+            return self
+                .highlighted_dir()
+                .and_then(|group| group.files.first().copied());
+        }
+        self.list_state
+            .selected()
+            .and_then(|row| self.visible.get(row).copied())
+    }
+
+    fn highlighted_file(&self) -> Option<&FileInfo> {
+        self.highlighted_index().map(|i| &self.files[i])
+    }
+
+    fn get_selected_size(&self) -> u64 {
+        reclaimable_size(self.selected_files())
+    }
+
+    // Space taken by all found files and by those of each format, biggest
+    // first, hard links counted once
+    fn format_sizes(&self) -> (u64, Vec<(&'static str, u64)>) {
+        let mut by_format: HashMap<&'static str, Vec<&FileInfo>> = HashMap::new();
+        for file in &self.files {
+            by_format.entry(file.format.label()).or_default().push(file);
+        }
+        let mut sizes: Vec<(&'static str, u64)> = by_format
+            .into_iter()
+            .map(|(format, files)| (format, unique_size(files.into_iter())))
+            .collect();
+        sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        (unique_size(self.files.iter()), sizes)
+    }
+
+    // How many files of each quantization there are and the space they take,
+    // biggest first
+    fn quantization_sizes(&self) -> Vec<(String, usize, u64)> {
+        self.grouped_sizes(|header| header.quantization().map(str::to_string))
+    }
+
+    fn architecture_sizes(&self) -> Vec<(String, usize, u64)> {
+        self.grouped_sizes(|header| header.architecture().map(str::to_lowercase))
+    }
+
+    fn family_sizes(&self) -> Vec<(String, usize, u64)> {
+        self.grouped_sizes(|header| header.family().map(str::to_string))
+    }
+
+    // The files found grouped by something of their header, with the number
+    // of files and the space they take, biggest first. Models without a
+    // header are counted by their format and GGUF files without it as
+    // unknown.
+    fn grouped_sizes(
+        &self,
+        key: impl Fn(&GgufHeader) -> Option<String>,
+    ) -> Vec<(String, usize, u64)> {
+        let mut groups: HashMap<String, Vec<&FileInfo>> = HashMap::new();
+        for file in &self.files {
+            let group = match &file.header {
+                Some(header) => key(header).unwrap_or_else(|| "unknown".to_string()),
+                None => file.format.label().to_string(),
+            };
+            groups.entry(group).or_default().push(file);
+        }
+        let mut sizes: Vec<(String, usize, u64)> = groups
+            .into_iter()
+            .map(|(group, files)| (group, files.len(), unique_size(files.into_iter())))
+            .collect();
+        sizes.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        sizes
+    }
+}
+
+// Object storage is listed by a scanner of its own, local roots are walked
+fn spawn_scan(options: ScanOptions) -> (ScanControl, Receiver<ScanMessage>) {
+    if options.roots.iter().any(|root| is_s3_path(root)) {
+        let scanner = S3Scanner::new(options);
+        return (scanner.control(), scanner.spawn());
+    }
+    let scanner = Scanner::new(options);
+    (scanner.control(), scanner.spawn())
+}
+
+// Local directories and object storage can't be scanned together, and only
+// local directories have to exist up front
+fn check_roots(roots: &[PathBuf]) -> Result<()> {
+    let s3 = roots.iter().filter(|root| is_s3_path(root)).count();
+    if s3 > 0 {
+        anyhow::ensure!(
+            s3 == roots.len(),
+            "s3:// locations can't be scanned along with local directories"
+        );
+        return Ok(());
+    }
+    for root in roots {
+        anyhow::ensure!(root.is_dir(), "{} is not a directory", root.display());
+    }
+    Ok(())
+}
+
+// Inspects one file the way the scan does
+fn scan_file(mut options: ScanOptions, path: &Path) -> Option<FileInfo> {
+    options.files = vec![path.to_owned()];
+    options.cache = None;
+    options.watch = false;
+    options.find_duplicates = false;
+    Scanner::new(options)
+        .spawn()
+        .into_iter()
+        .find_map(|message| match message {
+            ScanMessage::File(file) => Some(*file),
+            _ => None,
+        })
+}
+
+fn remove_path(
+    mode: DeleteMode,
+    quarantine_dir: &Path,
+    path: &Path,
+    is_dir: bool,
+) -> io::Result<()> {
+    if is_s3_path(path) {
+        return delete_object(path);
+    }
+    match mode {
+        DeleteMode::Quarantine => quarantine_file(path, quarantine_dir).map(|_| ()),
+        mode if is_dir => remove_dir(path, mode == DeleteMode::Permanent),
+        mode => remove_file(path, mode == DeleteMode::Permanent),
+    }
+}
+
+// The file manager is left running on its own, with its output kept off the
+// TUI. Files are highlighted in their folder where the platform can do that.
+fn reveal_in_file_manager(path: &Path) -> io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("explorer");
+        if path.is_dir() {
+            command.arg(path);
+        } else {
+            command.arg(format!("/select,{}", path.display()));
+        }
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        if !path.is_dir() {
+            command.arg("-R");
+        }
+        command.arg(path);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(path)
+        });
+        command
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                e.kind(),
+                format!("{} not found", command.get_program().to_string_lossy()),
+            ),
+            _ => e,
+        })
+}
+
+fn absolute_path(path: &Path) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+// Destinations are typed in by hand, so `~` is accepted for the home folder
+fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+// Characters of the bar showing the share of the disk usage in the tree
+const USAGE_BAR_LEN: usize = 10;
+
+// Rows of the directory and tree views, selected when all of their files
+// are and `[-]` when only some
+fn dir_items(app: &App) -> Vec<ListItem<'static>> {
+    let total: u64 = app
+        .dir_groups
+        .iter()
+        .filter(|group| group.depth == 0)
+        .map(|group| group.size)
+        .sum();
+    app.dir_groups
+        .iter()
+        .enumerate()
+        .map(|(index, group)| {
+            let selected = group.files.iter().filter(|&&i| app.selected[i]).count();
+            let checkbox = match selected {
+                0 => "[ ] ",
+                n if n == group.files.len() => "[x] ",
+                _ => "[-] ",
+            };
+            let modified = group
+                .modified
+                .map(format_day)
+                .unwrap_or_else(|| "-".to_string());
+            let mut row = format!(
+                "{}{} | {} | {:>8} | ",
+                checkbox,
+                pad_cell(Column::Size, &format_size(group.size)),
+                pad_cell(Column::Date, &modified),
+                group.files.len()
+            );
+            if app.view == ListView::Tree {
+                // Share of everything listed, the rows at the top add up to it
+                let share = group.size as f64 / total.max(1) as f64;
+                let filled = (share * USAGE_BAR_LEN as f64).round() as usize;
+                row.push_str(&format!(
+                    "{}{} {:>3}% | {}{} ",
+                    "█".repeat(filled),
+                    "░".repeat(USAGE_BAR_LEN - filled),
+                    (share * 100.0).round(),
+                    "  ".repeat(group.depth),
+                    match (group.has_children, app.dir_is_open(index)) {
+                        (true, true) => "▾",
+                        (true, false) => "▸",
+                        (false, _) => " ",
+                    }
+                ));
+            }
+            row.push_str(&group.label);
+            let style = if selected > 0 {
+                Style::default().fg(app.theme.selected)
+            } else {
+                Style::default()
+            };
+            ListItem::new(row).style(style)
+        })
+        .collect()
+}
+
+// Fits the text to the width of the column, long parent directories keep
+// their end
+fn pad_cell(column: Column, text: &str) -> String {
+    let width = column.width();
+    let len = text.chars().count();
+    let text = if len > width {
+        let tail: String = text.chars().skip(len - width + 1).collect();
+        format!("…{}", tail)
+    } else {
+        text.to_string()
+    };
+    if column == Column::Parameters {
+        format!("{:>width$}", text)
+    } else {
+        format!("{:<width$}", text)
+    }
+}
+
+fn source_label(file: &FileInfo) -> String {
+    file.source
+        .as_ref()
+        .map(|source| format!(" [{}]", source.label()))
+        .unwrap_or_default()
+}
+
+fn verification_label(verification: Option<&Verification>) -> &'static str {
+    match verification {
+        Some(Verification::Verified { .. }) => " (verified)",
+        Some(Verification::Modified { .. }) => " (modified)",
+        Some(Verification::Unknown) => " (unknown)",
+        None => "",
+    }
+}
+
+fn link_label(file: &FileInfo) -> String {
+    if file.symlink {
+        " (symlink)".to_string()
+    } else if file.links > 1 {
+        format!(" ({} hard links)", file.links)
+    } else {
+        String::new()
+    }
+}
+
+// UI code and run_app function remain the same...
+fn ui(frame: &mut Frame, app: &mut App) {
+    let gauge_height = if app.scanning { 3 } else { 0 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(gauge_height),
+            Constraint::Min(1),
+            Constraint::Length(3),
+        ])
+        .split(frame.area());
+
+    let mut title = if app.scanning {
+        format!(
+            "Scanning {} | Current: {} | Files found: {}",
+            app.roots_label(),
+            app.current_path,
+            app.files_found
+        )
+    } else {
+        format!(
+            "Scan of {} {} | Found {} model files",
+            app.roots_label(),
+            if app.scan_cancelled {
+                "cancelled"
+            } else {
+                "complete"
+            },
+            app.files.len()
+        )
+    };
+    if !app.files.is_empty() {
+        let (total, sizes) = app.format_sizes();
+        title.push_str(&format!(" | {} total", format_size(total)));
+        if sizes.len() > 1 {
+            let sizes: Vec<String> = sizes
+                .iter()
+                .map(|(format, size)| format!("{} {}", format, format_size(*size)))
+                .collect();
+            title.push_str(&format!(" ({})", sizes.join(", ")));
+        }
+    }
+    let incomplete: Vec<&FileInfo> = app
+        .files
+        .iter()
+        .filter(|file| app.is_incomplete(file))
+        .collect();
+    if !incomplete.is_empty() {
+        title.push_str(&format!(
+            " | {} incomplete split models, {}",
+            app.incomplete_shards.len(),
+            format_size(unique_size(incomplete.iter().copied()))
+        ));
+    }
+    if app.scanning && app.cached_files > 0 {
+        title.push_str(&format!(" | {} from cache, refreshing…", app.cached_files));
+    }
+    if app.paused_at.is_some() {
+        title.push_str(" | Paused");
+    }
+    if app.watching {
+        title.push_str(" | Watching for changes");
+    }
+    if app.checking_duplicates {
+        title.push_str(" | Checking for duplicates…");
+    } else if app.duplicate_count() > 0 {
+        title.push_str(&format!(" | {} duplicate groups", app.duplicate_count()));
+    }
+
+    frame.render_widget(
+        Paragraph::new(title)
+            .block(Block::default().borders(Borders::ALL))
+            .wrap(Wrap { trim: true }),
+        chunks[0],
+    );
+
+    if app.scanning {
+        render_progress(frame, app, chunks[1]);
+    }
+
+    let multiple_roots = app.roots.len() > 1;
+    let items: Vec<ListItem> = if app.view != ListView::Files {
+        dir_items(app)
+    } else {
+        app.visible
+            .iter()
+            .map(|&i| {
+                let file = &app.files[i];
+                let checkbox = if app.selected[i] { "[x] " } else { "[ ] " };
+                let root = if multiple_roots {
+                    format!("[{}] ", file.root.display())
+                } else {
+                    String::new()
+                };
+                let header = file.header.as_ref();
+                let mut size = file.size;
+                let mut size_class = header.and_then(GgufHeader::size_class);
+                let mut modified = file.modified;
+                let full_path = app.columns.contains(&Column::Path);
+                let display_name = |path: &Path| {
+                    if full_path {
+                        path.display().to_string()
+                    } else {
+                        path.file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_else(|| path.display().to_string())
+                    }
+                };
+                let mut name = display_name(&file.path);
+
+                // Collapsed split models show the totals over all their parts
+                if let Some(shard) = &file.shard {
+                    if app.is_collapsed_shard(i) {
+                        let members = app.shard_members(i);
+                        size = members.iter().map(|&j| app.files[j].size).sum();
+                        // Only the first part carries `general.size_label`
+                        let parameters: Option<u64> = members
+                            .iter()
+                            .map(|&j| app.files[j].header.as_ref().map(|h| h.parameter_count))
+                            .sum();
+                        size_class = members
+                            .iter()
+                            .find_map(|&j| {
+                                app.files[j]
+                                    .header
+                                    .as_ref()?
+                                    .get("general.size_label")?
+                                    .as_str()
+                                    .map(str::to_string)
+                            })
+                            .or_else(|| {
+                                parameters.filter(|&count| count > 0).map(format_size_class)
+                            });
+                        modified = members.iter().filter_map(|&j| app.files[j].modified).max();
+                        name = format!(
+                            "▸ {} ({} of {} parts)",
+                            display_name(&shard.group),
+                            members.len(),
+                            shard.count
+                        );
+                    } else {
+                        name = format!("▾ {}", name);
+                    }
+                }
+
+                let mut row = checkbox.to_string();
+                for &column in &app.columns {
+                    let cell = match column {
+                        Column::Size => format_size(size),
+                        Column::Date => modified.map(format_day).unwrap_or_else(|| "-".to_string()),
+                        Column::Format => match header.filter(|_| !file.format.is_packed()) {
+                            Some(header) => format!("{} v{}", file.format.label(), header.version),
+                            None => file.format.label().to_string(),
+                        },
+                        Column::Architecture => header
+                            .and_then(GgufHeader::architecture)
+                            .unwrap_or("-")
+                            .to_string(),
+                        Column::Quantization => header
+                            .and_then(GgufHeader::quantization)
+                            .unwrap_or("-")
+                            .to_string(),
+                        Column::Parameters => size_class.clone().unwrap_or_else(|| "-".to_string()),
+                        Column::ParentDir => file
+                            .path
+                            .parent()
+                            .map(|parent| parent.display().to_string())
+                            .unwrap_or_default(),
+                        Column::Path => continue,
+                    };
+                    row.push_str(&pad_cell(column, &cell));
+                    row.push_str(" | ");
+                }
+                let flagged = file.corrupt.is_some() || is_legacy(file) || app.is_incomplete(file);
+                let warning = if flagged { "⚠ " } else { "" };
+                row.push_str(&format!(
+                    "{}{}{}{}{}{}",
+                    root,
+                    warning,
+                    name,
+                    source_label(file),
+                    link_label(file),
+                    verification_label(app.verifications.get(&file.path))
+                ));
+
+                let color = if app.selected[i] {
+                    Some(app.theme.selected)
+                } else if flagged {
+                    Some(app.theme.warning)
+                } else if file.duplicate_group.is_some() {
+                    Some(app.theme.duplicate)
+                } else if file.readonly {
+                    Some(app.theme.protected)
+                } else {
+                    None
+                };
+                ListItem::new(row)
+                    .style(color.map_or_else(Style::default, |color| Style::default().fg(color)))
+            })
+            .collect()
+    };
+
+    let list = List::new(items).highlight_style(Style::default().bg(app.theme.highlight));
+
+    let list_area = if app.show_details {
+        let columns = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(chunks[2]);
+        render_details(frame, app, columns[1]);
+        columns[0]
+    } else {
+        chunks[2]
+    };
+
+    let block = Block::default()
+        .title(app.list_title())
+        .borders(Borders::ALL);
+    let inner = block.inner(list_area);
+    frame.render_widget(block, list_area);
+    let [header_area, rows_area] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+
+    let (header, header_cells) = app.list_header(header_area.x);
+    frame.render_widget(Paragraph::new(header).bold(), header_area);
+    app.header_cells = header_cells;
+    app.header_row = header_area.y;
+    app.rows_area = rows_area;
+    app.list_height = rows_area.height as usize;
+    frame.render_stateful_widget(list, rows_area, &mut app.list_state);
+
+    let total_selected_size = format_size(app.get_selected_size());
+    let help_text = if app.popup == Some(Popup::Filter) {
+        format!(
+            "Filter: {}█ | size>1G, age>90d | Enter: Apply | Esc: Clear | ↑/↓: Navigate",
+            app.filter
+        )
+    } else if app.popup == Some(Popup::Columns) {
+        "↑/↓: Navigate | Space: Show/Hide Column | Esc/Enter: Close".to_string()
+    } else if app.popup == Some(Popup::Architectures) {
+        "↑/↓: Navigate | Space: Show/Hide Architecture | u: Show All | Esc/Enter: Close".to_string()
+    } else if app.popup == Some(Popup::MoveDestination) {
+        format!(
+            "Move selected to: {}█ | Enter: Move | Esc: Cancel",
+            app.move_destination
+        )
+    } else if app.popup == Some(Popup::Rename) {
+        format!("Rename to: {}█ | Enter: Rename | Esc: Cancel", app.new_name)
+    } else if app.popup == Some(Popup::Command) {
+        format!(
+            ":{}█ | select <regex>, deselect <regex> | Enter: Run | Esc: Cancel",
+            app.command
+        )
+    } else {
+        // Only the keys needed to get going, `?` lists the rest
+        let mut help = format!(
+            "{}{} selected / {} files | Selected size: {}",
+            if app.read_only {
+                "READ ONLY | "
+            } else if app.dry_run {
+                "DRY RUN | "
+            } else {
+                ""
+            },
+            app.selected_files().count(),
+            app.files.len(),
+            total_selected_size
+        );
+        for action in FOOTER_ACTIONS {
+            if app.read_only && action == Action::Delete {
+                continue;
+            }
+            let keys = app.keymap.keys_label(action);
+            if !keys.is_empty() {
+                help.push_str(&format!(" | {}: {}", keys, app.action_description(action)));
+            }
+        }
+        help
+    };
+
+    let mut footer = Block::default()
+        .borders(Borders::ALL)
+        .title(app.status.clone().unwrap_or_default());
+    if !app.errors.is_empty() {
+        let badge = format!(
+            " {} errors, {} ",
+            app.errors.len(),
+            app.keymap.keys_label(Action::Errors)
+        );
+        footer = footer.title(
+            Line::from(badge)
+                .right_aligned()
+                .style(Style::default().fg(app.theme.danger).bold()),
+        );
+    }
+    frame.render_widget(
+        Paragraph::new(help_text)
+            .block(footer)
+            .alignment(Alignment::Center),
+        chunks[3],
+    );
+
+    match app.popup {
+        Some(Popup::ConfirmDelete) => render_confirm_delete(frame, app),
+        Some(Popup::ConfirmRevision) => render_confirm_revision(frame, app),
+        Some(Popup::ConfirmRenames) => render_confirm_renames(frame, app),
+        Some(Popup::Columns) => render_columns(frame, app),
+        Some(Popup::Architectures) => render_architectures(frame, app),
+        Some(Popup::Help) => render_help(frame, app),
+        Some(Popup::Errors) => render_errors(frame, app),
+        Some(Popup::Selection) => render_selection(frame, app),
+        Some(Popup::ChatTemplate) => render_chat_template(frame, app),
+        Some(Popup::Tensors) => render_tensors(frame, app),
+        Some(Popup::Diff) => render_diff(frame, app),
+        Some(Popup::Quantizations) => render_quantizations(frame, app),
+        Some(Popup::Families) => render_families(frame, app),
+        _ => {}
+    }
+}
+
+// Shown in the status bar, the help popup has all of them
+const FOOTER_ACTIONS: [Action; 6] = [
+    Action::Toggle,
+    Action::Delete,
+    Action::Filter,
+    Action::Sort,
+    Action::Help,
+    Action::Quit,
+];
+
+// Longest metadata value shown in the details pane, chat templates and the
+// like are cut off
+const MAX_DETAIL_VALUE_LEN: usize = 200;
+
+// Rows the highlight moves per notch of the scroll wheel
+const MOUSE_SCROLL_ROWS: isize = 3;
+
+fn render_details(frame: &mut Frame, app: &App, area: Rect) {
+    let mut lines = Vec::new();
+
+    if let Some(group) = app.highlighted_dir() {
+        lines.push(Line::from(group.dir.display().to_string()).bold());
+        lines.push(Line::from(format!(
+            "{} files, {}",
+            group.files.len(),
+            format_size(group.size)
+        )));
+        lines.push(Line::from(""));
+        for &i in &group.files {
+            let file = &app.files[i];
+            let name = file
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            lines.push(Line::from(format!(
+                "{:<10} {}",
+                format_size(file.size),
+                name
+            )));
+        }
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(Block::default().borders(Borders::ALL).title("Directory"))
+                .wrap(Wrap { trim: false }),
+            area,
+        );
+        return;
+    }
+
+    match app.highlighted_file() {
+        Some(file) => {
+            lines.push(Line::from(file.path.display().to_string()).bold());
+            lines.push(Line::from(format!("Size: {}", format_size(file.size))));
+            if let Some(modified) = file.modified {
+                lines.push(Line::from(format!("Modified: {}", format_date(modified))));
+            }
+            if let Some(accessed) = file.accessed {
+                lines.push(Line::from(format!("Last read: {}", format_date(accessed))));
+            }
+            if file.format == ModelFormat::Ggml {
+                lines.push(
+                    Line::from("Format: GGML (legacy, converted to GGUF in 2023)")
+                        .fg(app.theme.warning),
+                );
+            } else if file.format.is_bundle() {
+                lines.push(Line::from(format!(
+                    "Format: {} directory, size of all files in it",
+                    file.format.label()
+                )));
+            } else {
+                lines.push(Line::from(format!("Format: {}", file.format.label())));
+            }
+            if let Some((biggest, others)) = file.packed.split_first() {
+                lines.push(Line::from(format!("Archived GGUF: {}", biggest)));
+                if !others.is_empty() {
+                    lines.push(Line::from(format!(
+                        "Also in the archive: {}",
+                        others.join(", ")
+                    )));
+                }
+            }
+            if let Some(shard) = &file.shard {
+                lines.push(Line::from(format!(
+                    "Split model: part {} of {}",
+                    shard.number, shard.count
+                )));
+                if app.is_incomplete(file) {
+                    let found: HashSet<u32> = app
+                        .files
+                        .iter()
+                        .filter_map(|other| other.shard.as_ref())
+                        .filter(|other| other.group == shard.group)
+                        .map(|other| other.number)
+                        .collect();
+                    let missing: Vec<String> = (1..=shard.count)
+                        .filter(|number| !found.contains(number))
+                        .map(|number| number.to_string())
+                        .collect();
+                    lines.push(
+                        Line::from(format!(
+                            "Incomplete, missing {} {}",
+                            if missing.len() == 1 { "part" } else { "parts" },
+                            missing.join(", ")
+                        ))
+                        .fg(app.theme.warning),
+                    );
+                }
+            }
+            if let Some(corrupt) = &file.corrupt {
+                lines.push(
+                    Line::from(format!("Corrupt: {}", corrupt))
+                        .style(Style::default().fg(app.theme.warning)),
+                );
+            }
+            if let Some(source) = &file.source {
+                lines.push(Line::from(format!("Source: {}", source.label())));
+            }
+            if let Some(verification) = app.verifications.get(&file.path) {
+                lines.push(Line::from(format!(
+                    "Hugging Face: {}",
+                    verification.label()
+                )));
+            }
+            if let Some(checksums) = app.checksums.get(file) {
+                lines.push(Line::from(format!("BLAKE3: {}", checksums.blake3)));
+                lines.push(Line::from(format!("SHA256: {}", checksums.sha256)));
+            }
+            match &file.header {
+                Some(header) => {
+                    if header.is_legacy() {
+                        lines.push(
+                            Line::from(format!(
+                                "GGUF version: {} (legacy, current llama.cpp can't load it)",
+                                header.version
+                            ))
+                            .fg(app.theme.warning),
+                        );
+                    } else {
+                        lines.push(Line::from(format!("GGUF version: {}", header.version)));
+                    }
+                    lines.push(Line::from(format!("Tensors: {}", header.tensor_count)));
+                    let parameters = match header.size_class() {
+                        Some(class) => {
+                            format!("{} ({})", format_param_count(header.parameter_count), class)
+                        }
+                        None => format_param_count(header.parameter_count),
+                    };
+                    lines.push(Line::from(format!("Parameters: {}", parameters)));
+                    if let Some(context_length) = header.context_length() {
+                        lines.push(Line::from(format!(
+                            "Context length: {} tokens",
+                            context_length
+                        )));
+                    }
+                    match (header.tokenizer(), header.vocab_size()) {
+                        (Some(tokenizer), Some(vocab_size)) => lines.push(Line::from(format!(
+                            "Tokenizer: {}, {} tokens",
+                            tokenizer, vocab_size
+                        ))),
+                        (Some(tokenizer), None) => {
+                            lines.push(Line::from(format!("Tokenizer: {}", tokenizer)))
+                        }
+                        (None, Some(vocab_size)) => {
+                            lines.push(Line::from(format!("Vocabulary: {} tokens", vocab_size)))
+                        }
+                        (None, None) => {}
+                    }
+                    let has_template = header.get("tokenizer.chat_template").is_some();
+                    lines.push(Line::from(format!(
+                        "Chat template: {}",
+                        if has_template { "yes" } else { "no" }
+                    )));
+                    lines.push(Line::from(""));
+                    for (key, value) in &header.metadata {
+                        let mut value = value.to_string().replace('\n', "⏎");
+                        if value.chars().count() > MAX_DETAIL_VALUE_LEN {
+                            value = value.chars().take(MAX_DETAIL_VALUE_LEN).collect();
+                            value.push('…');
+                        }
+                        lines.push(Line::from(vec![
+                            Span::styled(
+                                format!("{}: ", key),
+                                Style::default().fg(app.theme.accent),
+                            ),
+                            Span::raw(value),
+                        ]));
+                    }
+                }
+                None => lines.push(Line::from("No GGUF metadata available")),
+            }
+        }
+        None => lines.push(Line::from("No file highlighted")),
+    }
+
+    // Hashing the highlighted file shows on top of its details
+    let mut area = area;
+    if let Some(job) = app
+        .checksum_job
+        .as_ref()
+        .filter(|job| Some(&job.path) == app.highlighted_file().map(|file| &file.path))
+    {
+        let [gauge_area, rest] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(area);
+        let ratio = (job.hashed as f64 / job.size.max(1) as f64).min(1.0);
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::default().borders(Borders::ALL).title("Checksums"))
+                .gauge_style(Style::default().fg(app.theme.progress))
+                .ratio(ratio)
+                .label(format!(
+                    "{:.0}% | {} of {}",
+                    ratio * 100.0,
+                    format_size(job.hashed),
+                    format_size(job.size)
+                )),
+            gauge_area,
+        );
+        area = rest;
+    }
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title("Details").borders(Borders::ALL)),
+        area,
+    );
+}
+
+fn render_progress(frame: &mut Frame, app: &App, area: Rect) {
+    let elapsed = app.scan_elapsed();
+    let rate = app.progress.files as f64 / elapsed.as_secs_f64().max(1.0);
+    // The walk ends on its own schedule, the estimate never claims it's over
+    let ratio = if app.progress.total > 0 {
+        (app.progress.done as f64 / app.progress.total as f64).min(0.99)
+    } else {
+        0.0
+    };
+    let mut label = format!(
+        "{:.0}% | {:.0} files/s | {} elapsed",
+        ratio * 100.0,
+        rate,
+        format_elapsed(elapsed)
+    );
+    // Drives or directories scanned side by side, each with its own share
+    if app.roots.len() > 1 {
+        let roots: Vec<String> = app
+            .roots
+            .iter()
+            .map(|root| {
+                let state = if app.roots_done.contains(root) {
+                    "done".to_string()
+                } else {
+                    match app.root_progress.get(root) {
+                        Some(progress) if progress.total > 0 => format!(
+                            "{:.0}%",
+                            (progress.done as f64 / progress.total as f64).min(0.99) * 100.0
+                        ),
+                        _ => "…".to_string(),
+                    }
+                };
+                format!("{} {}", root.display(), state)
+            })
+            .collect();
+        label.push_str(&format!(" | {}", roots.join(", ")));
+    }
+    if app.paused_at.is_some() {
+        label.push_str(" | Paused");
+    }
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::default().borders(Borders::ALL))
+            .gauge_style(Style::default().fg(app.theme.progress))
+            .ratio(ratio)
+            .label(label),
+        area,
+    );
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::vertical([Constraint::Percentage(percent_y)])
+        .flex(layout::Flex::Center)
+        .split(area);
+    Layout::horizontal([Constraint::Percentage(percent_x)])
+        .flex(layout::Flex::Center)
+        .split(vertical[0])[0]
+}
+
+fn render_columns(frame: &mut Frame, app: &App) {
+    let area = centered_rect(30, 40, frame.area());
+    let lines: Vec<Line> = Column::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            let checkbox = if app.columns.contains(column) {
+                "[x] "
+            } else {
+                "[ ] "
+            };
+            let line = Line::from(format!("{}{}", checkbox, column.label()));
+            if i == app.column_cursor {
+                line.style(Style::default().bg(app.theme.highlight))
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Columns")),
+        area,
+    );
+}
+
+fn render_architectures(frame: &mut Frame, app: &App) {
+    let area = centered_rect(30, 40, frame.area());
+    let architectures = app.found_architectures();
+    let mut lines: Vec<Line> = architectures
+        .iter()
+        .enumerate()
+        .map(|(i, (arch, count))| {
+            let shown = app
+                .architectures
+                .iter()
+                .any(|shown| shown.eq_ignore_ascii_case(arch));
+            let checkbox = if shown { "[x] " } else { "[ ] " };
+            let line = Line::from(format!("{}{} ({})", checkbox, arch, count));
+            if i == app.arch_cursor {
+                line.style(Style::default().bg(app.theme.highlight))
+            } else {
+                line
+            }
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::from("No GGUF models found yet"));
+    }
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Architectures")
+                .title_bottom(" none checked shows all "),
+        ),
+        area,
+    );
+}
+
+fn render_help(frame: &mut Frame, app: &App) {
+    let area = centered_rect(60, 80, frame.area());
+    let bindings: Vec<(String, Action)> = Action::ALL
+        .iter()
+        .map(|&action| (app.keymap.keys_label(action), action))
+        .filter(|(keys, _)| !keys.is_empty())
+        .collect();
+    let width = bindings
+        .iter()
+        .map(|(keys, _)| keys.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines: Vec<Line> = bindings
+        .iter()
+        .map(|(keys, action)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{:>width$}  ", keys, width = width),
+                    Style::default().fg(app.theme.accent),
+                ),
+                Span::raw(app.action_description(*action)),
+            ])
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(
+        Line::from("↑/↓/PgUp/PgDn: Scroll | Esc/?: Close")
+            .bold()
+            .alignment(Alignment::Center),
+    );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title("Keys"))
+            .scroll((app.popup_scroll, 0)),
+        area,
+    );
+}
+
+fn render_errors(frame: &mut Frame, app: &App) {
+    let area = centered_rect(80, 70, frame.area());
+    let lines: Vec<Line> = if app.errors.is_empty() {
+        vec![Line::from("No errors so far")]
+    } else {
+        app.errors
+            .iter()
+            .map(|error| Line::from(error.as_str()))
+            .collect()
+    };
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Errors ({})", app.errors.len()))
+                    .title_bottom(Line::from("↑/↓/PgUp/PgDn: Scroll | Esc/E: Close").centered()),
+            )
+            .wrap(Wrap { trim: false })
+            .scroll((app.popup_scroll, 0)),
+        area,
+    );
+}
+
+// Every selected file, also those scrolled out of the list or hidden by the
+// filter
+fn render_selection(frame: &mut Frame, app: &App) {
+    let area = centered_rect(80, 70, frame.area());
+    let files: Vec<&FileInfo> = app.selected_files().collect();
+    let lines: Vec<Line> = if files.is_empty() {
+        vec![Line::from("Nothing selected")]
+    } else {
+        files
+            .iter()
+            .map(|file| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>10}  ", format_size(file.size)),
+                        Style::default().fg(app.theme.accent),
+                    ),
+                    Span::raw(file.path.display().to_string()),
+                ])
+            })
+            .collect()
+    };
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        "Selected ({} files, {})",
+                        files.len(),
+                        format_size(app.get_selected_size())
+                    ))
+                    .title_bottom(
+                        Line::from(format!(
+                            "↑/↓/PgUp/PgDn: Scroll | Esc/{}: Close",
+                            app.keymap.keys_label(Action::Selection)
+                        ))
+                        .centered(),
+                    ),
+            )
+            .scroll((app.popup_scroll, 0)),
+        area,
+    );
+}
+
+// One row per tensor with its shape, type and size, the layout llama.cpp
+// prints when loading a model
+fn render_tensors(frame: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, frame.area());
+    let shapes: Vec<String> = app
+        .tensors
+        .iter()
+        .map(|tensor| {
+            let dims: Vec<String> = tensor.shape.iter().map(u64::to_string).collect();
+            dims.join(" × ")
+        })
+        .collect();
+    let name_width = app
+        .tensors
+        .iter()
+        .map(|tensor| tensor.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Name".len());
+    let shape_width = shapes
+        .iter()
+        .map(|shape| shape.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Shape".len());
+    let row = |name: &str, shape: &str, ggml_type: &str, size: &str| {
+        format!(
+            "{:<name_width$}  {:<shape_width$}  {:<8}  {:>10}",
+            name, shape, ggml_type, size
+        )
+    };
+    let mut lines = vec![Line::styled(
+        row("Name", "Shape", "Type", "Size"),
+        Style::default()
+            .fg(app.theme.accent)
+            .add_modifier(Modifier::BOLD),
+    )];
+    lines.extend(app.tensors.iter().zip(&shapes).map(|(tensor, shape)| {
+        Line::from(row(
+            &tensor.name,
+            shape,
+            &tensor.type_name(),
+            &tensor.size.map(format_size).unwrap_or("-".to_string()),
+        ))
+    }));
+    let name = app
+        .highlighted_file()
+        .and_then(|file| file.path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Tensors of {} ({})", name, app.tensors.len()))
+                    .title_bottom(
+                        Line::from(format!(
+                            "↑/↓/PgUp/PgDn: Scroll | Esc/{}: Close",
+                            app.keymap.keys_label(Action::Tensors)
+                        ))
+                        .centered(),
+                    ),
+            )
+            .scroll((app.popup_scroll, 0)),
+        area,
+    );
+}
+
+// Where the space goes by quantization, with a bar for each one's share
+fn render_quantizations(frame: &mut Frame, app: &App) {
+    const BAR_WIDTH: usize = 30;
+    let area = centered_rect(70, 70, frame.area());
+    let sizes = app.quantization_sizes();
+    let total = unique_size(app.files.iter());
+    let name_width = sizes
+        .iter()
+        .map(|(quant, _, _)| quant.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Quantization".len());
+    let row = |quant: &str, files: &str, size: &str, share: &str| {
+        format!(
+            "{:<name_width$}  {:>6}  {:>10}  {:>6}",
+            quant, files, size, share
+        )
+    };
+    let mut lines = vec![Line::styled(
+        row("Quantization", "Files", "Size", "Share"),
+        Style::default()
+            .fg(app.theme.accent)
+            .add_modifier(Modifier::BOLD),
+    )];
+    lines.extend(sizes.iter().map(|(quant, files, size)| {
+        let share = if total > 0 {
+            *size as f64 / total as f64
+        } else {
+            0.0
         };
-        self.list_state.select(Some(i));
+        Line::from(vec![
+            Span::raw(row(
+                quant,
+                &files.to_string(),
+                &format_size(*size),
+                &format!("{:.1}%", share * 100.0),
+            )),
+            Span::styled(
+                format!(
+                    "  {}",
+                    "█".repeat((share * BAR_WIDTH as f64).round() as usize)
+                ),
+                Style::default().fg(app.theme.progress),
+            ),
+        ])
+    }));
+    if sizes.is_empty() {
+        lines = vec![Line::from("No models found yet")];
     }
 
-    fn delete_selected(&mut self) -> io::Result<()> {
-        let mut i = 0;
-        while i < self.files.len() {
-            if self.selected[i] {
-                fs::remove_file(&self.files[i].path)?;
-                self.files.remove(i);
-                self.selected.remove(i);
-            } else {
-                i += 1;
-            }
-        }
-        if let Some(selected) = self.list_state.selected() {
-            if selected >= self.files.len() {
-                self.list_state
-                    .select(Some(self.files.len().saturating_sub(1)));
-            }
-        }
-        Ok(())
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        "Storage by quantization ({} total)",
+                        format_size(total)
+                    ))
+                    .title_bottom(
+                        Line::from(format!(
+                            "↑/↓/PgUp/PgDn: Scroll | Esc/{}: Close",
+                            app.keymap.keys_label(Action::Quantizations)
+                        ))
+                        .centered(),
+                    ),
+            )
+            .scroll((app.popup_scroll, 0)),
+        area,
+    );
+}
+
+// What kinds of models take up the space, a bar chart of the architectures
+// above one of the model families the files are based on
+fn render_families(frame: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, frame.area());
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            "Storage by architecture and family ({} files, {})",
+            app.files.len(),
+            format_size(unique_size(app.files.iter()))
+        ))
+        .title_bottom(
+            Line::from(format!(
+                "Esc/{}: Close",
+                app.keymap.keys_label(Action::Families)
+            ))
+            .centered(),
+        );
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+    if app.files.is_empty() {
+        frame.render_widget(Paragraph::new("No models found yet"), inner);
+        return;
     }
+    let halves =
+        Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).split(inner);
+    render_size_chart(
+        frame,
+        app,
+        halves[0],
+        "Architectures",
+        app.architecture_sizes(),
+    );
+    render_size_chart(frame, app, halves[1], "Families", app.family_sizes());
+}
 
-    fn get_selected_size(&self) -> u64 {
-        self.files
-            .iter()
-            .zip(self.selected.iter())
-            .filter(|(_, &selected)| selected)
-            .map(|(file, _)| file.size)
-            .sum()
+// One bar per group as far as they fit, the smallest ones that don't are
+// put together as other
+fn render_size_chart(
+    frame: &mut Frame,
+    app: &App,
+    area: Rect,
+    title: &str,
+    mut sizes: Vec<(String, usize, u64)>,
+) {
+    // Longer names are cut off, the bars need room too
+    const MAX_LABEL_WIDTH: usize = 30;
+    let block =
+        Block::default()
+            .borders(Borders::TOP)
+            .title(format!("{} ({})", title, sizes.len()));
+    let rows = block.inner(area).height as usize;
+    if sizes.len() > rows && rows > 0 {
+        let rest = sizes.split_off(rows - 1);
+        sizes.push((
+            format!("{} others", rest.len()),
+            rest.iter().map(|(_, files, _)| files).sum(),
+            rest.iter().map(|(_, _, size)| size).sum(),
+        ));
     }
+    let bars: Vec<Bar> = sizes
+        .iter()
+        .map(|(name, files, size)| {
+            let mut label: String = name.chars().take(MAX_LABEL_WIDTH).collect();
+            if name.chars().count() > MAX_LABEL_WIDTH {
+                label.pop();
+                label.push('…');
+            }
+            Bar::default()
+                .value(*size)
+                .label(Line::from(label).fg(app.theme.accent))
+                .text_value(format!(
+                    "{}, {} {}",
+                    format_size(*size),
+                    files,
+                    if *files == 1 { "file" } else { "files" }
+                ))
+        })
+        .collect();
+    frame.render_widget(
+        BarChart::default()
+            .block(block)
+            .direction(Direction::Horizontal)
+            .bar_width(1)
+            .bar_gap(0)
+            .bar_style(Style::default().fg(app.theme.progress))
+            // The value is written on the bar, as far as it reaches
+            .value_style(
+                Style::default()
+                    .fg(app.theme.progress)
+                    .add_modifier(Modifier::REVERSED),
+            )
+            .data(BarGroup::default().bars(&bars)),
+        area,
+    );
 }
 
-#[derive(Debug)]
-enum ScanMessage {
-    File(FileInfo),
-    Directory(String),
-    Done,
-    Error(String),
+// A row of the diff popup, what is compared and its value in either file
+struct DiffRow {
+    label: String,
+    a: String,
+    b: String,
 }
 
-fn format_size(size: u64) -> String {
-    const GB: u64 = 1024 * 1024 * 1024;
-    const MB: u64 = 1024 * 1024;
+// What tells two downloads apart first, then every metadata key either file
+// has, in the order of the first file
+fn diff_rows(a: &FileInfo, b: &FileInfo) -> Vec<DiffRow> {
+    let summary = |file: &FileInfo| {
+        let header = file.header.as_ref();
+        vec![
+            ("Size", format_size(file.size)),
+            (
+                "Architecture",
+                header
+                    .and_then(GgufHeader::architecture)
+                    .unwrap_or("-")
+                    .to_string(),
+            ),
+            (
+                "Quant",
+                header
+                    .and_then(GgufHeader::quantization)
+                    .unwrap_or("-")
+                    .to_string(),
+            ),
+            (
+                "Parameters",
+                header.map_or("-".to_string(), |header| {
+                    format_param_count(header.parameter_count)
+                }),
+            ),
+            (
+                "Context length",
+                header
+                    .and_then(GgufHeader::context_length)
+                    .map_or("-".to_string(), |length| length.to_string()),
+            ),
+            (
+                "Tokenizer",
+                header
+                    .and_then(GgufHeader::tokenizer)
+                    .unwrap_or("-")
+                    .to_string(),
+            ),
+            (
+                "Vocabulary",
+                header
+                    .and_then(GgufHeader::vocab_size)
+                    .map_or("-".to_string(), |size| size.to_string()),
+            ),
+            (
+                "Chat template",
+                match header.and_then(|header| header.get("tokenizer.chat_template")) {
+                    Some(_) => "yes".to_string(),
+                    None => "no".to_string(),
+                },
+            ),
+            (
+                "GGUF version",
+                header.map_or("-".to_string(), |header| header.version.to_string()),
+            ),
+        ]
+    };
+    let mut rows: Vec<DiffRow> = summary(a)
+        .into_iter()
+        .zip(summary(b))
+        .map(|((label, a), (_, b))| DiffRow {
+            label: label.to_string(),
+            a,
+            b,
+        })
+        .collect();
 
-    if size >= GB {
-        format!("{:.2} GB", size as f64 / GB as f64)
-    } else if size >= MB {
-        format!("{:.2} MB", size as f64 / MB as f64)
-    } else {
-        format!("{} B", size)
-    }
+    let metadata = |file: &FileInfo| -> Vec<(String, String)> {
+        file.header.as_ref().map_or(Vec::new(), |header| {
+            header
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.clone(), value.to_string()))
+                .collect()
+        })
+    };
+    let (a, b) = (metadata(a), metadata(b));
+    let mut keys: Vec<&String> = a.iter().map(|(key, _)| key).collect();
+    keys.extend(
+        b.iter()
+            .map(|(key, _)| key)
+            .filter(|key| !a.iter().any(|(k, _)| k == *key)),
+    );
+    let value = |metadata: &[(String, String)], key: &str| {
+        metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map_or("-".to_string(), |(_, value)| value.clone())
+    };
+    rows.extend(keys.into_iter().map(|key| DiffRow {
+        label: key.clone(),
+        a: value(&a, key),
+        b: value(&b, key),
+    }));
+    rows
 }
 
-fn scan_directory(tx: Sender<ScanMessage>) {
-    let (worker_tx, worker_rx) = mpsc::channel();
-    let tx_clone = tx.clone();
-
-    thread::spawn(move || {
-        for message in worker_rx {
-            tx_clone.send(message).ok();
+// The files next to each other, the rows where they differ stand out
+fn render_diff(frame: &mut Frame, app: &App) {
+    let area = centered_rect(90, 80, frame.area());
+    let Some((a, b)) = app.diff_files() else {
+        return;
+    };
+    let rows = diff_rows(a, b);
+    let name = |file: &FileInfo| {
+        file.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let label_width = rows
+        .iter()
+        .map(|row| row.label.chars().count())
+        .max()
+        .unwrap_or(0);
+    let value_width = (area.width.saturating_sub(2) as usize).saturating_sub(label_width + 4) / 2;
+    // Multi-line values like the chat template are shown on one line, cut
+    // to the column
+    let cell = |text: &str| {
+        let mut text = text.replace('\n', "⏎");
+        if text.chars().count() > value_width {
+            text = text.chars().take(value_width.saturating_sub(1)).collect();
+            text.push('…');
         }
-    });
-
-    let walker = WalkBuilder::new("/")
-        .hidden(false)
-        .ignore(false)
-        .git_ignore(false)
-        .threads(num_cpus::get())
-        .build_parallel();
-
-    walker.run(|| {
-        let worker_tx = worker_tx.clone();
-        Box::new(move |entry| {
-            let entry = match entry {
-                Ok(entry) => entry,
-                Err(_) => return ignore::WalkState::Continue,
-            };
+        format!("{:<value_width$}", text)
+    };
+    let differ = rows.iter().filter(|row| row.a != row.b).count();
+    let mut lines = vec![Line::styled(
+        format!(
+            "{:<label_width$}  {}  {}",
+            "",
+            cell(&name(a)),
+            cell(&name(b))
+        ),
+        Style::default()
+            .fg(app.theme.accent)
+            .add_modifier(Modifier::BOLD),
+    )];
+    lines.extend(rows.iter().map(|row| {
+        let line = Line::from(vec![
+            Span::styled(
+                format!("{:<label_width$}  ", row.label),
+                Style::default().fg(app.theme.accent),
+            ),
+            Span::raw(format!("{}  {}", cell(&row.a), cell(&row.b))),
+        ]);
+        if row.a == row.b {
+            line
+        } else {
+            line.fg(app.theme.warning)
+        }
+    }));
 
-            let path = entry.path();
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        "Diff of {} and {} ({} of {} differ)",
+                        name(a),
+                        name(b),
+                        differ,
+                        rows.len()
+                    ))
+                    .title_bottom(
+                        Line::from(format!(
+                            "↑/↓/PgUp/PgDn: Scroll | Esc/{}: Close",
+                            app.keymap.keys_label(Action::Diff)
+                        ))
+                        .centered(),
+                    ),
+            )
+            .scroll((app.popup_scroll, 0)),
+        area,
+    );
+}
 
-            // Send directory updates
-            if path.is_dir() {
-                if let Some(path_str) = path.to_str() {
-                    worker_tx
-                        .send(ScanMessage::Directory(path_str.to_string()))
-                        .ok();
+// Jinja tags and expressions stand out from the text around them, also
+// when they span several lines
+fn render_chat_template(frame: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, frame.area());
+    let template = app.chat_template().unwrap_or_default();
+    let tag = Style::default().fg(app.theme.accent);
+    let mut lines = Vec::new();
+    let mut in_tag = false;
+    for text in template.lines() {
+        let mut spans = Vec::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (end, closes) = if in_tag {
+                match ["%}", "}}", "#}"]
+                    .iter()
+                    .filter_map(|close| rest.find(close).map(|at| at + close.len()))
+                    .min()
+                {
+                    Some(end) => (end, true),
+                    None => (rest.len(), false),
                 }
-            }
-
-            // Check if it's a file and has the GGUF magic number
-            if path.is_file() {
-                match is_gguf_file(path) {
-                    Ok(true) => {
-                        if let Ok(metadata) = fs::metadata(path) {
-                            worker_tx
-                                .send(ScanMessage::File(FileInfo {
-                                    path: path.to_owned(),
-                                    size: metadata.len(),
-                                }))
-                                .ok();
-                        }
-                    }
-                    Ok(false) => {}
-                    Err(e) => {
-                        worker_tx
-                            .send(ScanMessage::Error(format!(
-                                "Error reading file {}: {}",
-                                path.display(),
-                                e
-                            )))
-                            .ok();
-                    }
+            } else {
+                match ["{%", "{{", "{#"]
+                    .iter()
+                    .filter_map(|open| rest.find(open))
+                    .min()
+                {
+                    Some(start) => (start, true),
+                    None => (rest.len(), false),
                 }
+            };
+            if end > 0 {
+                let style = if in_tag { tag } else { Style::default() };
+                spans.push(Span::styled(rest[..end].to_string(), style));
             }
+            rest = &rest[end..];
+            if closes {
+                in_tag = !in_tag;
+            }
+        }
+        lines.push(Line::from(spans));
+    }
+    let name = app
+        .highlighted_file()
+        .and_then(|file| file.path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
 
-            ignore::WalkState::Continue
-        })
-    });
-
-    tx.send(ScanMessage::Done).ok();
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Chat template of {}", name))
+                    .title_bottom(
+                        Line::from(format!(
+                            "↑/↓/PgUp/PgDn: Scroll | Esc/{}: Close",
+                            app.keymap.keys_label(Action::ChatTemplate)
+                        ))
+                        .centered(),
+                    ),
+            )
+            .scroll((app.popup_scroll, 0)),
+        area,
+    );
 }
 
-// UI code and run_app function remain the same...
-fn ui(frame: &mut Frame, app: &mut App) {
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3),
-            Constraint::Min(1),
-            Constraint::Length(3),
-        ])
-        .split(frame.area());
-
-    let title = if app.scanning {
-        format!(
-            "Scanning: {} | Directories: {} | Files found: {}",
-            app.current_path, app.dirs_scanned, app.files_found
-        )
-    } else {
-        format!("Scan complete | Found {} GGUF files", app.files.len())
+fn render_confirm_revision(frame: &mut Frame, app: &App) {
+    let Some(removal) = &app.pending_revision else {
+        return;
     };
+    let area = centered_rect(70, 50, frame.area());
+
+    let mut lines = vec![
+        Line::from(format!("Snapshot: {}", removal.snapshot_dir.display())),
+        Line::from(""),
+    ];
+    if removal.blobs.is_empty() {
+        lines.push(Line::from("All blobs are shared with other revisions"));
+    }
+    lines.extend(
+        removal
+            .blobs
+            .iter()
+            .map(|blob| Line::from(blob.display().to_string())),
+    );
+    lines.push(Line::from(""));
+    lines.push(
+        Line::from("y/Enter: Confirm | Esc/n: Cancel")
+            .bold()
+            .alignment(Alignment::Center),
+    );
 
+    frame.render_widget(Clear, area);
     frame.render_widget(
-        Paragraph::new(title)
-            .block(Block::default().borders(Borders::ALL))
-            .wrap(Wrap { trim: true }),
-        chunks[0],
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.theme.danger))
+                .title(format!(
+                    "Remove stale revision {} of {} ({})?",
+                    removal.revision,
+                    removal.repo,
+                    format_size(removal.size)
+                )),
+        ),
+        area,
     );
+}
 
-    let items: Vec<ListItem> = app
-        .files
+// Old and new names, files that keep theirs say why
+fn render_confirm_renames(frame: &mut Frame, app: &App) {
+    let area = centered_rect(80, 60, frame.area());
+    let name = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let count = app
+        .pending_renames
         .iter()
-        .enumerate()
-        .map(|(i, file)| {
-            let checkbox = if app.selected[i] { "[x] " } else { "[ ] " };
-            ListItem::new(format!(
-                "{}{:<10} | {}",
-                checkbox,
+        .filter(|(_, target)| target.is_ok())
+        .count();
+
+    let mut lines: Vec<Line> = app
+        .pending_renames
+        .iter()
+        .map(|(path, target)| match target {
+            Ok(target) => Line::from(format!("{} → {}", name(path), name(target))),
+            Err(reason) => {
+                Line::from(format!("{}: skipped, {}", name(path), reason)).fg(app.theme.warning)
+            }
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(
+        Line::from(if count > 0 {
+            "y/Enter: Rename | Esc/n: Cancel"
+        } else {
+            "Esc/n: Cancel"
+        })
+        .bold()
+        .alignment(Alignment::Center),
+    );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Rename {} files to {}?", count, app.name_template)),
+        ),
+        area,
+    );
+}
+
+fn render_confirm_delete(frame: &mut Frame, app: &App) {
+    let area = centered_rect(70, 60, frame.area());
+    let action = match app.delete_mode {
+        DeleteMode::Trash => "Move to trash",
+        DeleteMode::Permanent => "Permanently delete",
+        DeleteMode::Quarantine => "Quarantine",
+    };
+    let count = app.selected_files().count();
+
+    let mut lines: Vec<Line> = app
+        .selected_files()
+        .map(|file| {
+            Line::from(format!(
+                "{:<10} | {}",
                 format_size(file.size),
                 file.path.display()
             ))
         })
         .collect();
 
-    let list = List::new(items)
-        .block(Block::default().title("Files").borders(Borders::ALL))
-        .highlight_style(Style::default().bg(Color::DarkGray));
-
-    frame.render_stateful_widget(list, chunks[1], &mut app.list_state);
+    let referenced: Vec<&str> = app
+        .selected_files()
+        .filter_map(|file| match &file.source {
+            Some(ModelSource::Ollama { models }) => Some(models),
+            _ => None,
+        })
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    if !referenced.is_empty() || !app.in_use.is_empty() {
+        lines.push(Line::from(""));
+    }
+    if !referenced.is_empty() {
+        lines.push(
+            Line::from(format!(
+                "Warning: Ollama still references these blobs for {}",
+                referenced.join(", ")
+            ))
+            .fg(app.theme.warning)
+            .bold(),
+        );
+    }
+    for (path, users) in &app.in_use {
+        let users: Vec<String> = users
+            .iter()
+            .map(|user| format!("{} (pid {})", user.name, user.pid))
+            .collect();
+        lines.push(
+            Line::from(format!(
+                "Warning: {} has {} open",
+                users.join(", "),
+                path.display()
+            ))
+            .fg(app.theme.warning)
+            .bold(),
+        );
+    }
 
-    let total_selected_size = format_size(app.get_selected_size());
-    let help_text = format!(
-        "↑/↓: Navigate | Space: Toggle | A: Select All | U: Deselect All | D: Delete Selected | Q: Quit | Selected size: {}",
-        total_selected_size
+    lines.push(Line::from(""));
+    lines.push(
+        Line::from(if app.in_use.is_empty() {
+            "y/Enter: Confirm | Esc/n: Cancel"
+        } else {
+            "y/Enter: Delete anyway | Esc/n: Cancel"
+        })
+        .bold()
+        .alignment(Alignment::Center),
     );
 
+    frame.render_widget(Clear, area);
     frame.render_widget(
-        Paragraph::new(help_text)
-            .block(Block::default().borders(Borders::ALL))
-            .alignment(Alignment::Center),
-        chunks[2],
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.theme.danger))
+                .title(format!(
+                    "{} {} files ({})?",
+                    action,
+                    count,
+                    format_size(app.get_selected_size())
+                )),
+        ),
+        area,
     );
 }
 
-fn run_app(rx: Receiver<ScanMessage>) -> Result<()> {
+// The runner command for the file, split on whitespace with `{path}`
+// replaced in every word, so paths with spaces stay one argument. The path
+// goes last when the command doesn't mention it.
+fn runner_command(runner: &str, path: &Path) -> Option<std::process::Command> {
+    let path = absolute_path(path);
+    let mut words: Vec<String> = runner
+        .split_whitespace()
+        .map(|word| word.replace("{path}", &path))
+        .collect();
+    if !runner.contains("{path}") {
+        words.push(path);
+    }
+    let (program, args) = words.split_first()?;
+    let mut command = std::process::Command::new(program);
+    command.args(args);
+    Some(command)
+}
+
+// Hands the terminal over to the runner command until it exits, the scan
+// carries on in the background
+fn run_model(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
+    let Some(runner) = app.runner.clone() else {
+        app.status = Some(
+            "No runner set, add runner = \"llama-server -m {path}\" to the config".to_string(),
+        );
+        return Ok(());
+    };
+    let Some(file) = app.highlighted_file() else {
+        return Ok(());
+    };
+    let path = file.path.clone();
+    let Some(mut command) = runner_command(&runner, &path) else {
+        app.status = Some("The runner command in the config is empty".to_string());
+        return Ok(());
+    };
+
+    // Ctrl-C stops the model, not us. Setting the handler a second time
+    // fails, the first one is still there then.
+    let _ = ctrlc::set_handler(|| {});
+
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    let result = command.status();
+    match &result {
+        Ok(status) => println!(
+            "\n{} exited with {}, press Enter to return to ggufscan",
+            runner, status
+        ),
+        Err(e) => println!(
+            "\nCannot run {}: {}, press Enter to return to ggufscan",
+            runner, e
+        ),
+    }
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    enable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    terminal.clear()?;
+
+    app.status = Some(match result {
+        Ok(status) => format!("{} exited with {}", path.display(), status),
+        Err(e) => format!("Cannot run {}: {}", runner, e),
+    });
+    Ok(())
+}
+
+fn run_app(mut app: App, mut rx: Receiver<ScanMessage>) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
 
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
-    let mut app = App::new();
 
     loop {
-        if app.scanning {
+        {
+            let mut received_files = false;
             while let Ok(message) = rx.try_recv() {
                 match message {
                     ScanMessage::File(file_info) => {
-                        app.files.push(file_info);
-                        app.selected.push(false);
-                        app.files_found += 1;
-                        if app.files.len() == 1 {
-                            app.list_state.select(Some(0));
-                        }
+                        app.add_file(*file_info);
+                        received_files = true;
                     }
-                    ScanMessage::Directory(path) => {
-                        app.current_path = path;
-                        app.dirs_scanned += 1;
+                    ScanMessage::CacheLoaded(count) => app.cached_files = count,
+                    ScanMessage::Directory(path) => app.current_path = path,
+                    ScanMessage::Progress(progress) => app.progress = progress,
+                    ScanMessage::RootProgress(root, progress) => {
+                        app.root_progress.insert(root, progress);
+                    }
+                    ScanMessage::RootDone(root) => {
+                        app.roots_done.insert(root);
                     }
                     ScanMessage::Done => {
                         app.scanning = false;
+                        app.reselect.clear();
+                        app.record_scan();
+                        app.notify_done();
+                        // Split models missing parts can be told now
+                        let highlighted = app.highlighted_file().map(|file| file.path.clone());
+                        app.update_visible(highlighted);
+                    }
+                    ScanMessage::CheckingDuplicates => app.checking_duplicates = true,
+                    ScanMessage::DuplicateGroup(paths) => app.add_duplicate_group(paths),
+                    ScanMessage::DuplicatesDone => app.checking_duplicates = false,
+                    ScanMessage::Watching => app.watching = true,
+                    ScanMessage::Removed(path) => {
+                        app.retain_files(|file| !file.path.starts_with(&path));
+                    }
+                    ScanMessage::Cancelled => {
+                        app.scanning = false;
+                        app.checking_duplicates = false;
+                        app.paused_at = None;
+                        app.scan_cancelled = true;
                     }
-                    ScanMessage::Error(_) => {}
+                    ScanMessage::Error(error) => app.errors.push(error),
                 }
             }
+            if received_files {
+                app.sort_files();
+            }
         }
+        app.poll_move();
+        app.poll_archive();
+        app.poll_merge();
+        app.poll_delete();
+        app.poll_verify();
+        app.poll_checksum();
 
         terminal.draw(|frame| ui(frame, &mut app))?;
 
         if event::poll(Duration::from_millis(50))? {
-            match event::read()? {
-                Event::Key(key) => match key.code {
-                    KeyCode::Char('q') => break,
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Mouse(mouse) => {
+                    if app.popup.is_none() {
+                        app.handle_mouse(mouse);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            match app.popup {
+                Some(Popup::ConfirmDelete) => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
+                        app.popup = None;
+                        app.delete_selected();
+                    }
+                    KeyCode::Esc | KeyCode::Char('n') => app.popup = None,
+                    _ => {}
+                },
+                Some(Popup::ConfirmRevision) => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
+                        app.popup = None;
+                        app.remove_revision();
+                    }
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.popup = None;
+                        app.pending_revision = None;
+                    }
+                    _ => {}
+                },
+                Some(Popup::ConfirmRenames) => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => {
+                        app.popup = None;
+                        app.normalize_names();
+                    }
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.popup = None;
+                        app.pending_renames.clear();
+                    }
+                    _ => {}
+                },
+                Some(Popup::MoveDestination) => match key.code {
+                    KeyCode::Enter => {
+                        app.popup = None;
+                        app.start_move();
+                    }
+                    KeyCode::Esc => app.popup = None,
+                    KeyCode::Backspace => {
+                        app.move_destination.pop();
+                    }
+                    KeyCode::Char(c) => app.move_destination.push(c),
+                    _ => {}
+                },
+                Some(Popup::Rename) => match key.code {
+                    KeyCode::Enter => {
+                        app.popup = None;
+                        app.rename_highlighted();
+                    }
+                    KeyCode::Esc => app.popup = None,
+                    KeyCode::Backspace => {
+                        app.new_name.pop();
+                    }
+                    KeyCode::Char(c) => app.new_name.push(c),
+                    _ => {}
+                },
+                Some(Popup::Command) => match key.code {
+                    KeyCode::Enter => {
+                        app.popup = None;
+                        app.run_command();
+                    }
+                    KeyCode::Esc => {
+                        app.popup = None;
+                        app.command.clear();
+                    }
+                    // Like vim, backspacing over the `:` leaves
+                    KeyCode::Backspace if app.command.is_empty() => app.popup = None,
+                    KeyCode::Backspace => {
+                        app.command.pop();
+                    }
+                    KeyCode::Char(c) => app.command.push(c),
+                    _ => {}
+                },
+                Some(Popup::Columns) => match key.code {
+                    KeyCode::Up => app.column_cursor = app.column_cursor.saturating_sub(1),
+                    KeyCode::Down => {
+                        app.column_cursor = (app.column_cursor + 1).min(Column::ALL.len() - 1);
+                    }
+                    KeyCode::Char(' ') => app.toggle_column(),
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('C') => app.popup = None,
+                    _ => {}
+                },
+                Some(Popup::Architectures) => match key.code {
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.arch_cursor = app.arch_cursor.saturating_sub(1);
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        let last = app.found_architectures().len().saturating_sub(1);
+                        app.arch_cursor = (app.arch_cursor + 1).min(last);
+                    }
+                    KeyCode::Char(' ') => app.toggle_architecture(),
+                    KeyCode::Char('u') => {
+                        app.architectures.clear();
+                        let highlighted = app.highlighted_file().map(|file| file.path.clone());
+                        app.update_visible(highlighted);
+                    }
+                    KeyCode::Esc | KeyCode::Enter => app.popup = None,
+                    _ => {
+                        let press = KeyPress::from_event(&key);
+                        if app.keymap.action(&mut None, press) == Some(Action::Architectures) {
+                            app.popup = None;
+                        }
+                    }
+                },
+                Some(
+                    Popup::Help
+                    | Popup::Errors
+                    | Popup::Selection
+                    | Popup::ChatTemplate
+                    | Popup::Tensors
+                    | Popup::Diff
+                    | Popup::Quantizations,
+                ) => {
+                    match key.code {
+                        KeyCode::Up | KeyCode::Char('k') => {
+                            app.popup_scroll = app.popup_scroll.saturating_sub(1);
+                        }
+                        KeyCode::Down | KeyCode::Char('j') => app.scroll_popup(1),
+                        KeyCode::PageUp => app.popup_scroll = app.popup_scroll.saturating_sub(10),
+                        KeyCode::PageDown => app.scroll_popup(10),
+                        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => app.popup = None,
+                        _ => {
+                            // The key that opened the popup closes it too
+                            let press = KeyPress::from_event(&key);
+                            let action = app.keymap.action(&mut None, press);
+                            if matches!(
+                                action,
+                                Some(
+                                    Action::Help
+                                        | Action::Errors
+                                        | Action::Selection
+                                        | Action::ChatTemplate
+                                        | Action::Tensors
+                                        | Action::Diff
+                                        | Action::Quantizations
+                                )
+                            ) {
+                                app.popup = None;
+                            }
+                        }
+                    }
+                }
+                Some(Popup::Families) => match key.code {
+                    KeyCode::Esc | KeyCode::Enter => app.popup = None,
+                    _ => {
+                        let press = KeyPress::from_event(&key);
+                        if app.keymap.action(&mut None, press) == Some(Action::Families) {
+                            app.popup = None;
+                        }
+                    }
+                },
+                Some(Popup::Filter) => match key.code {
+                    KeyCode::Enter => app.popup = None,
+                    KeyCode::Esc => {
+                        app.clear_filter();
+                        app.popup = None;
+                    }
+                    KeyCode::Backspace => app.pop_filter_char(),
+                    KeyCode::Char(c) => app.push_filter_char(c),
                     KeyCode::Up => app.previous(),
                     KeyCode::Down => app.next(),
-                    KeyCode::Char(' ') => app.toggle_selected(),
-                    KeyCode::Char('a') => app.select_all(),
-                    KeyCode::Char('u') => app.deselect_all(),
-                    KeyCode::Char('d') => app.delete_selected()?,
                     _ => {}
                 },
-                _ => {}
+                None => {
+                    let press = KeyPress::from_event(&key);
+                    let Some(action) = app.keymap.action(&mut app.pending_key, press) else {
+                        continue;
+                    };
+                    // Anything but moving around ends the visual range, and
+                    // moving without Shift the one started with it
+                    let shift_move = matches!(action, Action::SelectUp | Action::SelectDown);
+                    if app.shift_range && action.is_movement() && !shift_move {
+                        app.visual = None;
+                        app.shift_range = false;
+                    }
+                    if !action.is_movement() && app.visual.is_some() {
+                        app.visual = None;
+                        app.shift_range = false;
+                        if matches!(action, Action::Visual | Action::CancelScan) {
+                            continue;
+                        }
+                    }
+
+                    match action {
+                        // A running delete stops after the file at hand
+                        Action::Quit => {
+                            app.cancel_delete();
+                            while app.delete_job.is_some() {
+                                thread::sleep(Duration::from_millis(20));
+                                app.poll_delete();
+                            }
+                            break;
+                        }
+                        Action::Up => app.previous(),
+                        Action::Down => app.next(),
+                        Action::SelectUp => app.extend_range(true),
+                        Action::SelectDown => app.extend_range(false),
+                        Action::PageUp => app.move_highlight(-app.page()),
+                        Action::PageDown => app.move_highlight(app.page()),
+                        Action::HalfPageUp => app.move_highlight(-app.half_page()),
+                        Action::HalfPageDown => app.move_highlight(app.half_page()),
+                        Action::Top => app.move_highlight(isize::MIN),
+                        Action::Bottom => app.move_highlight(isize::MAX),
+                        Action::Visual => app.toggle_visual(),
+                        Action::Expand if app.view == ListView::Directories => app.open_dir(),
+                        Action::Expand if app.view == ListView::Tree => app.expand_dir(),
+                        Action::Collapse if app.view == ListView::Tree => app.collapse_dir(),
+                        Action::Merge => app.request_merge(),
+                        Action::Expand => app.expand_shards(true),
+                        Action::Collapse
+                            if app.within_dir.is_some()
+                                && app
+                                    .highlighted_file()
+                                    .is_none_or(|file| file.shard.is_none()) =>
+                        {
+                            app.toggle_view(ListView::Directories)
+                        }
+                        Action::Collapse => app.expand_shards(false),
+                        Action::Toggle => app.toggle_selected(),
+                        Action::SelectAll => app.select_all(),
+                        Action::DeselectAll => app.deselect_all(),
+                        Action::InvertSelection => app.invert_selection(),
+                        Action::SelectDuplicates => app.select_duplicate_copies(),
+                        Action::SelectOlder => app.select_older_copies(),
+                        Action::SelectCorrupt => app.select_corrupt(),
+                        Action::Delete => app.request_delete(),
+                        Action::Undo => app.undo_delete(),
+                        Action::Move => app.request_move(),
+                        Action::Archive => app.request_archive(false),
+                        Action::RestoreArchive => app.request_archive(true),
+                        Action::Rename => app.request_rename(),
+                        Action::NormalizeNames => app.request_normalize_names(),
+                        Action::CopyPath => app.copy_paths(),
+                        Action::OpenFolder => app.open_folder(),
+                        Action::Run => run_model(&mut terminal, &mut app)?,
+                        Action::Verify => app.start_verify(),
+                        Action::Checksum => app.start_checksum(),
+                        Action::RemoveRevision => app.request_revision_removal(),
+                        Action::Details => app.show_details = !app.show_details,
+                        Action::ChatTemplate => app.show_chat_template(),
+                        Action::Tensors => app.show_tensors(),
+                        Action::Diff => app.show_diff(),
+                        Action::Sort => app.cycle_sort_key(),
+                        Action::SortDirection => app.flip_sort_direction(),
+                        Action::Filter => app.popup = Some(Popup::Filter),
+                        Action::Command => app.popup = Some(Popup::Command),
+                        Action::Architectures => {
+                            app.arch_cursor = 0;
+                            app.popup = Some(Popup::Architectures);
+                        }
+                        Action::Quantizations => app.open_scrolling_popup(Popup::Quantizations),
+                        Action::Families => app.popup = Some(Popup::Families),
+                        Action::Columns => app.popup = Some(Popup::Columns),
+                        Action::DirView => app.toggle_view(ListView::Directories),
+                        Action::TreeView => app.toggle_view(ListView::Tree),
+                        Action::Help => app.open_scrolling_popup(Popup::Help),
+                        Action::Errors => app.open_scrolling_popup(Popup::Errors),
+                        Action::Selection => app.open_scrolling_popup(Popup::Selection),
+                        Action::ExportJson => app.export(ExportFormat::Json),
+                        Action::ExportCsv => app.export(ExportFormat::Csv),
+                        Action::Pause => app.toggle_pause(),
+                        Action::CancelScan if app.delete_job.is_some() => app.cancel_delete(),
+                        Action::CancelScan => app.cancel_scan(),
+                        Action::Rescan => {
+                            if let Some(new_rx) = app.rescan() {
+                                rx = new_rx;
+                            }
+                        }
+                    }
+                    if action.is_movement() {
+                        app.apply_visual();
+                    }
+                }
             }
         }
     }
@@ -348,17 +5652,151 @@ fn run_app(rx: Receiver<ScanMessage>) -> Result<()> {
         DisableMouseCapture
     )?;
 
+    if app.removed_files > 0 {
+        eprintln!(
+            "Removed {} files this session, {} reclaimed",
+            app.removed_files,
+            format_size(app.reclaimed)
+        );
+    }
+    if app.dry_run && !app.dry_run_log.is_empty() {
+        for line in &app.dry_run_log {
+            println!("{}", line);
+        }
+        eprintln!(
+            "Dry run, nothing was touched. Would have freed {}",
+            format_size(app.dry_run_size)
+        );
+    }
     Ok(())
 }
 
+// `archive_dir` from the config or the default in the data directory
+fn archive_dir(config: &Config) -> Option<PathBuf> {
+    config
+        .archive_dir
+        .as_deref()
+        .map(expand_home)
+        .or_else(default_archive_dir)
+}
+
 fn main() -> Result<()> {
-    let (tx, rx) = mpsc::channel();
+    let args = Args::parse();
+    let config = Config::load()?;
+    if let Some(Command::Quarantine {
+        paths,
+        restore,
+        purge,
+    }) = &args.command
+    {
+        let quarantine = quarantine_dir(&config).context("No quarantine directory found")?;
+        return quarantine::run_quarantine(&quarantine, paths, *restore, *purge);
+    }
+    if let Some(Command::Clean(clean)) = &args.command {
+        return clean::run_clean(&config, clean);
+    }
+    if let Some(Command::Serve(serve)) = &args.command {
+        return serve::run_server(&config, serve);
+    }
+    if let Some(Command::Daemon(daemon)) = &args.command {
+        return daemon::run_daemon(&config, daemon);
+    }
+    if let Some(Command::Db { command }) = &args.command {
+        let inventory = inventory_path(&config).context("No inventory database found")?;
+        return db::run_db(&inventory, command);
+    }
 
-    thread::spawn(move || {
-        scan_directory(tx);
-    });
+    let remote = match &args.command {
+        Some(Command::Ssh(ssh)) => Some(RemoteTarget::parse(ssh)?),
+        _ => None,
+    };
+
+    let mut options = args.scan_options(&config)?;
+    if args.remote_helper {
+        let mut out = stdout().lock();
+        for message in Scanner::new(options).spawn() {
+            // The other side has gone away
+            if write_remote_message(&mut out, &message).is_err() {
+                break;
+            }
+        }
+        return Ok(());
+    }
+    // The roots of another machine are only checked over there
+    match &remote {
+        Some(remote) => options.roots = vec![remote.root()],
+        None => check_roots(&options.roots)?,
+    }
 
-    run_app(rx).context("Error running application")?;
+    let roots = options.roots.clone();
+    // The inventory is of this machine's disks
+    let inventory = if remote.is_none() && (args.record || config.record_scans) {
+        Some(inventory_path(&config).context("No inventory database found")?)
+    } else {
+        None
+    };
+    let notify = args.notify || config.notify;
+    let remote_output = match &args.command {
+        Some(Command::Ssh(ssh)) => ssh.output.map(ExportFormat::from),
+        _ => None,
+    };
+    if let (Some(remote), Some(format)) = (&remote, remote_output) {
+        let rx = remote.scanner().spawn();
+        let mut out = io::BufWriter::new(stdout().lock());
+        run_headless(&mut out, rx, format, &roots, &args.arch, None, notify)?;
+    } else if args.progress.is_some() {
+        let rx = spawn_scan(options).1;
+        run_events(
+            &mut stdout().lock(),
+            rx,
+            &roots,
+            &args.arch,
+            inventory.as_deref(),
+        )?;
+    } else if let Some(format) = args.headless_output() {
+        let rx = spawn_scan(options).1;
+        let mut out = io::BufWriter::new(stdout().lock());
+        let inventory = inventory.as_deref();
+        run_headless(&mut out, rx, format, &roots, &args.arch, inventory, notify)?;
+    } else {
+        let object_storage = roots.iter().any(|root| is_s3_path(root));
+        let delete_mode = if object_storage {
+            DeleteMode::Permanent
+        } else {
+            args.delete.delete_mode(&config)
+        };
+        let quarantine = quarantine_dir(&config);
+        anyhow::ensure!(
+            delete_mode != DeleteMode::Quarantine || quarantine.is_some(),
+            "No quarantine directory found, set quarantine_dir in the config"
+        );
+        let mut app = App::new(roots, delete_mode, quarantine.unwrap_or_default());
+        app.archive_dir = archive_dir(&config).unwrap_or_default();
+        if let Some(path) = default_checksum_cache_path() {
+            app.checksums = ChecksumCache::load(&path);
+        }
+        app.max_depth = args.scan.max_depth;
+        app.theme = config.theme();
+        app.keymap = config.keymap()?;
+        app.columns = config.columns;
+        app.runner = config.runner;
+        if let Some(gguf_split) = config.gguf_split {
+            app.gguf_split = gguf_split;
+        }
+        if let Some(template) = config.name_template {
+            app.name_template = template;
+        }
+        app.architectures = args.arch.clone();
+        app.inventory = inventory;
+        app.notify = notify;
+        app.dry_run = args.dry_run;
+        // Nothing over there can be deleted or moved from here
+        app.read_only = args.read_only || remote.is_some();
+        app.remote = remote;
+        app.object_storage = object_storage;
+        let rx = app.start_scan(options);
+        run_app(app, rx).context("Error running application")?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,255 @@
+//! Merging split models back into one file with llama.cpp's `gguf-split`.
+
+use ggufscan_core::{FileInfo, GgufHeader, ScanOptions, GGUF_MAGIC};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{config::DeleteMode, remove_path, scan_file};
+
+// Used when the config has no `gguf_split`
+pub(crate) const DEFAULT_GGUF_SPLIT: &str = "llama-gguf-split";
+
+// `model-*-of-00003.gguf` becomes `model.gguf`
+pub(crate) fn merged_path(group: &Path) -> Option<PathBuf> {
+    let name = group.file_name()?.to_str()?;
+    let (base, rest) = name.split_once("-*-of-")?;
+    let (_, extension) = rest.rsplit_once('.')?;
+    Some(group.with_file_name(format!("{}.{}", base, extension)))
+}
+
+// Runs `gguf-split --merge` and checks the result has every tensor of the
+// parts, a merged file that doesn't is removed again
+fn merge_shards(program: &str, first: &Path, output: &Path, tensors: u64) -> Result<(), String> {
+    let result = Command::new(program)
+        .arg("--merge")
+        .arg(first)
+        .arg(output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output();
+    let output_error = match result {
+        Ok(result) if result.status.success() => None,
+        Ok(result) => {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            Some(
+                stderr
+                    .lines()
+                    .rev()
+                    .find(|line| !line.trim().is_empty())
+                    .map_or_else(|| result.status.to_string(), str::to_string),
+            )
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Some(format!(
+            "{} not found, install llama.cpp or set gguf_split in the config",
+            program
+        )),
+        Err(e) => Some(e.to_string()),
+    };
+    let error = output_error.or_else(|| {
+        let header = fs::File::open(output).and_then(|mut file| {
+            let mut magic = [0; 4];
+            file.read_exact(&mut magic)?;
+            if magic != GGUF_MAGIC {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "not a GGUF file",
+                ));
+            }
+            GgufHeader::parse(io::BufReader::new(file))
+        });
+        match header {
+            Ok(header) if header.tensor_count == tensors => None,
+            Ok(header) => Some(format!(
+                "the merged file has {} tensors, expected {}",
+                header.tensor_count, tensors
+            )),
+            Err(e) => Some(format!("the merged file can't be read, {}", e)),
+        }
+    });
+    match error {
+        Some(error) => {
+            fs::remove_file(output).ok();
+            Err(error)
+        }
+        None => Ok(()),
+    }
+}
+
+// The parts of a split model, in order, merged into `output`
+pub(crate) struct Merge {
+    pub(crate) program: String,
+    pub(crate) parts: Vec<PathBuf>,
+    pub(crate) output: PathBuf,
+    // All the parts have together
+    pub(crate) tensors: u64,
+    pub(crate) delete_mode: DeleteMode,
+    pub(crate) quarantine_dir: PathBuf,
+    // How the merged file is inspected, when the list came from a scan
+    pub(crate) scan_options: Option<ScanOptions>,
+}
+
+pub(crate) struct MergeResult {
+    pub(crate) file: Option<FileInfo>,
+    // Parts that couldn't be removed after merging
+    pub(crate) errors: Vec<String>,
+}
+
+impl Merge {
+    // Merges and removes the parts the way deleting does once the merged
+    // file checks out
+    pub(crate) fn run(self) -> Result<MergeResult, String> {
+        merge_shards(&self.program, &self.parts[0], &self.output, self.tensors)?;
+        let errors = self
+            .parts
+            .iter()
+            .filter_map(|part| {
+                let e = remove_path(self.delete_mode, &self.quarantine_dir, part, false).err()?;
+                Some(format!("Error removing {}: {}", part.display(), e))
+            })
+            .collect();
+        Ok(MergeResult {
+            file: self
+                .scan_options
+                .and_then(|options| scan_file(options, &self.output)),
+            errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A GGUF file with `tensors` one element tensors and no metadata
+    fn gguf(tensors: u64) -> Vec<u8> {
+        let mut bytes = GGUF_MAGIC.to_vec();
+        bytes.extend(3u32.to_le_bytes());
+        bytes.extend(tensors.to_le_bytes());
+        bytes.extend(0u64.to_le_bytes());
+        for i in 0..tensors {
+            let name = format!("t{}", i);
+            bytes.extend((name.len() as u64).to_le_bytes());
+            bytes.extend(name.as_bytes());
+            bytes.extend(1u32.to_le_bytes());
+            bytes.extend(1u64.to_le_bytes());
+            bytes.extend(0u32.to_le_bytes());
+            bytes.extend((i * 4).to_le_bytes());
+        }
+        bytes
+    }
+
+    // A stand-in for `gguf-split` running `script` with the output path as `$3`
+    #[cfg(unix)]
+    fn program(dir: &Path, script: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("gguf-split");
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn names_the_merged_file() {
+        assert_eq!(
+            merged_path(Path::new("/models/llama-*-of-00003.gguf")),
+            Some(PathBuf::from("/models/llama.gguf"))
+        );
+        assert_eq!(
+            merged_path(Path::new("qwen-q4-*-of-00002.gguf")),
+            Some(PathBuf::from("qwen-q4.gguf"))
+        );
+        assert_eq!(merged_path(Path::new("/models/llama.gguf")), None);
+        assert_eq!(merged_path(Path::new("/models/llama-*-of-00003")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn merges_and_removes_the_parts() {
+        let dir = std::env::temp_dir().join(format!("ggufscan-test-{}-merge", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.gguf");
+        fs::write(&source, gguf(3)).unwrap();
+        let parts: Vec<PathBuf> = (1..=2)
+            .map(|i| dir.join(format!("model-{:05}-of-00002.gguf", i)))
+            .collect();
+        for part in &parts {
+            fs::write(part, b"GGUF").unwrap();
+        }
+        let output = dir.join("model.gguf");
+        let merge = |tensors| Merge {
+            program: program(&dir, &format!("cp {:?} \"$3\"", source)),
+            parts: parts.clone(),
+            output: output.clone(),
+            tensors,
+            delete_mode: DeleteMode::Permanent,
+            quarantine_dir: dir.join("quarantine"),
+            scan_options: None,
+        };
+
+        let error = merge(4).run().err();
+        assert_eq!(
+            error.as_deref(),
+            Some("the merged file has 3 tensors, expected 4")
+        );
+        assert!(!output.exists());
+        assert!(parts.iter().all(|part| part.exists()));
+
+        let result = merge(3).run().unwrap();
+        assert!(result.errors.is_empty());
+        assert!(result.file.is_none());
+        assert_eq!(fs::read(&output).unwrap(), gguf(3));
+        assert!(parts.iter().all(|part| !part.exists()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_failed_merges() {
+        let dir =
+            std::env::temp_dir().join(format!("ggufscan-test-{}-merge-errors", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("model-00001-of-00002.gguf");
+        let output = dir.join("model.gguf");
+
+        let failing = program(
+            &dir,
+            "echo 'reading model' >&2\necho 'split 2 is missing' >&2\necho >&2\ntouch \"$3\"\nexit 1",
+        );
+        assert_eq!(
+            merge_shards(&failing, &first, &output, 1),
+            Err("split 2 is missing".to_string())
+        );
+        assert!(!output.exists());
+
+        let silent = program(&dir, "exit 3");
+        assert_eq!(
+            merge_shards(&silent, &first, &output, 1),
+            Err("exit status: 3".to_string())
+        );
+
+        let not_gguf = program(&dir, "echo model > \"$3\"");
+        assert_eq!(
+            merge_shards(&not_gguf, &first, &output, 1),
+            Err("the merged file can't be read, not a GGUF file".to_string())
+        );
+        assert!(!output.exists());
+
+        let missing = dir.join("missing").to_str().unwrap().to_string();
+        assert_eq!(
+            merge_shards(&missing, &first, &output, 1),
+            Err(format!(
+                "{} not found, install llama.cpp or set gguf_split in the config",
+                missing
+            ))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
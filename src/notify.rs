@@ -0,0 +1,60 @@
+//! Desktop notifications through what the system comes with: `notify-send`
+//! on Linux and the BSDs, `osascript` on macOS and PowerShell on Windows.
+
+use std::{
+    io,
+    process::{Command, Stdio},
+    thread,
+};
+
+// Shown without waiting for it, a notification that can't be shown is only
+// an error when the command isn't there
+pub(crate) fn notify(title: &str, body: &str) -> io::Result<()> {
+    // The texts go in as variables, none of them need quoting that way
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            "Add-Type -AssemblyName System.Windows.Forms; \
+             $icon = New-Object System.Windows.Forms.NotifyIcon; \
+             $icon.Icon = [System.Drawing.SystemIcons]::Information; \
+             $icon.Visible = $true; \
+             $icon.ShowBalloonTip(10000, $env:GGUFSCAN_TITLE, $env:GGUFSCAN_BODY, 'Info'); \
+             Start-Sleep -Seconds 10; \
+             $icon.Dispose()",
+        ]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.args([
+            "-e",
+            "display notification (system attribute \"GGUFSCAN_BODY\") \
+             with title (system attribute \"GGUFSCAN_TITLE\")",
+        ]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=ggufscan", title, body]);
+        command
+    };
+    let mut child = command
+        .env("GGUFSCAN_TITLE", title)
+        .env("GGUFSCAN_BODY", body)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                e.kind(),
+                format!(
+                    "{} not found for the notification",
+                    command.get_program().to_string_lossy()
+                ),
+            ),
+            _ => e,
+        })?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! `ggufscan quarantine`: lists the files deleted into the quarantine
+//! directory, restores them to where they were or purges them for good.
+
+use anyhow::{Context, Result};
+use ggufscan_core::{
+    default_quarantine_dir, format_size, purge_quarantined, quarantined_files, restore_quarantined,
+    QuarantinedFile,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{config::Config, expand_home};
+
+// `quarantine_dir` from the config or the default in the data directory
+pub(crate) fn quarantine_dir(config: &Config) -> Option<PathBuf> {
+    config
+        .quarantine_dir
+        .as_deref()
+        .map(expand_home)
+        .or_else(default_quarantine_dir)
+}
+
+pub(crate) fn run_quarantine(
+    quarantine: &Path,
+    paths: &[PathBuf],
+    restore: bool,
+    purge: bool,
+) -> Result<()> {
+    let files = quarantined_below(quarantine, paths)?;
+    if files.is_empty() {
+        eprintln!("Nothing quarantined in {}", quarantine.display());
+        return Ok(());
+    }
+
+    let mut done = 0;
+    let mut done_size = 0;
+    for file in &files {
+        let result = if restore {
+            restore_quarantined(&file.original, quarantine)
+        } else if purge {
+            purge_quarantined(file, quarantine)
+        } else {
+            println!(
+                "{:<10} | {}",
+                format_size(file.size),
+                file.original.display()
+            );
+            continue;
+        };
+        match result {
+            Ok(()) => {
+                done += 1;
+                done_size += file.size;
+            }
+            Err(e) => eprintln!("Error with {}: {}", file.original.display(), e),
+        }
+    }
+
+    let total = files.iter().map(|file| file.size).sum();
+    if restore {
+        eprintln!(
+            "Restored {} of {} files, {}",
+            done,
+            files.len(),
+            format_size(done_size)
+        );
+    } else if purge {
+        eprintln!(
+            "Purged {} of {} files, freed {}",
+            done,
+            files.len(),
+            format_size(done_size)
+        );
+    } else {
+        eprintln!(
+            "{} files quarantined in {}, {} total",
+            files.len(),
+            quarantine.display(),
+            format_size(total)
+        );
+    }
+    Ok(())
+}
+
+// The quarantined files that were below one of `paths`, all of them when
+// there are none
+fn quarantined_below(quarantine: &Path, paths: &[PathBuf]) -> Result<Vec<QuarantinedFile>> {
+    let paths: Vec<PathBuf> = paths
+        .iter()
+        .map(std::path::absolute)
+        .collect::<io::Result<_>>()?;
+    Ok(quarantined_files(quarantine)
+        .with_context(|| format!("Error reading quarantine {}", quarantine.display()))?
+        .into_iter()
+        .filter(|file| paths.is_empty() || paths.iter().any(|path| file.original.starts_with(path)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ggufscan_core::quarantine_file;
+    use std::fs;
+
+    #[test]
+    fn restores_and_purges_below_paths() {
+        let dir =
+            std::env::temp_dir().join(format!("ggufscan-test-{}-quarantine", std::process::id()));
+        let quarantine = dir.join("quarantine");
+        let (llama, phi) = (dir.join("a/llama.gguf"), dir.join("b/phi.gguf"));
+        for path in [&llama, &phi] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"GGUF").unwrap();
+            quarantine_file(path, &quarantine).unwrap();
+        }
+        let originals = |paths: &[PathBuf]| -> Vec<PathBuf> {
+            let mut files: Vec<PathBuf> = quarantined_below(&quarantine, paths)
+                .unwrap()
+                .into_iter()
+                .map(|file| file.original)
+                .collect();
+            files.sort();
+            files
+        };
+        assert_eq!(originals(&[]), [llama.as_path(), phi.as_path()]);
+        assert_eq!(originals(&[dir.join("a")]), [llama.as_path()]);
+        assert!(originals(&[dir.join("c")]).is_empty());
+
+        run_quarantine(&quarantine, &[dir.join("a")], true, false).unwrap();
+        assert!(llama.exists());
+        assert_eq!(originals(&[]), [phi.as_path()]);
+
+        run_quarantine(&quarantine, &[], false, true).unwrap();
+        assert!(!phi.exists());
+        assert!(originals(&[]).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+//! Names for model files made from their metadata, for renaming the
+//! selected files in one go.
+
+use ggufscan_core::{FileInfo, MetadataValue, ModelFormat};
+use std::path::PathBuf;
+
+// Used when the config has no `name_template`
+pub(crate) const DEFAULT_NAME_TEMPLATE: &str = "{name}-{params}-{quant}.gguf";
+
+// A normalized file name, the template with `{name}`, `{arch}`, `{params}`
+// and `{quant}` filled in from the metadata. Spaces become dashes and
+// characters not allowed in file names on some system are left out.
+fn normalized_name(template: &str, file: &FileInfo) -> Result<String, String> {
+    let header = file
+        .header
+        .as_ref()
+        .filter(|_| file.format == ModelFormat::Gguf)
+        .ok_or("not a GGUF file")?;
+    let fields = [
+        (
+            "name",
+            header
+                .get("general.name")
+                .and_then(MetadataValue::as_str)
+                .map(str::to_string),
+        ),
+        ("arch", header.architecture().map(str::to_string)),
+        ("params", header.size_class()),
+        ("quant", header.quantization().map(str::to_string)),
+    ];
+    let mut name = template.to_string();
+    for (field, value) in fields {
+        let placeholder = format!("{{{}}}", field);
+        if !name.contains(&placeholder) {
+            continue;
+        }
+        let value = value.ok_or_else(|| format!("no {} in the metadata", field))?;
+        let value: String = value
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-")
+            .chars()
+            .filter(|c| !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+            .collect();
+        name = name.replace(&placeholder, &value);
+    }
+    if name.trim().is_empty() || name.contains(std::path::is_separator) {
+        return Err(format!("invalid name {}", name));
+    }
+    Ok(name)
+}
+
+// The new path of each file, or why it keeps its name. Files named that way
+// already are left out, and of two files getting the same name only the
+// first one is renamed.
+pub(crate) fn planned_renames<'a>(
+    template: &str,
+    files: impl Iterator<Item = &'a FileInfo>,
+) -> Vec<(PathBuf, Result<PathBuf, String>)> {
+    let mut targets: Vec<PathBuf> = Vec::new();
+    let mut renames = Vec::new();
+    for file in files {
+        let target = if file.shard.is_some() {
+            Err("part of a split model".to_string())
+        } else {
+            normalized_name(template, file).map(|name| file.path.with_file_name(name))
+        };
+        let target = target.and_then(|target| {
+            if target != file.path && (target.exists() || targets.contains(&target)) {
+                Err(format!("{} already exists", target.display()))
+            } else {
+                Ok(target)
+            }
+        });
+        match &target {
+            Ok(target) if *target == file.path => continue,
+            Ok(target) => targets.push(target.clone()),
+            Err(_) => {}
+        }
+        renames.push((file.path.clone(), target));
+    }
+    renames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ggufscan_core::{GgufHeader, Shard};
+    use std::{fs, path::Path};
+
+    fn model(path: &Path, name: &str) -> FileInfo {
+        FileInfo {
+            path: path.to_owned(),
+            size: 4 << 30,
+            root: PathBuf::from("/models"),
+            modified: None,
+            accessed: None,
+            format: ModelFormat::Gguf,
+            header: Some(GgufHeader {
+                version: 3,
+                tensor_count: 0,
+                metadata: vec![
+                    (
+                        "general.architecture".to_string(),
+                        MetadataValue::String("llama".to_string()),
+                    ),
+                    (
+                        "general.name".to_string(),
+                        MetadataValue::String(name.to_string()),
+                    ),
+                    ("general.file_type".to_string(), MetadataValue::U32(15)),
+                ],
+                parameter_count: 8_000_000_000,
+                tensor_type: None,
+                data_end: None,
+            }),
+            duplicate_group: None,
+            file_id: None,
+            links: 1,
+            symlink: false,
+            readonly: false,
+            source: None,
+            shard: Shard::parse(path, None),
+            corrupt: None,
+            packed: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn fills_in_the_template() {
+        let file = model(Path::new("/models/a.gguf"), "Meta Llama: 3 <Instruct>");
+        assert_eq!(
+            normalized_name(DEFAULT_NAME_TEMPLATE, &file).as_deref(),
+            Ok("Meta-Llama-3-Instruct-8B-Q4_K_M.gguf")
+        );
+        assert_eq!(
+            normalized_name("{arch}/{quant}.gguf", &file),
+            Err("invalid name llama/Q4_K_M.gguf".to_string())
+        );
+        let mut file = model(Path::new("/models/a.gguf"), "Llama");
+        file.header.as_mut().unwrap().metadata.remove(1);
+        assert_eq!(
+            normalized_name(DEFAULT_NAME_TEMPLATE, &file),
+            Err("no name in the metadata".to_string())
+        );
+        assert_eq!(
+            normalized_name("{arch}.gguf", &file).as_deref(),
+            Ok("llama.gguf")
+        );
+        file.format = ModelFormat::Safetensors;
+        assert_eq!(
+            normalized_name("{arch}.gguf", &file),
+            Err("not a GGUF file".to_string())
+        );
+    }
+
+    #[test]
+    fn plans_renames() {
+        let dir = std::env::temp_dir().join(format!("ggufscan-test-{}-rename", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Phi-8B-Q4_K_M.gguf"), b"GGUF").unwrap();
+        let files = [
+            model(&dir.join("Llama-8B-Q4_K_M.gguf"), "Llama"),
+            model(&dir.join("mistral-a.gguf"), "Mistral"),
+            model(&dir.join("mistral-b.gguf"), "Mistral"),
+            model(&dir.join("phi.gguf"), "Phi"),
+            model(&dir.join("qwen-00001-of-00002.gguf"), "Qwen"),
+        ];
+        for file in &files {
+            fs::write(&file.path, b"GGUF").unwrap();
+        }
+        let renames = planned_renames(DEFAULT_NAME_TEMPLATE, files.iter());
+        let taken = |name: &str| Err(format!("{} already exists", dir.join(name).display()));
+        assert_eq!(
+            renames,
+            [
+                (
+                    dir.join("mistral-a.gguf"),
+                    Ok(dir.join("Mistral-8B-Q4_K_M.gguf"))
+                ),
+                (dir.join("mistral-b.gguf"), taken("Mistral-8B-Q4_K_M.gguf")),
+                (dir.join("phi.gguf"), taken("Phi-8B-Q4_K_M.gguf")),
+                (
+                    dir.join("qwen-00001-of-00002.gguf"),
+                    Err("part of a split model".to_string())
+                ),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,351 @@
+//! When `ggufscan daemon` scans, a cron expression with the five crontab
+//! fields (minute, hour, day of month, month and day of week) in local time,
+//! or one of `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly`.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone, Timelike};
+use std::{fmt, str::FromStr};
+
+// Long enough to reach 29 February from any day, schedules like `0 0 31 2 *`
+// give up after it
+const SEARCH_YEARS: i64 = 8;
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    text: String,
+    // One bit per allowed value
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Like cron, a day matches either field when neither starts with `*`
+    // and both otherwise
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// The first time after `time` the schedule runs at.
+    pub fn next_after(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = time.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let end = start + Duration::days(366 * SEARCH_YEARS);
+        let mut time = start;
+        while time < end {
+            if !has(self.months, time.month()) {
+                time = start_of_next_month(time)?;
+            } else if !self.day_matches(time) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !has(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else if let Some(local) = Local.from_local_datetime(&time).earliest() {
+                return Some(local);
+            } else {
+                // Skipped over by the clock changing
+                time += Duration::minutes(1);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, time: NaiveDateTime) -> bool {
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let expression = match text.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "expected minute, hour, day of month, month and day of week, got {:?}",
+                text
+            ));
+        };
+        // Sunday is 0 and 7
+        let weekdays = parse_field(weekday, 0, 7, WEEKDAY_NAMES, 0)?;
+        Ok(Self {
+            text: text.trim().to_string(),
+            minutes: parse_field(minute, 0, 59, &[], 0)?,
+            hours: parse_field(hour, 0, 23, &[], 0)?,
+            days: parse_field(day, 1, 31, &[], 0)?,
+            months: parse_field(month, 1, 12, MONTH_NAMES, 1)?,
+            weekdays: (weekdays | (weekdays >> 7)) & 0x7f,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn start_of_next_month(time: NaiveDateTime) -> Option<NaiveDateTime> {
+    let (year, month) = match time.month() {
+        12 => (time.year() + 1, 1),
+        month => (time.year(), month + 1),
+    };
+    chrono::NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)
+}
+
+// Lists of `*`, `5`, `1-5` and `mon-fri`, each with an optional `/step`.
+// `names` are the values from `first_name` on.
+fn parse_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+    first_name: u32,
+) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let named = names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(text))
+            .map(|index| index as u32 + first_name);
+        match named.or_else(|| text.parse().ok()) {
+            Some(value) if (min..=max).contains(&value) => Ok(value),
+            _ => Err(format!(
+                "{:?} isn't between {} and {} in {:?}",
+                text, min, max, field
+            )),
+        }
+    };
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|&step| step > 0)
+                    .ok_or_else(|| format!("invalid step {:?} in {:?}", step, field))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // `5/15` runs from 5 on
+                None if step > 1 => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start > end {
+            return Err(format!("{:?} runs backwards in {:?}", range, field));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn next(schedule: &str, time: DateTime<Local>) -> DateTime<Local> {
+        schedule
+            .parse::<Schedule>()
+            .unwrap()
+            .next_after(time)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_fields() {
+        let schedule: Schedule = "*/15 9-17 1,15 * mon-fri".parse().unwrap();
+        assert_eq!(schedule.minutes, 1 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(schedule.hours, (9..=17).map(|hour| 1 << hour).sum::<u64>());
+        assert_eq!(schedule.days, 1 << 1 | 1 << 15);
+        assert_eq!(
+            schedule.months,
+            (1..=12).map(|month| 1 << month).sum::<u64>()
+        );
+        assert_eq!(schedule.weekdays, 0b011_1110);
+        assert!(!schedule.any_day && !schedule.any_weekday);
+        assert_eq!(schedule.to_string(), "*/15 9-17 1,15 * mon-fri");
+    }
+
+    #[test]
+    fn parses_steps_and_names() {
+        let schedule: Schedule = "5/20 0-6/3 * JAN,jul-aug *".parse().unwrap();
+        assert_eq!(schedule.minutes, 1 << 5 | 1 << 25 | 1 << 45);
+        assert_eq!(schedule.hours, 1 | 1 << 3 | 1 << 6);
+        assert_eq!(schedule.months, 1 << 1 | 1 << 7 | 1 << 8);
+        assert!(schedule.any_day && schedule.any_weekday);
+    }
+
+    #[test]
+    fn sunday_is_0_and_7() {
+        let zero: Schedule = "0 0 * * 0".parse().unwrap();
+        let seven: Schedule = "0 0 * * 7".parse().unwrap();
+        let name: Schedule = "0 0 * * sun".parse().unwrap();
+        assert_eq!(zero.weekdays, 1);
+        assert_eq!(seven.weekdays, 1);
+        assert_eq!(name.weekdays, 1);
+        let all: Schedule = "0 0 * * 0-7".parse().unwrap();
+        assert_eq!(all.weekdays, 0x7f);
+    }
+
+    #[test]
+    fn expands_macros() {
+        for (name, expression) in [
+            ("@yearly", "0 0 1 1 *"),
+            ("@annually", "0 0 1 1 *"),
+            ("@monthly", "0 0 1 * *"),
+            ("@weekly", "0 0 * * 0"),
+            ("@daily", "0 0 * * *"),
+            ("@midnight", "0 0 * * *"),
+            ("@hourly", "0 * * * *"),
+        ] {
+            let schedule: Schedule = name.parse().unwrap();
+            let expanded: Schedule = expression.parse().unwrap();
+            assert_eq!(schedule.minutes, expanded.minutes, "{}", name);
+            assert_eq!(schedule.hours, expanded.hours, "{}", name);
+            assert_eq!(schedule.days, expanded.days, "{}", name);
+            assert_eq!(schedule.months, expanded.months, "{}", name);
+            assert_eq!(schedule.weekdays, expanded.weekdays, "{}", name);
+            assert_eq!(schedule.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * 32 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "5-1 * * * *",
+            "*/0 * * * *",
+            "*/x * * * *",
+            "* * * foo *",
+            "1,,2 * * * *",
+            "@often",
+        ] {
+            assert!(expression.parse::<Schedule>().is_err(), "{:?}", expression);
+        }
+    }
+
+    #[test]
+    fn next_after_is_strictly_later() {
+        let time = at(2026, 1, 14, 3, 0);
+        assert_eq!(next("0 3 * * *", time), at(2026, 1, 15, 3, 0));
+        // Seconds into the minute still wait for the next one
+        let time = time + Duration::seconds(30);
+        assert_eq!(next("* * * * *", time), at(2026, 1, 14, 3, 1));
+    }
+
+    #[test]
+    fn next_after_steps() {
+        let time = at(2026, 1, 14, 7, 31);
+        assert_eq!(next("*/15 * * * *", time), at(2026, 1, 14, 7, 45));
+        assert_eq!(
+            next("*/15 * * * *", at(2026, 1, 14, 7, 50)),
+            at(2026, 1, 14, 8, 0)
+        );
+        assert_eq!(next("0 */6 * * *", time), at(2026, 1, 14, 12, 0));
+        assert_eq!(next("@hourly", time), at(2026, 1, 14, 8, 0));
+    }
+
+    #[test]
+    fn next_after_crosses_month_and_year_ends() {
+        assert_eq!(
+            next("0 0 1 * *", at(2026, 1, 31, 12, 0)),
+            at(2026, 2, 1, 0, 0)
+        );
+        assert_eq!(
+            next("30 12 31 * *", at(2026, 1, 31, 13, 0)),
+            at(2026, 3, 31, 12, 30)
+        );
+        assert_eq!(next("@yearly", at(2026, 7, 14, 0, 0)), at(2027, 1, 1, 0, 0));
+        assert_eq!(
+            next("59 23 31 12 *", at(2026, 12, 31, 23, 59)),
+            at(2027, 12, 31, 23, 59)
+        );
+    }
+
+    #[test]
+    fn next_after_finds_leap_days() {
+        assert_eq!(
+            next("0 0 29 2 *", at(2026, 3, 1, 0, 0)),
+            at(2028, 2, 29, 0, 0)
+        );
+        assert!("0 0 30 2 *"
+            .parse::<Schedule>()
+            .unwrap()
+            .next_after(at(2026, 1, 1, 0, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn next_after_weekdays() {
+        // 2026-01-14 is a Wednesday
+        let time = at(2026, 1, 14, 12, 0);
+        assert_eq!(next("0 9 * * mon-fri", time), at(2026, 1, 15, 9, 0));
+        assert_eq!(next("0 9 * * sat,sun", time), at(2026, 1, 17, 9, 0));
+        assert_eq!(next("0 0 * * 7", time), at(2026, 1, 18, 0, 0));
+        assert_eq!(next("@weekly", time), at(2026, 1, 18, 0, 0));
+    }
+
+    #[test]
+    fn restricted_day_and_weekday_match_either() {
+        // The 20th or any Friday, whichever comes first
+        let time = at(2026, 1, 14, 12, 0);
+        assert_eq!(next("0 0 20 * fri", time), at(2026, 1, 16, 0, 0));
+        assert_eq!(next("0 0 15 * fri", time), at(2026, 1, 15, 0, 0));
+    }
+
+    #[test]
+    fn starred_day_or_weekday_matches_both() {
+        let time = at(2026, 1, 14, 12, 0);
+        // Every 10th day of the month from the 1st, on any day of the week
+        assert_eq!(next("0 0 */10 * *", time), at(2026, 1, 21, 0, 0));
+        // Fridays only, the day of the month doesn't widen it
+        assert_eq!(next("0 0 * * fri", time), at(2026, 1, 16, 0, 0));
+        // Both have to match when the day of the week is a step of `*`
+        assert_eq!(next("0 0 13 * */5", time), at(2026, 2, 13, 0, 0));
+    }
+}
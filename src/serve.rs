@@ -0,0 +1,724 @@
+//! `ggufscan serve`: scans the roots in the background and answers a small
+//! JSON API over HTTP, the inventory for dashboards and scripts to read.
+//!
+//! - `GET /api/status`: whether a scan is running and when the last one ended
+//! - `GET /api/files`: the files found, as `--output json` prints them
+//! - `GET /api/metadata?path=…`: the GGUF metadata of one of them
+//! - `POST /api/rescan`: scans again, the old list is served until it's done
+//! - `POST /api/delete` with `{"paths": […]}`: deletes files of the list
+//!
+//! Requests are answered one per connection and up to 64 connections at a
+//! time, which is plenty for a handful of clients polling the inventory.
+
+use anyhow::{Context, Result};
+use ggufscan_core::{
+    export_files, file_users, percent_decode, reclaimable_size, unix_seconds, ExportFormat,
+    FileInfo, MetadataValue, ScanMessage, ScanOptions,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashSet,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    check_roots, config::Config, configured_roots, quarantine_dir, remove_path, spawn_scan,
+    DeleteMode, ServeArgs,
+};
+
+// Requests are small, anything bigger isn't one of ours
+const MAX_HEAD_LEN: u64 = 16 * 1024;
+const MAX_BODY_LEN: u64 = 1024 * 1024;
+// A client that stops sending holds up only its own thread, and not forever
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// Connections answered at once, more are turned away until some are done
+const MAX_CONNECTIONS: usize = 64;
+
+// What the last scan found, replaced as a whole once a rescan is done
+#[derive(Default)]
+struct Inventory {
+    files: Vec<FileInfo>,
+    errors: Vec<String>,
+    scanning: bool,
+    finished: Option<SystemTime>,
+    // Deleted while a scan was running, which may still have seen them
+    deleted: HashSet<PathBuf>,
+}
+
+struct Server {
+    options: ScanOptions,
+    roots: Vec<PathBuf>,
+    token: Option<String>,
+    allow_origin: Option<String>,
+    delete_mode: DeleteMode,
+    quarantine: PathBuf,
+    inventory: Mutex<Inventory>,
+    connections: AtomicUsize,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+struct Response {
+    status: u16,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, value: Value) -> Self {
+        Self {
+            status,
+            body: serde_json::to_vec_pretty(&value).unwrap_or_default(),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(status, json!({ "error": message.into() }))
+    }
+}
+
+#[derive(Deserialize)]
+struct DeleteRequest {
+    paths: Vec<PathBuf>,
+}
+
+pub(crate) fn run_server(config: &Config, args: &ServeArgs) -> Result<()> {
+    let roots = if args.paths.is_empty() {
+        configured_roots(config)?
+    } else {
+        args.paths.clone()
+    };
+    check_roots(&roots)?;
+    // Objects have no trash to go to
+    let object_storage = roots.iter().any(|root| ggufscan_core::is_s3_path(root));
+    let delete_mode = if object_storage {
+        DeleteMode::Permanent
+    } else {
+        args.delete.delete_mode(config)
+    };
+    let quarantine = quarantine_dir(config);
+    anyhow::ensure!(
+        delete_mode != DeleteMode::Quarantine || quarantine.is_some(),
+        "No quarantine directory found, set quarantine_dir in the config"
+    );
+    let token = args
+        .token
+        .clone()
+        .or_else(|| std::env::var("GGUFSCAN_TOKEN").ok())
+        .filter(|token| !token.is_empty());
+
+    let options = args.scan.scan_options(config, roots.clone())?;
+    let listener = TcpListener::bind((args.bind.as_str(), args.port))
+        .with_context(|| format!("Error listening on {}:{}", args.bind, args.port))?;
+    let server = Arc::new(Server {
+        options,
+        roots,
+        token,
+        allow_origin: args.allow_origin.clone(),
+        delete_mode,
+        quarantine: quarantine.unwrap_or_default(),
+        inventory: Mutex::new(Inventory::default()),
+        connections: AtomicUsize::new(0),
+    });
+    server.start_scan();
+
+    eprintln!(
+        "Serving the models of {} on http://{}",
+        server
+            .roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        listener.local_addr()?
+    );
+    if server.token.is_none() {
+        eprintln!("No --token given, rescans are open to anyone and deleting is disabled");
+    }
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        if server.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+            stream.set_write_timeout(Some(READ_TIMEOUT)).ok();
+            let busy = Response::error(503, "Too many connections, try again later");
+            server.write_response(&mut stream, &busy).ok();
+            continue;
+        }
+        let server = server.clone();
+        thread::spawn(move || {
+            // Given back even when handling the request panics
+            let _slot = ConnectionSlot(&server.connections);
+            server.handle_connection(stream);
+        });
+    }
+    Ok(())
+}
+
+// One of the `MAX_CONNECTIONS`, taken until dropped
+struct ConnectionSlot<'a>(&'a AtomicUsize);
+
+impl Drop for ConnectionSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Server {
+    // Runs a scan on a thread of its own unless one is running already
+    fn start_scan(self: &Arc<Self>) -> bool {
+        {
+            let mut inventory = self.inventory.lock().unwrap();
+            if inventory.scanning {
+                return false;
+            }
+            inventory.scanning = true;
+        }
+        let server = self.clone();
+        thread::spawn(move || {
+            let (mut files, mut errors) = (Vec::new(), Vec::new());
+            for message in spawn_scan(server.options.clone()).1 {
+                match message {
+                    ScanMessage::File(file) => files.push(*file),
+                    ScanMessage::Error(error) => errors.push(error),
+                    ScanMessage::Done | ScanMessage::Cancelled => break,
+                    _ => {}
+                }
+            }
+            files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+            let mut inventory = server.inventory.lock().unwrap();
+            let deleted = std::mem::take(&mut inventory.deleted);
+            files.retain(|file| !deleted.contains(&file.path));
+            inventory.files = files;
+            inventory.errors = errors;
+            inventory.scanning = false;
+            inventory.finished = Some(SystemTime::now());
+        });
+        true
+    }
+
+    fn handle_connection(self: &Arc<Self>, mut stream: TcpStream) {
+        stream.set_read_timeout(Some(READ_TIMEOUT)).ok();
+        let response = match read_request(&stream) {
+            Ok(request) => self.handle(&request),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Response::error(400, e.to_string()),
+            // The client went away or stopped sending
+            Err(_) => return,
+        };
+        self.write_response(&mut stream, &response).ok();
+    }
+
+    fn handle(self: &Arc<Self>, request: &Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            // The browser asks before sending the token along
+            ("OPTIONS", _) => Response {
+                status: 204,
+                body: Vec::new(),
+            },
+            ("GET", "/api/status") => self.status(),
+            ("GET", "/api/files") => self.files(),
+            ("GET", "/api/metadata") => self.metadata(request),
+            ("POST", "/api/rescan") => match self.authorize(request) {
+                Some(refused) => refused,
+                None if self.start_scan() => Response::json(202, json!({ "scanning": true })),
+                None => Response::error(409, "A scan is already running"),
+            },
+            ("POST", "/api/delete") => {
+                if self.token.is_none() {
+                    return Response::error(
+                        403,
+                        "Deleting is disabled, start the server with --token",
+                    );
+                }
+                match self.authorize(request) {
+                    Some(refused) => refused,
+                    None => self.delete(request),
+                }
+            }
+            (_, "/api/status" | "/api/files" | "/api/metadata" | "/api/rescan" | "/api/delete") => {
+                Response::error(405, format!("{} isn't supported here", request.method))
+            }
+            _ => Response::error(404, format!("No such endpoint {}", request.path)),
+        }
+    }
+
+    fn status(&self) -> Response {
+        let inventory = self.inventory.lock().unwrap();
+        Response::json(
+            200,
+            json!({
+                "roots": self.roots,
+                "scanning": inventory.scanning,
+                "last_scan": inventory.finished.map(unix_seconds),
+                "files": inventory.files.len(),
+                "total_size": reclaimable_size(inventory.files.iter()),
+                "errors": inventory.errors,
+            }),
+        )
+    }
+
+    fn files(&self) -> Response {
+        let inventory = self.inventory.lock().unwrap();
+        let mut body = Vec::new();
+        match export_files(&mut body, ExportFormat::Json, &self.roots, &inventory.files) {
+            Ok(()) => Response { status: 200, body },
+            Err(e) => Response::error(500, e.to_string()),
+        }
+    }
+
+    fn metadata(&self, request: &Request) -> Response {
+        let Some(path) = request.query("path") else {
+            return Response::error(400, "Give the file as ?path=");
+        };
+        let inventory = self.inventory.lock().unwrap();
+        let Some(file) = inventory
+            .files
+            .iter()
+            .find(|file| file.path == Path::new(path))
+        else {
+            return Response::error(404, format!("{} isn't in the inventory", path));
+        };
+        let metadata: serde_json::Map<String, Value> = file
+            .header
+            .iter()
+            .flat_map(|header| &header.metadata)
+            .map(|(key, value)| (key.clone(), metadata_json(value)))
+            .collect();
+        Response::json(
+            200,
+            json!({
+                "path": file.path,
+                "format": file.format,
+                "gguf_version": file.header.as_ref().map(|header| header.version),
+                "tensor_count": file.header.as_ref().map(|header| header.tensor_count),
+                "metadata": metadata,
+            }),
+        )
+    }
+
+    // Only files of the inventory are deleted, and not while a process has
+    // them open
+    fn delete(&self, request: &Request) -> Response {
+        let mut paths = match serde_json::from_slice::<DeleteRequest>(&request.body) {
+            Ok(delete) => delete.paths,
+            Err(e) => return Response::error(400, format!("Expected {{\"paths\": [...]}}, {}", e)),
+        };
+        // A path given twice would fail the second time, being gone
+        let mut seen = HashSet::new();
+        paths.retain(|path| seen.insert(path.clone()));
+        let files: Vec<(PathBuf, bool)> = {
+            let inventory = self.inventory.lock().unwrap();
+            paths
+                .iter()
+                .map(|path| {
+                    let bundle = inventory
+                        .files
+                        .iter()
+                        .find(|file| file.path == *path)
+                        .map(|file| file.format.is_bundle());
+                    (path.clone(), bundle)
+                })
+                .filter_map(|(path, bundle)| Some((path, bundle?)))
+                .collect()
+        };
+        let mut failed: Vec<Value> = paths
+            .iter()
+            .filter(|path| !files.iter().any(|(file, _)| file == *path))
+            .map(|path| json!({ "path": path, "error": "not in the inventory" }))
+            .collect();
+        let in_use = file_users(
+            &files
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>(),
+        );
+
+        let mut deleted = Vec::new();
+        for (path, is_dir) in files {
+            if let Some(users) = in_use.get(&path) {
+                let users: Vec<&str> = users.iter().map(|user| user.name.as_str()).collect();
+                failed.push(json!({
+                    "path": path,
+                    "error": format!("{} has it open", users.join(", ")),
+                }));
+                continue;
+            }
+            match remove_path(self.delete_mode, &self.quarantine, &path, is_dir) {
+                Ok(()) => deleted.push(path),
+                Err(e) => failed.push(json!({ "path": path, "error": e.to_string() })),
+            }
+        }
+
+        let mut inventory = self.inventory.lock().unwrap();
+        let (removed, kept) = std::mem::take(&mut inventory.files)
+            .into_iter()
+            .partition::<Vec<_>, _>(|file| deleted.contains(&file.path));
+        inventory.files = kept;
+        if inventory.scanning {
+            inventory.deleted.extend(deleted.iter().cloned());
+        }
+        Response::json(
+            200,
+            json!({
+                "deleted": deleted,
+                "failed": failed,
+                "freed": reclaimable_size(removed.iter()),
+            }),
+        )
+    }
+
+    fn authorize(&self, request: &Request) -> Option<Response> {
+        let token = self.token.as_ref()?;
+        let given = request
+            .header("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match given {
+            Some(given) if tokens_match(given, token) => None,
+            Some(_) => Some(Response::error(403, "Wrong token")),
+            None => Some(Response::error(
+                401,
+                "Send the token as Authorization: Bearer TOKEN",
+            )),
+        }
+    }
+
+    fn write_response(&self, stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            response.status,
+            reason(response.status),
+            response.body.len()
+        );
+        if !response.body.is_empty() {
+            head.push_str("Content-Type: application/json\r\n");
+        }
+        if let Some(origin) = &self.allow_origin {
+            head.push_str(&format!(
+                "Access-Control-Allow-Origin: {}\r\n\
+                 Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+                 Access-Control-Allow-Headers: Authorization, Content-Type\r\n\
+                 Vary: Origin\r\n",
+                origin
+            ));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&response.body)?;
+        stream.flush()
+    }
+}
+
+// The request line, headers and a body of `Content-Length` bytes
+fn read_request(stream: impl Read) -> io::Result<Request> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut reader = BufReader::new(stream);
+    let mut head_left = MAX_HEAD_LEN;
+    let mut line = String::new();
+    read_head_line(&mut reader, &mut head_left, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("Malformed request line"));
+    };
+    let method = method.to_string();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = path.to_string();
+    // Values come `%`-encoded, spaces also as `+`
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+
+    let mut headers = Vec::new();
+    loop {
+        if read_head_line(&mut reader, &mut head_left, &mut line)? == 0 {
+            return Err(invalid("Request headers cut off or too long"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid("Malformed header"))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    let mut request = Request {
+        method,
+        path,
+        query,
+        headers,
+        body: Vec::new(),
+    };
+    let len: u64 = match request.header("Content-Length") {
+        Some(len) => len
+            .parse()
+            .map_err(|_| invalid("Malformed Content-Length"))?,
+        None => 0,
+    };
+    if len > MAX_BODY_LEN {
+        return Err(invalid("Request body too big"));
+    }
+    let mut body = vec![0u8; len as usize];
+    reader.read_exact(&mut body)?;
+    request.body = body;
+    Ok(request)
+}
+
+// The head as a whole gets no more than `MAX_HEAD_LEN` bytes, a line that
+// doesn't end within them reads as cut off
+fn read_head_line(
+    reader: &mut BufReader<impl Read>,
+    left: &mut u64,
+    line: &mut String,
+) -> io::Result<usize> {
+    line.clear();
+    let read = reader.take(*left).read_line(line)?;
+    *left -= read as u64;
+    Ok(read)
+}
+
+// Plain JSON values, arrays with the items the header kept and how many
+// there are in the file
+fn metadata_json(value: &MetadataValue) -> Value {
+    match value {
+        MetadataValue::U8(v) => json!(v),
+        MetadataValue::I8(v) => json!(v),
+        MetadataValue::U16(v) => json!(v),
+        MetadataValue::I16(v) => json!(v),
+        MetadataValue::U32(v) => json!(v),
+        MetadataValue::I32(v) => json!(v),
+        MetadataValue::F32(v) => json!(v),
+        MetadataValue::Bool(v) => json!(v),
+        MetadataValue::String(v) => json!(v),
+        MetadataValue::Array { len, items } => json!({
+            "len": len,
+            "items": items.iter().map(metadata_json).collect::<Vec<_>>(),
+        }),
+        MetadataValue::U64(v) => json!(v),
+        MetadataValue::I64(v) => json!(v),
+        MetadataValue::F64(v) => json!(v),
+    }
+}
+
+// Compares every byte, so the time taken doesn't tell how much matched
+fn tokens_match(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> io::Result<Request> {
+        read_request(text.as_bytes())
+    }
+
+    fn invalid(text: &str) -> String {
+        let error = parse(text).err().expect("an invalid request");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        error.to_string()
+    }
+
+    #[test]
+    fn reads_requests() {
+        let request = parse(
+            "GET /api/metadata?path=%2Fm%20a.gguf&raw&x=1=2 HTTP/1.1\r\n\
+             Host: localhost\r\n\
+             authorization:  Bearer secret \r\n\
+             \r\n",
+        )
+        .unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/api/metadata");
+        assert_eq!(request.query("path"), Some("/m a.gguf"));
+        assert_eq!(request.query("raw"), Some(""));
+        assert_eq!(request.query("x"), Some("1=2"));
+        assert_eq!(request.header("Authorization"), Some("Bearer secret"));
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn reads_the_body_by_its_length() {
+        let request = parse(
+            "POST /api/delete HTTP/1.1\n\
+             Content-Length: 13\n\
+             \n\
+             {\"paths\": []}trailing",
+        )
+        .unwrap();
+        assert_eq!(request.body, b"{\"paths\": []}");
+
+        let error = parse("POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort")
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        assert!(invalid("").contains("request line"));
+        assert!(invalid("GET /\r\n\r\n").contains("request line"));
+        assert!(invalid("GET / HTTP/1.1\r\nHost: x\r\n").contains("cut off"));
+        assert!(invalid("GET / HTTP/1.1\r\nbroken\r\n\r\n").contains("Malformed header"));
+        assert!(invalid("POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n").contains("Content-Length"));
+        let too_big = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_LEN + 1
+        );
+        assert!(invalid(&too_big).contains("too big"));
+    }
+
+    #[test]
+    fn limits_the_head() {
+        let long_line = format!(
+            "GET /{} HTTP/1.1\r\n\r\n",
+            "a".repeat(MAX_HEAD_LEN as usize)
+        );
+        assert!(invalid(&long_line).contains("request line"));
+        let many_headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-Header: value\r\n".repeat(MAX_HEAD_LEN as usize / 17)
+        );
+        assert!(invalid(&many_headers).contains("too long"));
+    }
+
+    #[test]
+    fn deletes_paths_given_twice_once() {
+        let dir = std::env::temp_dir().join(format!("ggufscan-test-{}-serve", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("model.gguf");
+        std::fs::write(&path, b"GGUF").unwrap();
+        let file = FileInfo {
+            path: path.clone(),
+            size: 4,
+            root: dir.clone(),
+            modified: None,
+            accessed: None,
+            format: ggufscan_core::ModelFormat::Gguf,
+            header: None,
+            duplicate_group: None,
+            file_id: None,
+            links: 1,
+            symlink: false,
+            readonly: false,
+            source: None,
+            shard: None,
+            corrupt: None,
+            packed: Vec::new(),
+        };
+        let server = Server {
+            options: ScanOptions {
+                roots: vec![dir.clone()],
+                excludes: ggufscan_core::globset::GlobSet::empty(),
+                find_duplicates: false,
+                cache: None,
+                watch: false,
+                one_file_system: false,
+                follow_symlinks: false,
+                skip_network_mounts: false,
+                default_excludes: true,
+                max_depth: None,
+                min_size: 0,
+                older_than: None,
+                deep: false,
+                files: Vec::new(),
+                priority_dirs: Vec::new(),
+                scan_archives: false,
+            },
+            roots: vec![dir.clone()],
+            token: Some("secret".to_string()),
+            allow_origin: None,
+            delete_mode: DeleteMode::Permanent,
+            quarantine: PathBuf::new(),
+            inventory: Mutex::new(Inventory {
+                files: vec![file],
+                ..Inventory::default()
+            }),
+            connections: AtomicUsize::new(0),
+        };
+        let request = Request {
+            method: "POST".to_string(),
+            path: "/api/delete".to_string(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: serde_json::to_vec(&json!({ "paths": [path, path, dir.join("other.gguf")] }))
+                .unwrap(),
+        };
+        let response = server.delete(&request);
+        let exists = path.exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(response.status, 200);
+        let body: Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(body["deleted"], json!([path]));
+        assert_eq!(body["failed"].as_array().unwrap().len(), 1);
+        assert_eq!(body["failed"][0]["error"], "not in the inventory");
+        assert!(!exists);
+        assert!(server.inventory.lock().unwrap().files.is_empty());
+    }
+
+    #[test]
+    fn compares_tokens() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret2", "secret"));
+        assert!(!tokens_match("", "secret"));
+    }
+}
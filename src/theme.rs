@@ -0,0 +1,116 @@
+//! Colors of the TUI, one of the built-in themes with the colors set in the
+//! config file on top.
+
+use ratatui::style::Color;
+use serde::{de, Deserialize, Deserializer};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    // Background of the highlighted row
+    pub highlight: Color,
+    pub selected: Color,
+    pub duplicate: Color,
+    // Write-protected files
+    pub protected: Color,
+    // Metadata keys in the details pane
+    pub accent: Color,
+    pub progress: Color,
+    pub warning: Color,
+    // Borders of popups confirming something destructive
+    pub danger: Color,
+}
+
+impl Theme {
+    pub fn new(name: ThemeName, colors: &ThemeColors) -> Self {
+        let base = match name {
+            ThemeName::Dark => Self {
+                highlight: Color::DarkGray,
+                selected: Color::LightGreen,
+                duplicate: Color::Yellow,
+                protected: Color::LightMagenta,
+                accent: Color::Cyan,
+                progress: Color::Green,
+                warning: Color::Yellow,
+                danger: Color::Red,
+            },
+            ThemeName::Light => Self {
+                highlight: Color::Gray,
+                selected: Color::Green,
+                duplicate: Color::Rgb(160, 110, 0),
+                protected: Color::Magenta,
+                accent: Color::Blue,
+                progress: Color::Green,
+                warning: Color::Rgb(160, 110, 0),
+                danger: Color::Red,
+            },
+            ThemeName::HighContrast => Self {
+                highlight: Color::Blue,
+                selected: Color::LightGreen,
+                duplicate: Color::LightYellow,
+                protected: Color::LightMagenta,
+                accent: Color::LightCyan,
+                progress: Color::White,
+                warning: Color::LightYellow,
+                danger: Color::LightRed,
+            },
+        };
+        Self {
+            highlight: colors.highlight.unwrap_or(base.highlight),
+            selected: colors.selected.unwrap_or(base.selected),
+            duplicate: colors.duplicate.unwrap_or(base.duplicate),
+            protected: colors.protected.unwrap_or(base.protected),
+            accent: colors.accent.unwrap_or(base.accent),
+            progress: colors.progress.unwrap_or(base.progress),
+            warning: colors.warning.unwrap_or(base.warning),
+            danger: colors.danger.unwrap_or(base.danger),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(ThemeName::default(), &ThemeColors::default())
+    }
+}
+
+// The `[colors]` table of the config, names like `yellow`, `#ffaa00` or
+// ANSI numbers
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeColors {
+    #[serde(deserialize_with = "deserialize_color")]
+    pub highlight: Option<Color>,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub selected: Option<Color>,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub duplicate: Option<Color>,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub protected: Option<Color>,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub accent: Option<Color>,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub progress: Option<Color>,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub warning: Option<Color>,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub danger: Option<Color>,
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Color>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Color::from_str(&name)
+        .map(Some)
+        .map_err(|_| de::Error::custom(format!("unknown color {:?}", name)))
+}